@@ -1,6 +1,15 @@
+use base64::Engine;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use std::collections::HashMap;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::str::FromStr;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CityJSON {
@@ -23,6 +32,11 @@ pub struct CityJSON {
     #[serde(flatten)]
     other: serde_json::Value,
 }
+impl Default for CityJSON {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 impl CityJSON {
     pub fn new() -> Self {
         let co: HashMap<String, CityObject> = HashMap::new();
@@ -41,6 +55,16 @@ impl CityJSON {
             other: json!(null),
         }
     }
+    pub fn with_capacity(objects: usize, vertices: usize) -> Self {
+        let mut cj = CityJSON::new();
+        cj.reserve(objects, vertices);
+        cj
+    }
+    pub fn reserve(&mut self, objects: usize, vertices: usize) {
+        //-- for collect, to avoid reallocating for each feature added
+        self.city_objects.reserve(objects);
+        self.vertices.reserve(vertices);
+    }
     pub fn get_empty_copy(&self) -> Self {
         let co: HashMap<String, CityObject> = HashMap::new();
         let v: Vec<Vec<i64>> = Vec::new();
@@ -57,9 +81,302 @@ impl CityJSON {
             extensions: self.extensions.clone(),
         }
     }
+    pub fn get_metadata(&self) -> CityJSON {
+        //-- first line of a CityJSONSeq
+        let mut cj1: CityJSON = self.get_empty_copy();
+        //-- if geometry-templates have material/textures then these need to be added to 1st line
+        if let Some(x) = &self.geometry_templates {
+            let mut gts2: GeometryTemplates = x.clone();
+            let mut m_oldnew: HashMap<usize, usize> = HashMap::new();
+            let mut t_oldnew: HashMap<usize, usize> = HashMap::new();
+            let mut t_v_oldnew: HashMap<usize, usize> = HashMap::new();
+            for g in &mut gts2.templates {
+                g.update_material(&mut m_oldnew);
+                g.update_texture(&mut t_oldnew, &mut t_v_oldnew);
+            }
+            cj1.appearance = self.slice_appearance(&m_oldnew, &t_oldnew, &t_v_oldnew);
+            //-- the templates refer to the sliced appearance
+            cj1.geometry_templates = Some(gts2);
+        }
+        cj1
+    }
+    pub fn get_cjfeature(&self, key: &str) -> Option<CityJSONFeature> {
+        let cos = &self.city_objects;
+        let co = cos.get(key)?;
+        let mut cjf = CityJSONFeature::new();
+        let mut co2: CityObject = co.clone();
+        let mut g_vi_oldnew: HashMap<usize, usize> = HashMap::new();
+        let mut m_oldnew: HashMap<usize, usize> = HashMap::new();
+        let mut t_oldnew: HashMap<usize, usize> = HashMap::new();
+        let mut t_v_oldnew: HashMap<usize, usize> = HashMap::new();
+        if let Some(x) = &mut co2.geometry {
+            for g in x.iter_mut() {
+                g.update_geometry_boundaries(&mut g_vi_oldnew);
+                g.update_material(&mut m_oldnew);
+                g.update_texture(&mut t_oldnew, &mut t_v_oldnew);
+            }
+        }
+        cjf.add_co(key.to_string(), co2);
+        cjf.id = key.to_string();
+
+        //-- TODO: to fix: children-of-children?
+        //-- process all the children (only one-level lower), the members of a
+        //-- CityObjectGroup are not: they are in their own features
+        let children = if co.is_group() {
+            Vec::new()
+        } else {
+            co.get_children_keys()
+        };
+        for childkey in children {
+            let coc = cos.get(&childkey).unwrap();
+            let mut coc2: CityObject = coc.clone();
+            if let Some(x) = &mut coc2.geometry {
+                for g in x.iter_mut() {
+                    g.update_geometry_boundaries(&mut g_vi_oldnew);
+                    g.update_material(&mut m_oldnew);
+                    g.update_texture(&mut t_oldnew, &mut t_v_oldnew);
+                }
+            }
+            cjf.add_co(childkey.clone(), coc2);
+        }
+
+        //-- "slice" geometry vertices
+        let allvertices = &self.vertices;
+        let mut g_new_vertices: Vec<Vec<i64>> = Vec::new();
+        g_new_vertices.resize(g_vi_oldnew.len(), vec![]);
+        for (old, new) in &g_vi_oldnew {
+            g_new_vertices[*new] = allvertices[*old].clone();
+        }
+        cjf.vertices = g_new_vertices;
+
+        //-- "slice" materials
+        cjf.appearance = self.slice_appearance(&m_oldnew, &t_oldnew, &t_v_oldnew);
+        Some(cjf)
+    }
+    pub fn write_cityjsonseq<W: Write>(&self, w: &mut W, order: SortingStrategy) -> io::Result<()> {
+        //-- the 1st line then one line per feature, serialised directly in w (a feature with
+        //-- a missing child is an error)
+        serde_json::to_writer(&mut *w, &self.get_metadata())?;
+        w.write_all(b"\n")?;
+        let mut roots: Vec<(&String, &CityObject)> = self
+            .iter_city_objects()
+            .filter(|(_, co)| self.is_feature_root(co))
+            .collect();
+        match order {
+            SortingStrategy::Alphabetical => (),
+            SortingStrategy::Type => {
+                roots.sort_by(|a, b| (&a.1.thetype, a.0).cmp(&(&b.1.thetype, b.0)))
+            }
+            SortingStrategy::Random(seed) => roots.shuffle(&mut StdRng::seed_from_u64(seed)),
+        }
+        for (key, co) in roots {
+            let missing = co
+                .get_children_keys()
+                .into_iter()
+                .find(|k| !self.city_objects.contains_key(k));
+            if let Some(k) = missing {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("child #{} of CityObject #{} is missing", k, key),
+                ));
+            }
+            serde_json::to_writer(&mut *w, &self.get_cjfeature(key).unwrap())?;
+            w.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+    fn slice_appearance(
+        &self,
+        m_oldnew: &HashMap<usize, usize>,
+        t_oldnew: &HashMap<usize, usize>,
+        t_v_oldnew: &HashMap<usize, usize>,
+    ) -> Option<Appearance> {
+        let a = self.appearance.as_ref()?;
+        let mut acjf: Appearance = Appearance::new();
+        acjf.default_theme_material = a.default_theme_material.clone();
+        acjf.default_theme_texture = a.default_theme_texture.clone();
+        if let Some(am) = &a.materials {
+            let mut mats2: Vec<Value> = Vec::new();
+            mats2.resize(m_oldnew.len(), json!(null));
+            for (old, new) in m_oldnew {
+                mats2[*new] = am[*old].clone();
+            }
+            acjf.materials = Some(mats2);
+        }
+        if let Some(at) = &a.textures {
+            let mut texs2: Vec<Value> = Vec::new();
+            texs2.resize(t_oldnew.len(), json!(null));
+            for (old, new) in t_oldnew {
+                texs2[*new] = at[*old].clone();
+            }
+            acjf.textures = Some(texs2);
+        }
+        if let Some(atv) = &a.vertices_texture {
+            let mut t_new_vertices: Vec<Vec<f64>> = Vec::new();
+            t_new_vertices.resize(t_v_oldnew.len(), vec![]);
+            for (old, new) in t_v_oldnew {
+                t_new_vertices[*new] = atv[*old].clone();
+            }
+            acjf.vertices_texture = Some(t_new_vertices);
+        }
+        Some(acjf)
+    }
     pub fn add_co(&mut self, id: String, co: CityObject) {
         self.city_objects.insert(id, co);
     }
+    pub fn reference_system(&self) -> Option<Result<ReferenceSystem, String>> {
+        //-- None if there is no "metadata"/"referenceSystem"
+        let url = self.metadata.as_ref()?.get("referenceSystem")?;
+        match url.as_str() {
+            Some(x) => Some(ReferenceSystem::from_url(x)),
+            None => Some(Err("\"referenceSystem\" is not a string".to_string())),
+        }
+    }
+    pub fn crs_compatible(&self, other: &CityJSON) -> bool {
+        //-- the vertices of other can be used as they are: same transform (up to
+        //-- TRANSFORM_EPSILON) and same "referenceSystem" (the URLs can differ, eg http/https)
+        if !self
+            .transform
+            .approx_eq(&other.transform, TRANSFORM_EPSILON)
+        {
+            return false;
+        }
+        match (self.reference_system(), other.reference_system()) {
+            (None, None) => true,
+            (Some(Ok(a)), Some(Ok(b))) => a.authority == b.authority && a.code == b.code,
+            _ => {
+                let url = |c: &CityJSON| c.metadata.as_ref()?.get("referenceSystem").cloned();
+                url(self) == url(other)
+            }
+        }
+    }
+    pub fn point_of_contact(&self) -> Option<Result<PointOfContact, String>> {
+        //-- None if there is no "metadata"/"pointOfContact"
+        let j = self.metadata.as_ref()?.get("pointOfContact")?;
+        Some(serde_json::from_value(j.clone()).map_err(|e| format!("\"pointOfContact\": {}", e)))
+    }
+    pub fn set_point_of_contact(&mut self, poc: &PointOfContact) {
+        self.metadata.get_or_insert_with(|| json!({}))["pointOfContact"] = json!(poc);
+    }
+    pub fn local_origin(&self) -> [f64; 3] {
+        //-- the translate is a good local origin: coordinates relative to it are small
+        [
+            self.transform.translate[0],
+            self.transform.translate[1],
+            self.transform.translate[2],
+        ]
+    }
+    pub fn sorted_ids(&self) -> Vec<&String> {
+        //-- the roots of the features first (see is_feature_root()), then the others; both
+        //-- sorted by id
+        let mut re: Vec<&String> = self.city_objects.keys().collect();
+        re.sort_by_key(|k| (!self.is_feature_root(&self.city_objects[*k]), *k));
+        re
+    }
+    pub fn iter_city_objects(&self) -> impl Iterator<Item = (&String, &CityObject)> {
+        //-- in the order of sorted_ids(), always the same for the same document
+        self.sorted_ids()
+            .into_iter()
+            .map(|k| (k, &self.city_objects[k]))
+    }
+    pub fn stats(&self) -> Stats {
+        let a = self.appearance.as_ref();
+        Stats {
+            city_objects: self.city_objects.len(),
+            geometries: self
+                .city_objects
+                .values()
+                .map(|co| co.geometry.as_ref().map_or(0, |x| x.len()))
+                .sum(),
+            attributes: self
+                .city_objects
+                .values()
+                .map(|co| co.attribute_keys().len())
+                .sum(),
+            vertices: self.vertices.len(),
+            materials: a.and_then(|a| a.materials.as_ref()).map_or(0, |x| x.len()),
+            textures: a.and_then(|a| a.textures.as_ref()).map_or(0, |x| x.len()),
+            vertices_texture: a
+                .and_then(|a| a.vertices_texture.as_ref())
+                .map_or(0, |x| x.len()),
+            geometry_templates: self
+                .geometry_templates
+                .as_ref()
+                .map_or(0, |x| x.templates.len()),
+            per_type: self.stats_per_type(),
+        }
+    }
+    fn stats_per_type(&self) -> BTreeMap<String, TypeStats> {
+        let mut re: BTreeMap<String, TypeStats> = BTreeMap::new();
+        for co in self.city_objects.values() {
+            let ts = re.entry(co.thetype.clone()).or_default();
+            ts.count += 1;
+            for g in co.geometry.iter().flatten() {
+                ts.geometries += 1;
+                ts.vertices += g.vertex_indices().len();
+                if let Some(l) = &g.lod {
+                    ts.lods.insert(l.clone());
+                }
+                ts.textured |= g.texture.is_some();
+            }
+        }
+        re
+    }
+    pub fn vertices_flat(&self) -> Vec<f64> {
+        //-- [x0, y0, z0, x1, y1, z1, ...] in the coordinates of the CRS, eg for numpy or a GPU
+        let mut re: Vec<f64> = Vec::with_capacity(self.vertices.len() * 3);
+        for v in &self.vertices {
+            re.extend(self.transform.real_coordinates(v));
+        }
+        re
+    }
+    pub fn boundaries_flat(&self) -> Vec<usize> {
+        //-- triangle index buffer (3 indices of vertices_flat() per triangle) of all the
+        //-- surfaces of all the geometries, in the order of sorted_ids(); as for OBJ the holes
+        //-- are ignored, the exterior rings are triangulated by ear clipping (orientation kept)
+        let vs: Vec<[f64; 3]> = self
+            .vertices
+            .iter()
+            .map(|v| self.transform.real_coordinates(v))
+            .collect();
+        let mut re: Vec<usize> = Vec::new();
+        for (_, co) in self.iter_city_objects() {
+            for g in co.geometry.iter().flatten() {
+                for surface in g.get_surfaces() {
+                    if let Some(ring) = surface.first() {
+                        re.extend(triangulate_ring(ring, &vs).into_iter().flatten());
+                    }
+                }
+            }
+        }
+        re
+    }
+    pub fn num_vertices(&self) -> usize {
+        self.vertices.len()
+    }
+    pub fn num_features(&self) -> usize {
+        //-- one feature per top-level CityObject
+        self.city_objects
+            .values()
+            .filter(|co| self.is_feature_root(co))
+            .count()
+    }
+    pub fn is_feature_root(&self, co: &CityObject) -> bool {
+        //-- a top-level CityObject, or one whose parents are all CityObjectGroups: a group
+        //-- is a feature without its members, they are in their own features
+        match &co.parents {
+            Some(x) => x
+                .iter()
+                .all(|p| self.city_objects.get(p).is_some_and(|g| g.is_group())),
+            None => true,
+        }
+    }
+    pub fn geometries_iter_mut(&mut self) -> impl Iterator<Item = &mut Geometry> {
+        self.city_objects
+            .values_mut()
+            .filter_map(|co| co.geometry.as_mut())
+            .flatten()
+    }
     pub fn add_vertices(&mut self, mut v: Vec<Vec<i64>>) {
         self.vertices.append(&mut v);
     }
@@ -74,7 +391,7 @@ impl CityJSON {
         };
     }
     pub fn add_material(&mut self, jm: Value) -> usize {
-        let re = match &mut self.appearance {
+        match &mut self.appearance {
             Some(x) => x.add_material(jm),
             None => {
                 let mut a: Appearance = Appearance::new();
@@ -82,11 +399,10 @@ impl CityJSON {
                 self.appearance = Some(a);
                 re
             }
-        };
-        re
+        }
     }
     pub fn add_texture(&mut self, jm: Value) -> usize {
-        let re = match &mut self.appearance {
+        match &mut self.appearance {
             Some(x) => x.add_texture(jm),
             None => {
                 let mut a: Appearance = Appearance::new();
@@ -94,34 +410,42 @@ impl CityJSON {
                 self.appearance = Some(a);
                 re
             }
-        };
-        re
+        }
     }
-    pub fn add_one_cjf(&mut self, mut cjf: CityJSONFeature) {
-        // let mut g_oldnew: HashMap<usize, usize> = HashMap::new();
-        let mut m_oldnew: HashMap<usize, usize> = HashMap::new();
-        let mut t_oldnew: HashMap<usize, usize> = HashMap::new();
-        let mut t_v_oldnew: HashMap<usize, usize> = HashMap::new();
+    pub fn add_one_cjf(&mut self, cjf: CityJSONFeature) -> Result<Vec<String>, CjseqError> {
+        //-- returns the conflicts of the default themes of the appearance, if any; the
+        //-- indices are checked first, otherwise once offset they would refer to the vertices
+        //-- (or templates) of another feature
+        cjf.check_vertex_indices()?;
+        cjf.check_template_indices(self.geometry_templates.as_ref())?;
+        Ok(self.add_checked_cjf(cjf))
+    }
+    fn add_checked_cjf(&mut self, mut cjf: CityJSONFeature) -> Vec<String> {
         let g_offset = self.vertices.len();
-        let mut t_offset = 0;
-        if let Some(cjf_app) = &cjf.appearance {
-            // println!("{:?}", cjf_app);
-            if let Some(cjf_mat) = &cjf_app.materials {
-                // println!("{:?}", cjf_mat);
-                for (i, m) in cjf_mat.iter().enumerate() {
-                    m_oldnew.insert(i, self.add_material(m.clone()));
-                }
-            }
-            if let Some(cjf_tex) = &cjf_app.textures {
-                for (i, m) in cjf_tex.iter().enumerate() {
-                    t_oldnew.insert(i, self.add_texture(m.clone()));
+        //-- the local templates are moved to those of the CityJSON (an identical one is reused)
+        let mut t_oldnew: HashMap<usize, usize> = HashMap::new();
+        if let Some(local) = cjf.geometry_templates.take() {
+            let gts = self
+                .geometry_templates
+                .get_or_insert_with(GeometryTemplates::new);
+            let vts = local.vertices();
+            for (i, mut g) in local.templates.into_iter().enumerate() {
+                let mut oldnew: HashMap<usize, usize> = HashMap::new();
+                g.update_geometry_boundaries(&mut oldnew);
+                let mut newvts: Vec<Vec<f64>> = vec![vec![]; oldnew.len()];
+                for (old, new) in &oldnew {
+                    newvts[*new] = vts[*old].clone();
                 }
-            }
-            if let Some(cjf_v_tex) = &cjf_app.vertices_texture {
-                t_offset = cjf_v_tex.len();
-                self.add_vertices_texture(cjf_v_tex.clone());
+                t_oldnew.insert(i, gts.add_template(g, newvts));
             }
         }
+        let mut remap = match &cjf.appearance {
+            Some(cjf_app) if !cjf_app.is_empty() => self
+                .appearance
+                .get_or_insert_with(Appearance::new)
+                .merge(cjf_app),
+            _ => AppearanceRemap::default(),
+        };
 
         for (key, co) in &mut cjf.city_objects {
             //-- boundaries
@@ -131,9 +455,12 @@ impl CityJSON {
                     g.offset_geometry_boundaries(g_offset);
                     // g.update_geometry_boundaries(&mut g_oldnew, g_offset);
                     //-- material
-                    g.update_material(&mut m_oldnew);
+                    g.update_material(&mut remap.materials);
                     //-- texture
-                    g.update_texture(&mut t_oldnew, &mut t_v_oldnew, t_offset);
+                    g.update_texture(&mut remap.textures, &mut remap.vertices_texture);
+                    if let Some(t) = g.template.and_then(|t| t_oldnew.get(&t)) {
+                        g.template = Some(*t);
+                    }
                 }
             }
             //-- update the collected json object by adding the CityObjects
@@ -141,10 +468,126 @@ impl CityJSON {
         }
         //-- add the new vertices
         self.add_vertices(cjf.vertices.clone());
+        remap.conflicts
+    }
+
+    pub fn rename_city_object(&mut self, old: &str, new: &str) -> bool {
+        if !self.city_objects.contains_key(old) || self.city_objects.contains_key(new) {
+            return false;
+        }
+        let mut m: HashMap<String, String> = HashMap::new();
+        m.insert(old.to_string(), new.to_string());
+        self.rename_city_objects(&m);
+        true
+    }
+
+    pub fn compact_ids(&mut self) -> HashMap<String, String> {
+        //-- ids are numbered in alphabetical order; returns the map new->original
+        let mut ids: Vec<String> = self.city_objects.keys().cloned().collect();
+        ids.sort();
+        let mut m: HashMap<String, String> = HashMap::new();
+        let mut re: HashMap<String, String> = HashMap::new();
+        for (i, id) in ids.into_iter().enumerate() {
+            m.insert(id.clone(), i.to_string());
+            re.insert(i.to_string(), id);
+        }
+        self.rename_city_objects(&m);
+        re
+    }
+
+    fn rename_city_objects(&mut self, oldnew: &HashMap<String, String>) {
+        rename_city_objects(&mut self.city_objects, oldnew);
     }
 
-    pub fn remove_duplicate_vertices(&mut self) {
-        // let totalinput = self.vertices.len();
+    pub fn ensure_toplevel_ids_unique(
+        &self,
+        cjf: &mut CityJSONFeature,
+        strategy: DupStrategy,
+    ) -> Result<bool, String> {
+        //-- to be called before adding a feature: its ids that are already used are either
+        //-- an error, renamed ("id_1", "id_2", ...), or the feature is skipped (returns false)
+        let dups: Vec<String> = cjf
+            .city_objects
+            .keys()
+            .filter(|k| self.city_objects.contains_key(*k))
+            .cloned()
+            .collect();
+        if dups.is_empty() {
+            return Ok(true);
+        }
+        match strategy {
+            DupStrategy::Error => Err(format!("duplicate CityObject id \"{}\"", dups[0])),
+            DupStrategy::KeepFirst => Ok(false),
+            DupStrategy::SuffixNumeric => {
+                let mut m: HashMap<String, String> = HashMap::new();
+                for d in dups {
+                    let mut i = 1;
+                    let mut newid = format!("{}_{}", d, i);
+                    while self.city_objects.contains_key(&newid)
+                        || cjf.city_objects.contains_key(&newid)
+                    {
+                        i += 1;
+                        newid = format!("{}_{}", d, i);
+                    }
+                    m.insert(d, newid);
+                }
+                if let Some(x) = m.get(&cjf.id) {
+                    cjf.id = x.clone();
+                }
+                rename_city_objects(&mut cjf.city_objects, &m);
+                Ok(true)
+            }
+        }
+    }
+
+    pub fn check_version(&self, other: &CityJSON) -> Result<(), CjseqError> {
+        if other.version != self.version {
+            return Err(CjseqError::VersionMismatch {
+                expected: self.version.clone(),
+                found: other.version.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    pub fn merge(
+        &mut self,
+        other: &CityJSON,
+        strategy: DupStrategy,
+    ) -> Result<Vec<String>, CjseqError> {
+        //-- the features of other are added, its vertices are requantized if the transforms differ;
+        //-- returns the conflicts of the default themes of the appearance, if any
+        self.check_version(other)?;
+        if other.geometry_templates.is_some() {
+            return Err("cannot merge a CityJSON with geometry-templates"
+                .to_string()
+                .into());
+        }
+        let mut conflicts: Vec<String> = Vec::new();
+        for (k, _) in other
+            .iter_city_objects()
+            .filter(|(_, co)| other.is_feature_root(co))
+        {
+            let mut cjf = other.get_cjfeature(k).unwrap();
+            if !other
+                .transform
+                .approx_eq(&self.transform, TRANSFORM_EPSILON)
+            {
+                for v in cjf.vertices.iter_mut() {
+                    *v = self
+                        .transform
+                        .quantize(&other.transform.real_coordinates(v));
+                }
+            }
+            if self.ensure_toplevel_ids_unique(&mut cjf, strategy)? {
+                conflicts.extend(self.add_one_cjf(cjf)?);
+            }
+        }
+        Ok(conflicts)
+    }
+
+    pub fn remove_duplicate_vertices(&mut self) -> CollectReport {
+        let report = CollectReport::new(self);
         let mut h: HashMap<String, usize> = HashMap::new();
         let mut newids: HashMap<usize, usize> = HashMap::new();
         let mut newvertices: Vec<Vec<i64>> = Vec::new();
@@ -163,179 +606,1971 @@ impl CityJSON {
             }
         }
         //-- update indices
-        let cos = &mut self.city_objects;
-        for (_key, co) in cos.iter_mut() {
-            match &mut co.geometry {
-                Some(x) => {
-                    for g in x.iter_mut() {
-                        g.update_geometry_boundaries(&mut newids);
-                    }
-                }
-                None => (),
-            }
+        for g in self.geometries_iter_mut() {
+            g.update_geometry_boundaries(&mut newids);
         }
         //-- replace the vertices, innit?
         self.vertices = newvertices;
+        report.then(CollectReport::new(self))
     }
 
-    pub fn retransform(&mut self) {
-        let mut newvertices: Vec<Vec<i64>> = Vec::new();
-        let mut mins: Vec<i64> = vec![i64::MAX, i64::MAX, i64::MAX];
-        //-- find min-xyz
-        for v in &self.vertices {
-            for i in 0..3 {
-                if v[i] < mins[i] {
-                    mins[i] = v[i];
-                }
+    pub fn list_unreferenced_vertices(&self) -> Vec<usize> {
+        //-- the indices of the vertices not referenced by any geometry (sorted)
+        let mut used = vec![false; self.vertices.len()];
+        for i in self.referenced_vertices() {
+            if let Some(x) = used.get_mut(i) {
+                *x = true;
             }
         }
-        //-- subtract the mins from each vertex
-        for v in &self.vertices {
-            let v: Vec<i64> = vec![v[0] - mins[0], v[1] - mins[1], v[2] - mins[2]];
-            newvertices.push(v);
+        (0..used.len()).filter(|i| !used[*i]).collect()
+    }
+
+    fn referenced_vertices(&self) -> Vec<usize> {
+        let mut used: Vec<usize> = self
+            .city_objects
+            .values()
+            .filter_map(|co| co.geometry.as_ref())
+            .flatten()
+            .flat_map(|g| g.vertex_indices())
+            .collect();
+        used.sort_unstable();
+        used.dedup();
+        used
+    }
+
+    pub fn remove_unused_vertices(&mut self) {
+        //-- the vertices not referenced by any geometry are removed, the order is kept
+        let used = self.referenced_vertices();
+        let mut newids: HashMap<usize, usize> = HashMap::new();
+        let mut newvertices: Vec<Vec<i64>> = Vec::new();
+        for i in used {
+            newids.insert(i, newvertices.len());
+            newvertices.push(self.vertices[i].clone());
+        }
+        for g in self.geometries_iter_mut() {
+            g.update_geometry_boundaries(&mut newids);
         }
-        //-- replace the vertices, innit?
         self.vertices = newvertices;
-        //-- update the transform/translate
-        let ttx = (mins[0] as f64 * self.transform.scale[0]) + self.transform.translate[0];
-        let tty = (mins[1] as f64 * self.transform.scale[1]) + self.transform.translate[1];
-        let ttz = (mins[2] as f64 * self.transform.scale[2]) + self.transform.translate[2];
-        self.transform.translate = vec![ttx, tty, ttz];
     }
-}
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct CityJSONFeature {
-    #[serde(rename = "type")]
-    pub thetype: String,
-    pub id: String,
-    #[serde(rename = "CityObjects")]
-    pub city_objects: HashMap<String, CityObject>,
-    pub vertices: Vec<Vec<i64>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub appearance: Option<Appearance>,
-}
-impl CityJSONFeature {
-    pub fn new() -> Self {
-        let co: HashMap<String, CityObject> = HashMap::new();
-        let v: Vec<Vec<i64>> = Vec::new();
-        CityJSONFeature {
-            thetype: "CityJSONFeature".to_string(),
-            id: "".to_string(),
-            city_objects: co,
-            vertices: v,
-            appearance: None,
+    pub fn prune_appearance(&mut self) {
+        //-- the materials, textures, and texture vertices not used are removed (the
+        //-- geometry-templates can also use them)
+        if self.appearance.is_none() {
+            return;
+        }
+        let mut m_oldnew: HashMap<usize, usize> = HashMap::new();
+        let mut t_oldnew: HashMap<usize, usize> = HashMap::new();
+        let mut t_v_oldnew: HashMap<usize, usize> = HashMap::new();
+        //-- sorted so that the result is always the same
+        let mut ids: Vec<String> = self.city_objects.keys().cloned().collect();
+        ids.sort();
+        for id in ids {
+            let co = self.city_objects.get_mut(&id).unwrap();
+            for g in co.geometry.iter_mut().flatten() {
+                g.update_material(&mut m_oldnew);
+                g.update_texture(&mut t_oldnew, &mut t_v_oldnew);
+            }
+        }
+        if let Some(gts) = &mut self.geometry_templates {
+            for g in &mut gts.templates {
+                g.update_material(&mut m_oldnew);
+                g.update_texture(&mut t_oldnew, &mut t_v_oldnew);
+            }
         }
+        self.appearance = self.slice_appearance(&m_oldnew, &t_oldnew, &t_v_oldnew);
     }
-    pub fn add_co(&mut self, id: String, co: CityObject) {
-        self.city_objects.insert(id, co);
+
+    pub fn drop_appearance(&mut self) {
+        //-- the materials, textures and texture vertices, and their references in the geometries
+        self.appearance = None;
+        for g in self.geometries_iter_mut() {
+            g.material = None;
+            g.texture = None;
+        }
+        if let Some(gts) = &mut self.geometry_templates {
+            for g in &mut gts.templates {
+                g.material = None;
+                g.texture = None;
+            }
+        }
     }
-    pub fn centroid(&self) -> Vec<f64> {
-        let mut totals: Vec<f64> = vec![0., 0., 0.];
+
+    pub fn select_theme(&mut self, theme: Option<&str>) {
+        //-- only the materials and textures of one theme are kept; with None those of the
+        //-- "default-theme-material" and "default-theme-texture" (a kind without default
+        //-- theme is not modified)
+        let a = self.appearance.as_ref();
+        let (tm, tt) = match theme {
+            Some(t) => (Some(t.to_string()), Some(t.to_string())),
+            None => (
+                a.and_then(|a| a.default_theme_material.clone()),
+                a.and_then(|a| a.default_theme_texture.clone()),
+            ),
+        };
+        let (tm, tt) = (tm.as_deref(), tt.as_deref());
+        for g in self.geometries_iter_mut() {
+            g.select_theme(tm, tt);
+        }
+        if let Some(gts) = &mut self.geometry_templates {
+            for g in &mut gts.templates {
+                g.select_theme(tm, tt);
+            }
+        }
+        self.prune_appearance();
+        if let Some(a) = &mut self.appearance {
+            if tm.is_some() {
+                a.default_theme_material = tm
+                    .filter(|_| a.materials.as_ref().is_some_and(|x| !x.is_empty()))
+                    .map(|t| t.to_string());
+            }
+            if tt.is_some() {
+                a.default_theme_texture = tt
+                    .filter(|_| a.textures.as_ref().is_some_and(|x| !x.is_empty()))
+                    .map(|t| t.to_string());
+            }
+        }
+    }
+
+    pub fn bbox(&self) -> Option<[f64; 6]> {
+        //-- in the CRS of the file, None if there are no vertices
+        let mut re: Option<[f64; 6]> = None;
         for v in &self.vertices {
-            for i in 0..3 {
-                totals[i] += v[i] as f64;
+            let p = self.transform.real_coordinates(v);
+            match &mut re {
+                Some(b) => {
+                    for i in 0..3 {
+                        b[i] = b[i].min(p[i]);
+                        b[i + 3] = b[i + 3].max(p[i]);
+                    }
+                }
+                None => re = Some([p[0], p[1], p[2], p[0], p[1], p[2]]),
             }
         }
+        re
+    }
+
+    pub fn set_geographical_extent(&mut self, e: &[f64; 6]) {
+        //-- rounded to the precision of the transform
+        let f = 10_f64.powi(self.transform.decimals() as i32);
+        let e: Vec<f64> = e.iter().map(|x| (x * f).round() / f).collect();
+        self.metadata.get_or_insert_with(|| json!({}))["geographicalExtent"] = json!(e);
+    }
+
+    pub fn update_geographical_extent(&mut self) {
+        if let Some(e) = self.bbox() {
+            self.set_geographical_extent(&e);
+        }
+    }
+
+    pub fn rescale(&mut self, factor: f64) {
+        //-- to convert the units of the coordinates, eg 0.3048 for feet => metres: the
+        //-- vertices are not modified, only the transform (and the extents and templates).
+        //-- the "referenceSystem" is not modified, its units are then not those of the file
         for i in 0..3 {
-            totals[i] /= self.vertices.len() as f64;
+            self.transform.scale[i] *= factor;
+            self.transform.translate[i] *= factor;
+        }
+        let mul = |v: &mut Value| {
+            if let Some(x) = v.as_f64() {
+                *v = json!(x * factor);
+            }
+        };
+        if let Some(Value::Array(e)) = self
+            .metadata
+            .as_mut()
+            .and_then(|m| m.get_mut("geographicalExtent"))
+        {
+            e.iter_mut().for_each(mul);
+        }
+        for co in self.city_objects.values_mut() {
+            for x in co.geographical_extent.iter_mut().flatten() {
+                *x *= factor;
+            }
+        }
+        if let Some(gts) = &mut self.geometry_templates {
+            if let Value::Array(vs) = &mut gts.vertices_templates {
+                vs.iter_mut()
+                    .filter_map(|v| v.as_array_mut())
+                    .flatten()
+                    .for_each(mul);
+            }
+        }
+        //-- the translation of the GeometryInstances (the rest of the matrix has no unit)
+        for g in self.geometries_iter_mut() {
+            if let Some(Value::Array(m)) = &mut g.transformation_matrix {
+                for i in [3, 7, 11] {
+                    if let Some(x) = m.get_mut(i) {
+                        mul(x);
+                    }
+                }
+            }
         }
-        return totals;
     }
-}
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct CityObject {
-    #[serde(rename = "type")]
-    pub thetype: String,
-    #[serde(rename = "geographicalExtent")]
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub geographical_extent: Option<Vec<f64>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub attributes: Option<Value>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub geometry: Option<Vec<Geometry>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub children: Option<Vec<String>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub parents: Option<Vec<String>>,
-    #[serde(flatten)]
-    other: serde_json::Value,
-}
+    pub fn center_on_origin(&mut self) -> [f64; 3] {
+        //-- the centre of the bbox becomes (0, 0, 0), eg for WebGL viewers: only the translate
+        //-- (and the extents) are modified; returns the offset subtracted (add it to go back)
+        let c = match self.bbox() {
+            Some(b) => [
+                (b[0] + b[3]) / 2.0,
+                (b[1] + b[4]) / 2.0,
+                (b[2] + b[5]) / 2.0,
+            ],
+            None => return [0.0, 0.0, 0.0],
+        };
+        for (t, x) in self.transform.translate.iter_mut().zip(c) {
+            *t -= x;
+        }
+        let shift = |e: &mut [f64]| {
+            for (i, x) in e.iter_mut().enumerate() {
+                *x -= c[i % 3];
+            }
+        };
+        if let Some(mut e) = self
+            .metadata
+            .as_ref()
+            .and_then(|m| m.get("geographicalExtent"))
+            .and_then(|x| serde_json::from_value::<[f64; 6]>(x.clone()).ok())
+        {
+            shift(&mut e);
+            self.set_geographical_extent(&e);
+        }
+        for co in self.city_objects.values_mut() {
+            if let Some(e) = co.geographical_extent.as_mut() {
+                shift(e);
+            }
+        }
+        c
+    }
 
-impl CityObject {
-    pub fn is_toplevel(&self) -> bool {
-        match &self.parents {
-            Some(x) => {
-                if x.is_empty() {
-                    return true;
-                } else {
-                    return false;
-                }
+    pub fn drop_empty_geometries(&mut self) {
+        for co in self.city_objects.values_mut() {
+            if let Some(gs) = &mut co.geometry {
+                gs.retain(|g| !g.is_empty());
+            }
+        }
+    }
+
+    pub fn dedup_geometries(&mut self) {
+        for co in self.city_objects.values_mut() {
+            co.dedup_geometries();
+        }
+    }
+
+    pub fn drop_attributes(&mut self, keys: &[String]) {
+        for co in self.city_objects.values_mut() {
+            if let Some(Value::Object(m)) = &mut co.attributes {
+                m.retain(|k, _| !keys.contains(k));
+            }
+        }
+    }
+
+    pub fn keep_attributes(&mut self, keys: &[String]) {
+        //-- all the attributes not listed are removed
+        for co in self.city_objects.values_mut() {
+            if let Some(Value::Object(m)) = &mut co.attributes {
+                m.retain(|k, _| keys.contains(k));
+            }
+        }
+    }
+
+    pub fn strip_extensions(&mut self) {
+        //-- lossy: the CityObjects of an Extension type ("+Foo"), the extra attributes and
+        //-- root properties ("+foo") and the "extensions" are removed
+        let n = self.city_objects.len();
+        self.retain_city_objects(|_, co| !co.is_extension_type());
+        for co in self.city_objects.values_mut() {
+            if let Some(Value::Object(m)) = &mut co.attributes {
+                m.retain(|k, _| !k.starts_with('+'));
+            }
+        }
+        if let Value::Object(m) = &mut self.other {
+            m.retain(|k, _| !k.starts_with('+'));
+        }
+        self.extensions = None;
+        if self.city_objects.len() < n {
+            self.remove_unused_vertices();
+        }
+    }
+
+    pub fn retain_city_objects<F: FnMut(&str, &CityObject) -> bool>(&mut self, mut f: F) {
+        //-- the CityObjects for which f is false are removed, and so are the references to
+        //-- them in "children"/"parents"; the vertices and the appearance are not modified,
+        //-- remove_unused_vertices() and prune_appearance() can be used afterwards
+        let removed: HashSet<String> = self
+            .city_objects
+            .iter()
+            .filter(|(k, co)| !f(k, co))
+            .map(|(k, _)| k.clone())
+            .collect();
+        if removed.is_empty() {
+            return;
+        }
+        self.city_objects.retain(|k, _| !removed.contains(k));
+        for co in self.city_objects.values_mut() {
+            for l in [&mut co.children, &mut co.parents] {
+                if let Some(x) = l {
+                    x.retain(|k| !removed.contains(k));
+                    if x.is_empty() {
+                        *l = None;
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn merge_vertices_per_object(&mut self) {
+        //-- like remove_duplicate_vertices() but only the vertices of the same CityObject are
+        //-- merged, a vertex shared by 2 CityObjects is thus kept twice
+        let mut newvertices: Vec<Vec<i64>> = Vec::new();
+        let mut ids: Vec<String> = self.city_objects.keys().cloned().collect();
+        ids.sort();
+        for id in ids {
+            let gs = match &mut self.city_objects.get_mut(&id).unwrap().geometry {
+                Some(x) => x,
+                None => continue,
+            };
+            let mut h: HashMap<&Vec<i64>, usize> = HashMap::new();
+            let mut newids: HashMap<usize, usize> = HashMap::new();
+            for i in gs.iter().flat_map(|g| g.vertex_indices()) {
+                let v = &self.vertices[i];
+                let n = *h.entry(v).or_insert_with(|| {
+                    newvertices.push(v.clone());
+                    newvertices.len() - 1
+                });
+                newids.insert(i, n);
+            }
+            for g in gs.iter_mut() {
+                g.update_geometry_boundaries(&mut newids);
+            }
+        }
+        self.vertices = newvertices;
+    }
+
+    pub fn canonicalize_vertices(&mut self) {
+        //-- like remove_duplicate_vertices() but the vertices are sorted (x, y, z),
+        //-- thus the result doesn't depend on the order of the CityObjects/features
+        let mut sorted: Vec<Vec<i64>> = self.vertices.clone();
+        sorted.sort();
+        sorted.dedup();
+        let mut newids: HashMap<usize, usize> = HashMap::new();
+        {
+            let pos: HashMap<&Vec<i64>, usize> =
+                sorted.iter().enumerate().map(|(i, v)| (v, i)).collect();
+            for (i, v) in self.vertices.iter().enumerate() {
+                newids.insert(i, pos[v]);
+            }
+        }
+        for g in self.geometries_iter_mut() {
+            g.update_geometry_boundaries(&mut newids);
+        }
+        self.vertices = sorted;
+    }
+
+    pub fn expand_templates(&mut self) -> Result<(), String> {
+        //-- each GeometryInstance is replaced by a copy of its template, the vertices of the
+        //-- template being transformed by the matrix and then translated to the reference point;
+        //-- nothing is modified if one of the instances (or the templates) is invalid
+        let gts = match &self.geometry_templates {
+            Some(x) => x.clone(),
+            None => return Ok(()),
+        };
+        let vts: Vec<Vec<f64>> = serde_json::from_value(gts.vertices_templates.clone())
+            .map_err(|e| format!("\"vertices-templates\" are invalid: {}", e))?;
+        let mut newvertices: Vec<Vec<i64>> = Vec::new();
+        let mut expanded: Vec<(String, usize, Geometry)> = Vec::new();
+        let mut ids: Vec<&String> = self.city_objects.keys().collect();
+        ids.sort();
+        for id in ids {
+            let geoms = match &self.city_objects[id].geometry {
+                Some(x) => x,
+                None => continue,
+            };
+            for (gi, g) in geoms.iter().enumerate() {
+                if g.thetype != GeometryType::GeometryInstance {
+                    continue;
+                }
+                let what = format!("CityObject #{}: GeometryInstance #{}", id, gi);
+                let refpt = match serde_json::from_value::<Vec<usize>>(g.boundaries.clone()) {
+                    Ok(a) if a.len() == 1 && a[0] < self.vertices.len() => {
+                        self.transform.real_coordinates(&self.vertices[a[0]])
+                    }
+                    _ => {
+                        return Err(format!(
+                            "{}: \"boundaries\" {} is not the index of a vertex",
+                            what, g.boundaries
+                        ))
+                    }
+                };
+                let mut g2: Geometry = match g.template.and_then(|t| gts.templates.get(t)) {
+                    Some(t) => t.clone(),
+                    None => {
+                        return Err(format!(
+                            "{}: template {:?} does not exist",
+                            what, g.template
+                        ))
+                    }
+                };
+                let mut vi_oldnew: HashMap<usize, usize> = HashMap::new();
+                g2.update_geometry_boundaries(&mut vi_oldnew);
+                let mut vs: Vec<Vec<i64>> = vec![vec![]; vi_oldnew.len()];
+                for (old, new) in &vi_oldnew {
+                    let vt = match vts.get(*old) {
+                        Some(v) if v.len() == 3 => v,
+                        _ => {
+                            return Err(format!(
+                                "{}: vertex #{} of template #{} does not exist",
+                                what,
+                                old,
+                                g.template.unwrap()
+                            ))
+                        }
+                    };
+                    let p = g.apply_matrix([vt[0], vt[1], vt[2]]);
+                    let p = [p[0] + refpt[0], p[1] + refpt[1], p[2] + refpt[2]];
+                    vs[*new] = self.transform.quantize(&p);
+                }
+                g2.offset_geometry_boundaries(self.vertices.len() + newvertices.len());
+                newvertices.append(&mut vs);
+                expanded.push((id.clone(), gi, g2));
+            }
+        }
+        for (id, gi, g2) in expanded {
+            self.city_objects
+                .get_mut(&id)
+                .unwrap()
+                .geometry
+                .as_mut()
+                .unwrap()[gi] = g2;
+        }
+        self.add_vertices(newvertices);
+        self.geometry_templates = None;
+        Ok(())
+    }
+
+    pub fn inline_textures(&mut self, base_dir: &Path) -> std::io::Result<()> {
+        //-- the images of the textures are replaced by data URIs (base64), the relative
+        //-- paths being relative to base_dir; URLs and data URIs are kept as is
+        let textures = match self.appearance.as_mut().and_then(|a| a.textures.as_mut()) {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+        for t in textures {
+            let image = match t.get("image").and_then(|x| x.as_str()) {
+                Some(x) => x.to_string(),
+                None => continue,
+            };
+            if image.starts_with("data:") || image.contains("://") {
+                continue;
+            }
+            let p = base_dir.join(&image);
+            let bytes = std::fs::read(&p).map_err(|e| {
+                std::io::Error::new(
+                    e.kind(),
+                    format!("cannot read texture image \"{}\": {}", p.display(), e),
+                )
+            })?;
+            let mime = match t.get("type").and_then(|x| x.as_str()) {
+                Some("PNG") => "image/png",
+                _ => "image/jpeg",
+            };
+            t["image"] = Value::String(format!(
+                "data:{};base64,{}",
+                mime,
+                base64::engine::general_purpose::STANDARD.encode(bytes)
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn dedup_textures(&mut self, base_dir: &Path) -> usize {
+        //-- the textures with the same content are merged: same image (the bytes of the file,
+        //-- relative to base_dir, or of the data URI) and same other properties (type,
+        //-- wrapMode, etc.); an image that cannot be read is compared by its normalised path.
+        //-- returns the number of textures removed
+        let textures = match self.appearance.as_ref().and_then(|a| a.textures.as_ref()) {
+            Some(x) => x.clone(),
+            None => return 0,
+        };
+        let mut keys: HashMap<String, usize> = HashMap::new();
+        let mut unique: Vec<Value> = Vec::new();
+        let mut t_oldnew: HashMap<usize, usize> = HashMap::new();
+        for (i, t) in textures.into_iter().enumerate() {
+            let n = *keys
+                .entry(texture_content_key(&t, base_dir))
+                .or_insert_with(|| {
+                    unique.push(t);
+                    unique.len() - 1
+                });
+            t_oldnew.insert(i, n);
+        }
+        let removed = t_oldnew.len() - unique.len();
+        if removed == 0 {
+            return 0;
+        }
+        //-- the texture vertices are not modified
+        let a = self.appearance.as_mut().unwrap();
+        a.textures = Some(unique);
+        let nv = a.vertices_texture.as_ref().map_or(0, |x| x.len());
+        let mut t_v_oldnew: HashMap<usize, usize> = (0..nv).map(|i| (i, i)).collect();
+        for g in self.geometries_iter_mut() {
+            g.update_texture(&mut t_oldnew, &mut t_v_oldnew);
+        }
+        if let Some(gts) = &mut self.geometry_templates {
+            for g in &mut gts.templates {
+                g.update_texture(&mut t_oldnew, &mut t_v_oldnew);
+            }
+        }
+        removed
+    }
+
+    pub fn content_hash(&self) -> String {
+        //-- SHA-256 of a canonical version: vertices sorted and deduplicated, and keys sorted
+        //-- (serde_json::Map is ordered), so the order of the CityObjects/vertices is irrelevant
+        let mut cj2 = self.clone();
+        cj2.canonicalize_vertices();
+        let s = serde_json::to_string(&cj2.to_value()).unwrap();
+        let digest = Sha256::digest(s.as_bytes());
+        digest.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    pub fn attribute_schema(&self) -> HashMap<String, AttributeInfo> {
+        //-- for each attribute key of all the CityObjects, the type of its values (the nulls
+        //-- are ignored, Null if it's always null) and the number of CityObjects having it
+        let mut re: HashMap<String, AttributeInfo> = HashMap::new();
+        for co in self.city_objects.values() {
+            let m = match &co.attributes {
+                Some(Value::Object(m)) => m,
+                _ => continue,
+            };
+            for (k, v) in m {
+                let t = match v {
+                    Value::Null => AttributeType::Null,
+                    Value::Bool(_) => AttributeType::Bool,
+                    Value::Number(_) => AttributeType::Number,
+                    Value::String(_) => AttributeType::String,
+                    Value::Array(_) => AttributeType::Array,
+                    Value::Object(_) => AttributeType::Object,
+                };
+                let e = re.entry(k.clone()).or_insert(AttributeInfo {
+                    thetype: t,
+                    count: 0,
+                    count_per_type: BTreeMap::new(),
+                });
+                e.count += 1;
+                *e.count_per_type.entry(co.thetype.clone()).or_default() += 1;
+                e.thetype = match (e.thetype, t) {
+                    (a, AttributeType::Null) => a,
+                    (AttributeType::Null, b) => b,
+                    (a, b) if a == b => a,
+                    _ => AttributeType::Mixed,
+                };
+            }
+        }
+        re
+    }
+    pub fn split_by_type(&self) -> Result<HashMap<String, CityJSON>, CjseqError> {
+        //-- one CityJSON per type of the top-level CityObjects, children follow their parent
+        let mut re: HashMap<String, CityJSON> = HashMap::new();
+        for (key, co) in &self.city_objects {
+            if self.is_feature_root(co) {
+                let cjf = self.get_cjfeature(key).unwrap();
+                re.entry(co.thetype.clone())
+                    .or_insert_with(|| self.get_metadata())
+                    .add_one_cjf(cjf)?;
+            }
+        }
+        for cj in re.values_mut() {
+            cj.retransform();
+            cj.remove_duplicate_vertices();
+            cj.update_geographical_extent();
+        }
+        Ok(re)
+    }
+
+    pub fn retransform(&mut self) -> CollectReport {
+        let report = CollectReport::new(self);
+        if self.vertices.is_empty() {
+            return report;
+        }
+        let mut newvertices: Vec<Vec<i64>> = Vec::new();
+        let mut mins: Vec<i64> = vec![i64::MAX, i64::MAX, i64::MAX];
+        //-- find min-xyz
+        for v in &self.vertices {
+            for i in 0..3 {
+                if v[i] < mins[i] {
+                    mins[i] = v[i];
+                }
+            }
+        }
+        //-- subtract the mins from each vertex
+        for v in &self.vertices {
+            let v: Vec<i64> = vec![v[0] - mins[0], v[1] - mins[1], v[2] - mins[2]];
+            newvertices.push(v);
+        }
+        //-- replace the vertices, innit?
+        self.vertices = newvertices;
+        //-- update the transform/translate
+        let ttx = (mins[0] as f64 * self.transform.scale[0]) + self.transform.translate[0];
+        let tty = (mins[1] as f64 * self.transform.scale[1]) + self.transform.translate[1];
+        let ttz = (mins[2] as f64 * self.transform.scale[2]) + self.transform.translate[2];
+        self.transform.translate = vec![ttx, tty, ttz];
+        report.then(CollectReport::new(self))
+    }
+
+    pub fn vertex_precision_report(&self) -> PrecisionReport {
+        //-- per axis, the finest spacing between the distinct coordinates and the coarsest
+        //-- scale that keeps them all (the gcd of their differences times the scale)
+        let mut min_spacing: Vec<Option<f64>> = vec![None; 3];
+        let mut lossless_scale: Vec<f64> = self.transform.scale.clone();
+        for i in 0..3 {
+            let mut cs: Vec<i64> = self.vertices.iter().map(|v| v[i]).collect();
+            cs.sort_unstable();
+            cs.dedup();
+            let mut min = i64::MAX;
+            let mut g = 0;
+            for w in cs.windows(2) {
+                let d = w[1] - w[0];
+                min = min.min(d);
+                g = gcd(g, d);
+            }
+            if g > 0 {
+                min_spacing[i] = Some(min as f64 * self.transform.scale[i]);
+                lossless_scale[i] = g as f64 * self.transform.scale[i];
+            }
+        }
+        PrecisionReport {
+            scale: self.transform.scale.clone(),
+            min_spacing,
+            lossless_scale,
+        }
+    }
+
+    pub fn simplify_footprints(&mut self, tolerance: f64) {
+        //-- Douglas-Peucker (in 2D, tolerance in the units of the CRS) on the rings of the
+        //-- MultiSurfaces/CompositeSurfaces of the lowest LoD of each CityObject; the
+        //-- textured geometries are not modified (one uv per vertex of the rings)
+        let t = self.transform.clone();
+        let vs: Vec<[f64; 2]> = self
+            .vertices
+            .iter()
+            .map(|v| {
+                let p = t.real_coordinates(v);
+                [p[0], p[1]]
+            })
+            .collect();
+        let mut modified = false;
+        for co in self.city_objects.values_mut() {
+            let gs = match co.geometry.as_mut() {
+                Some(x) => x,
+                None => continue,
+            };
+            let lowest = gs
+                .iter()
+                .filter_map(|g| g.lod_f64())
+                .min_by(|a, b| a.total_cmp(b));
+            for g in gs.iter_mut() {
+                if g.lod_f64() != lowest
+                    || g.texture.is_some()
+                    || !matches!(
+                        g.thetype,
+                        GeometryType::MultiSurface | GeometryType::CompositeSurface
+                    )
+                {
+                    continue;
+                }
+                let surfaces: Vec<Vec<Vec<usize>>> = g
+                    .get_surfaces()
+                    .iter()
+                    .map(|sf| {
+                        sf.iter()
+                            .map(|r| simplify_ring(r, &vs, tolerance))
+                            .collect()
+                    })
+                    .collect();
+                g.boundaries = serde_json::to_value(&surfaces).unwrap();
+                modified = true;
+            }
+        }
+        if modified {
+            self.remove_unused_vertices();
+        }
+    }
+}
+
+fn simplify_ring(ring: &[usize], vs: &[[f64; 2]], tolerance: f64) -> Vec<usize> {
+    //-- the ring is split into 2 chains at its 1st vertex and the vertex the furthest from
+    //-- it; a ring never has less than 3 vertices (the furthest one from the 2 anchors is
+    //-- kept). a ring with an invalid index is not modified
+    let n = ring.len();
+    if n < 4 || ring.iter().any(|i| *i >= vs.len()) {
+        return ring.to_vec();
+    }
+    let p = |k: usize| vs[ring[k % n]];
+    let dist = |q: [f64; 2], a: [f64; 2], b: [f64; 2]| {
+        let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+        let l2 = dx * dx + dy * dy;
+        let t = match l2 > 0.0 {
+            true => (((q[0] - a[0]) * dx + (q[1] - a[1]) * dy) / l2).clamp(0.0, 1.0),
+            false => 0.0,
+        };
+        ((q[0] - a[0] - t * dx).powi(2) + (q[1] - a[1] - t * dy).powi(2)).sqrt()
+    };
+    let far = (1..n)
+        .max_by(|a, b| {
+            let (da, db) = (dist(p(*a), p(0), p(0)), dist(p(*b), p(0), p(0)));
+            da.total_cmp(&db)
+        })
+        .unwrap();
+    let mut keep = vec![false; n];
+    keep[0] = true;
+    keep[far] = true;
+    //-- the chains are 0..far and far..n (n is the vertex 0)
+    let mut stack: Vec<(usize, usize)> = vec![(0, far), (far, n)];
+    while let Some((a, b)) = stack.pop() {
+        let best = (a + 1..b)
+            .map(|k| (k, dist(p(k), p(a), p(b))))
+            .max_by(|x, y| x.1.total_cmp(&y.1));
+        if let Some((k, d)) = best {
+            if d > tolerance {
+                keep[k] = true;
+                stack.push((a, k));
+                stack.push((k, b));
+            }
+        }
+    }
+    if keep.iter().filter(|x| **x).count() < 3 {
+        let k = (1..n)
+            .filter(|k| *k != far)
+            .max_by(|a, b| {
+                let (da, db) = (dist(p(*a), p(0), p(far)), dist(p(*b), p(0), p(far)));
+                da.total_cmp(&db)
+            })
+            .unwrap();
+        keep[k] = true;
+    }
+    (0..n).filter(|k| keep[*k]).map(|k| ring[k]).collect()
+}
+
+pub fn triangulate_ring(ring: &[usize], vs: &[[f64; 3]]) -> Vec<[usize; 3]> {
+    //-- ear clipping in the plane of the ring (the axis of the largest component of its
+    //-- normal is dropped); a ring with an invalid index gives no triangle
+    let mut r: Vec<usize> = ring.to_vec();
+    if r.len() > 1 && r.first() == r.last() {
+        r.pop();
+    }
+    if r.len() < 3 || r.iter().any(|i| *i >= vs.len()) {
+        return vec![];
+    }
+    //-- Newell's method
+    let mut n = [0.0; 3];
+    for (k, i) in r.iter().enumerate() {
+        let (a, b) = (vs[*i], vs[r[(k + 1) % r.len()]]);
+        n[0] += (a[1] - b[1]) * (a[2] + b[2]);
+        n[1] += (a[2] - b[2]) * (a[0] + b[0]);
+        n[2] += (a[0] - b[0]) * (a[1] + b[1]);
+    }
+    let drop = (0..3)
+        .max_by(|a, b| n[*a].abs().total_cmp(&n[*b].abs()))
+        .unwrap();
+    let (u, v) = ((drop + 1) % 3, (drop + 2) % 3);
+    let p = |i: usize| (vs[i][u], vs[i][v]);
+    //-- the orientation of the ring in the projection
+    let sign = if n[drop] < 0.0 { -1.0 } else { 1.0 };
+    let cross = |a: (f64, f64), b: (f64, f64), c: (f64, f64)| {
+        sign * ((b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0))
+    };
+    let mut re: Vec<[usize; 3]> = Vec::new();
+    while r.len() > 3 {
+        let m = r.len();
+        let ear = (0..m).find(|k| {
+            let (i0, i1, i2) = (r[(k + m - 1) % m], r[*k], r[(k + 1) % m]);
+            let (a, b, c) = (p(i0), p(i1), p(i2));
+            cross(a, b, c) > 0.0
+                && r.iter().filter(|i| ![i0, i1, i2].contains(i)).all(|i| {
+                    let q = p(*i);
+                    cross(a, b, q) < 0.0 || cross(b, c, q) < 0.0 || cross(c, a, q) < 0.0
+                })
+        });
+        //-- a degenerate ring (eg collinear points): the rest is a fan
+        let k = ear.unwrap_or(1);
+        re.push([r[(k + m - 1) % m], r[k], r[(k + 1) % m]]);
+        r.remove(k);
+    }
+    re.push([r[0], r[1], r[2]]);
+    re
+}
+
+fn texture_content_key(t: &Value, base_dir: &Path) -> String {
+    //-- SHA-256 of the image and of the other properties of a texture
+    let mut props = t.clone();
+    let image = match props.as_object_mut().and_then(|m| m.remove("image")) {
+        Some(Value::String(x)) => x,
+        _ => String::new(),
+    };
+    let mut h = Sha256::new();
+    let data = image.strip_prefix("data:").and_then(|x| x.split_once(','));
+    if let Some((_, d)) = data {
+        match base64::engine::general_purpose::STANDARD.decode(d) {
+            Ok(b) => h.update(b),
+            Err(_) => h.update(d.as_bytes()),
+        }
+    } else if image.contains("://") {
+        h.update(image.as_bytes());
+    } else {
+        match std::fs::read(base_dir.join(&image)) {
+            Ok(b) => h.update(b),
+            Err(_) => h.update(normalise_path(&image).as_bytes()),
+        }
+    }
+    h.update(serde_json::to_string(&props).unwrap().as_bytes());
+    h.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn normalise_path(p: &str) -> String {
+    //-- "./a/../b\\c.jpg" => "b/c.jpg"
+    let mut re: Vec<&str> = Vec::new();
+    for c in p.split(['/', '\\']) {
+        match c {
+            "" | "." => (),
+            ".." if re.last().is_some_and(|x| *x != "..") => {
+                re.pop();
+            }
+            _ => re.push(c),
+        }
+    }
+    re.join("/")
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct Stats {
+    pub city_objects: usize,
+    pub geometries: usize,
+    //-- the number of attributes of all the CityObjects
+    pub attributes: usize,
+    pub vertices: usize,
+    pub materials: usize,
+    pub textures: usize,
+    pub vertices_texture: usize,
+    pub geometry_templates: usize,
+    pub per_type: BTreeMap<String, TypeStats>,
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct TypeStats {
+    //-- the number of CityObjects of the type
+    pub count: usize,
+    //-- the vertices of each geometry (a vertex shared by 2 geometries is counted twice)
+    pub vertices: usize,
+    pub geometries: usize,
+    pub lods: BTreeSet<String>,
+    pub textured: bool,
+}
+
+impl TypeStats {
+    pub fn average_geometries(&self) -> f64 {
+        match self.count {
+            0 => 0.0,
+            n => self.geometries as f64 / n as f64,
+        }
+    }
+}
+
+impl Stats {
+    pub fn rows(&self) -> [(&'static str, usize); 8] {
+        //-- with the names of the CityJSON specifications
+        [
+            ("CityObjects", self.city_objects),
+            ("geometries", self.geometries),
+            ("attributes", self.attributes),
+            ("vertices", self.vertices),
+            ("materials", self.materials),
+            ("textures", self.textures),
+            ("vertices-texture", self.vertices_texture),
+            ("geometry-templates", self.geometry_templates),
+        ]
+    }
+}
+
+//-- what remove_duplicate_vertices() and retransform() did
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CollectReport {
+    pub vertices_before: usize,
+    pub vertices_after: usize,
+    pub translate_before: Vec<f64>,
+    pub translate_after: Vec<f64>,
+}
+
+impl CollectReport {
+    pub fn new(cj: &CityJSON) -> Self {
+        //-- nothing done yet
+        CollectReport {
+            vertices_before: cj.vertices.len(),
+            vertices_after: cj.vertices.len(),
+            translate_before: cj.transform.translate.clone(),
+            translate_after: cj.transform.translate.clone(),
+        }
+    }
+    pub fn then(self, next: CollectReport) -> Self {
+        //-- self followed by next
+        CollectReport {
+            vertices_before: self.vertices_before,
+            vertices_after: next.vertices_after,
+            translate_before: self.translate_before,
+            translate_after: next.translate_after,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum AttributeType {
+    String,
+    Number,
+    Bool,
+    Array,
+    Object,
+    //-- only null values
+    Null,
+    //-- values of different types
+    Mixed,
+}
+
+//-- what attribute_schema() found for one attribute key
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct AttributeInfo {
+    #[serde(rename = "type")]
+    pub thetype: AttributeType,
+    //-- the number of CityObjects with that key (also when null)
+    pub count: usize,
+    //-- the same, per type of CityObject
+    pub count_per_type: BTreeMap<String, usize>,
+}
+
+//-- what vertex_precision_report() found, in the units of the CRS
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PrecisionReport {
+    pub scale: Vec<f64>,
+    //-- None if all the vertices have the same coordinate on that axis
+    pub min_spacing: Vec<Option<f64>>,
+    pub lossless_scale: Vec<f64>,
+}
+
+impl PrecisionReport {
+    pub fn recommendation(&self) -> String {
+        //-- a coarser lossless scale: the current one over-resolves the vertices; a spacing
+        //-- equal to the scale: some distinct vertices may have been merged (under-resolved)
+        let coarser = (0..3).any(|i| self.lossless_scale[i] > self.scale[i] * (1.0 + 1e-9));
+        let at_limit = (0..3).any(|i| {
+            self.min_spacing[i].is_some_and(|d| (d - self.scale[i]).abs() <= self.scale[i] * 1e-9)
+        });
+        if coarser {
+            format!(
+                "the scale {:?} over-resolves the vertices, {:?} would be lossless",
+                self.scale, self.lossless_scale
+            )
+        } else if at_limit {
+            format!(
+                "some vertices are {:?} apart, the scale may under-resolve them (use a finer one)",
+                self.scale
+            )
+        } else {
+            format!("the scale {:?} is adequate", self.scale)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortingStrategy {
+    //-- by id
+    Alphabetical,
+    //-- by type of the main CityObject, then by id
+    Type,
+    //-- shuffled, the same seed always gives the same order
+    Random(u64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DupStrategy {
+    Error,
+    SuffixNumeric,
+    KeepFirst,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CjseqError {
+    //-- the versions of 2 documents differ (eg when merging/collecting)
+    VersionMismatch { expected: String, found: String },
+    //-- nothing to read (eg an empty stdin or file)
+    EmptyInput,
+    //-- a feature that cannot be added, eg an index of its boundaries out of range
+    InvalidValue { feature: String, reason: String },
+    CityJson(String),
+}
+impl fmt::Display for CjseqError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CjseqError::VersionMismatch { expected, found } => write!(
+                f,
+                "version mismatch: expected CityJSON v{}, found v{}",
+                expected, found
+            ),
+            CjseqError::EmptyInput => write!(f, "no input received"),
+            CjseqError::InvalidValue { feature, reason } => {
+                write!(f, "feature #{}: {}", feature, reason)
+            }
+            CjseqError::CityJson(e) => write!(f, "{}", e),
+        }
+    }
+}
+impl std::error::Error for CjseqError {}
+impl From<String> for CjseqError {
+    fn from(err: String) -> Self {
+        CjseqError::CityJson(err)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CityJSONFeature {
+    #[serde(rename = "type")]
+    pub thetype: String,
+    pub id: String,
+    #[serde(rename = "CityObjects")]
+    pub city_objects: HashMap<String, CityObject>,
+    pub vertices: Vec<Vec<i64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub appearance: Option<Appearance>,
+    //-- local templates (not in the specifications): the GeometryInstances of the feature then
+    //-- refer to them and not to those of the 1st line, and collect moves them to the CityJSON
+    #[serde(rename = "geometry-templates")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub geometry_templates: Option<GeometryTemplates>,
+}
+impl FromStr for CityJSON {
+    type Err = serde_json::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(strip_bom(s))
+    }
+}
+impl CityJSON {
+    pub fn from_str_lenient(s: &str) -> Result<Self, serde_json::Error> {
+        //-- also accepts a file without "transform" (real coordinates as vertices)
+        let mut v: Value = serde_json::from_str(&strip_trailing_commas(strip_bom(s)))?;
+        add_missing_transform(&mut v);
+        serde_json::from_value(v)
+    }
+    pub fn from_str_strict(s: &str) -> Result<Self, String> {
+        //-- the unknown members of the CityJSON and of its CityObjects are errors (eg a typo
+        //-- like "CityObjcts"), those starting with "+" are allowed (Extensions)
+        let v: Value = serde_json::from_str(strip_bom(s)).map_err(|e| e.to_string())?;
+        check_unknown_members(&v, &CITYJSON_MEMBERS)?;
+        serde_json::from_value(v).map_err(|e| e.to_string())
+    }
+    pub fn from_value(v: Value) -> Result<Self, serde_json::Error> {
+        //-- no string round-trip when the JSON is already parsed (eg Python/WASM bindings)
+        serde_json::from_value(v)
+    }
+    pub fn to_value(&self) -> Value {
+        //-- serialised by reference, no clone needed
+        serde_json::to_value(self).unwrap()
+    }
+}
+
+impl Default for CityJSONFeature {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl CityJSONFeature {
+    pub fn new() -> Self {
+        let co: HashMap<String, CityObject> = HashMap::new();
+        let v: Vec<Vec<i64>> = Vec::new();
+        CityJSONFeature {
+            thetype: "CityJSONFeature".to_string(),
+            id: "".to_string(),
+            city_objects: co,
+            vertices: v,
+            appearance: None,
+            geometry_templates: None,
+        }
+    }
+    pub fn add_co(&mut self, id: String, co: CityObject) {
+        self.city_objects.insert(id, co);
+    }
+    pub fn bbox(&self, transform: &Transform) -> Option<[f64; 6]> {
+        //-- in the CRS of the file, None if there are no vertices
+        let mut re: Option<[f64; 6]> = None;
+        for v in &self.vertices {
+            let p = transform.real_coordinates(v);
+            match &mut re {
+                Some(b) => {
+                    for i in 0..3 {
+                        b[i] = b[i].min(p[i]);
+                        b[i + 3] = b[i + 3].max(p[i]);
+                    }
+                }
+                None => re = Some([p[0], p[1], p[2], p[0], p[1], p[2]]),
+            }
+        }
+        re
+    }
+    pub fn main_centroid(&self, transform: &Transform) -> [f64; 3] {
+        //-- centroid of the main CityObject, or of all the vertices if it has no geometry
+        match self
+            .city_objects
+            .get(&self.id)
+            .and_then(|co| co.centroid(&self.vertices, transform))
+        {
+            Some(c) => c,
+            None => {
+                let ci = self.centroid();
+                [
+                    (ci[0] * transform.scale[0]) + transform.translate[0],
+                    (ci[1] * transform.scale[1]) + transform.translate[1],
+                    (ci[2] * transform.scale[2]) + transform.translate[2],
+                ]
+            }
+        }
+    }
+    pub fn add_geometry_template_reference(&mut self, gts: &GeometryTemplates, i: usize) -> usize {
+        //-- template i of gts (eg those of the 1st line) is copied in the local templates of
+        //-- the feature, with its vertices; returns its local index (an identical template is
+        //-- reused)
+        let vts = gts.vertices();
+        let mut g = gts.templates[i].clone();
+        let mut oldnew: HashMap<usize, usize> = HashMap::new();
+        g.update_geometry_boundaries(&mut oldnew);
+        let mut newvts: Vec<Vec<f64>> = vec![vec![]; oldnew.len()];
+        for (old, new) in &oldnew {
+            newvts[*new] = vts[*old].clone();
+        }
+        let local = self
+            .geometry_templates
+            .get_or_insert_with(GeometryTemplates::new);
+        local.add_template(g, newvts)
+    }
+    pub fn localize_templates(&mut self, gts: &GeometryTemplates) {
+        //-- the feature gets a copy of the templates it uses, it is then self-contained
+        if self.geometry_templates.is_some() {
+            return;
+        }
+        let mut ids: Vec<String> = self.city_objects.keys().cloned().collect();
+        ids.sort();
+        for id in ids {
+            let n = self.city_objects[&id]
+                .geometry
+                .as_ref()
+                .map_or(0, |x| x.len());
+            for k in 0..n {
+                let g = &self.city_objects[&id].geometry.as_ref().unwrap()[k];
+                let t = match (&g.thetype, g.template) {
+                    (GeometryType::GeometryInstance, Some(t)) if t < gts.templates.len() => t,
+                    _ => continue,
+                };
+                let t2 = self.add_geometry_template_reference(gts, t);
+                let co = self.city_objects.get_mut(&id).unwrap();
+                co.geometry.as_mut().unwrap()[k].template = Some(t2);
+            }
+        }
+    }
+    pub fn check_template_indices(
+        &self,
+        header: Option<&GeometryTemplates>,
+    ) -> Result<(), CjseqError> {
+        //-- the templates of the GeometryInstances must be in the local templates, or in those
+        //-- of the 1st line if the feature has none
+        let n = match (&self.geometry_templates, header) {
+            (Some(x), _) | (None, Some(x)) => x.templates.len(),
+            (None, None) => 0,
+        };
+        let mut ids: Vec<&String> = self.city_objects.keys().collect();
+        ids.sort();
+        for id in ids {
+            for g in self.city_objects[id].geometry.iter().flatten() {
+                if g.thetype != GeometryType::GeometryInstance {
+                    continue;
+                }
+                match g.template {
+                    Some(t) if t < n => (),
+                    t => {
+                        let t = t.map_or("null".to_string(), |x| x.to_string());
+                        return Err(CjseqError::InvalidValue {
+                            feature: self.id.clone(),
+                            reason: format!(
+                                "CityObject #{} uses the template #{} but there are only {} templates",
+                                id, t, n
+                            ),
+                        });
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+    pub fn check_vertex_indices(&self) -> Result<(), CjseqError> {
+        //-- all the indices of the boundaries must refer to one of the vertices of the feature
+        let n = self.vertices.len();
+        let mut ids: Vec<&String> = self.city_objects.keys().collect();
+        ids.sort();
+        for id in ids {
+            for g in self.city_objects[id].geometry.iter().flatten() {
+                if let Some(i) = g.vertex_indices().last().filter(|i| **i >= n) {
+                    return Err(CjseqError::InvalidValue {
+                        feature: self.id.clone(),
+                        reason: format!(
+                            "CityObject #{} uses the vertex #{} but the feature has only {} vertices",
+                            id, i, n
+                        ),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+    pub fn centroid(&self) -> Vec<f64> {
+        let mut totals: Vec<f64> = vec![0., 0., 0.];
+        for v in &self.vertices {
+            for (t, c) in totals.iter_mut().zip(v) {
+                *t += *c as f64;
+            }
+        }
+        for t in &mut totals {
+            *t /= self.vertices.len() as f64;
+        }
+        totals
+    }
+    pub fn remove_children(&mut self) {
+        //-- only the main CityObject is kept, its "children" are removed
+        self.reslice(|cj, id| {
+            let children = cj.city_objects[id].get_children_keys();
+            cj.retain_city_objects(|k, _| !children.iter().any(|c| c == k));
+        });
+    }
+    pub fn keep_only_children(&mut self) {
+        //-- the main CityObject is kept (so that the feature is valid) but without its geometry
+        self.reslice(|cj, id| cj.city_objects.get_mut(id).unwrap().geometry = None);
+    }
+    fn reslice<F: FnOnce(&mut CityJSON, &str)>(&mut self, f: F) {
+        //-- the vertices and the appearance not used anymore are removed with get_cjfeature()
+        if !self.city_objects.contains_key(&self.id) {
+            return;
+        }
+        let id = self.id.clone();
+        let mut cj = CityJSON::new();
+        //-- cj has no templates, the GeometryInstances refer to those of the 1st line
+        cj.add_checked_cjf(std::mem::take(self));
+        f(&mut cj, &id);
+        *self = cj.get_cjfeature(&id).unwrap();
+    }
+}
+
+impl FromStr for CityJSONFeature {
+    type Err = serde_json::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(strip_bom(s))
+    }
+}
+impl CityJSONFeature {
+    pub fn from_str_lenient(s: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(&strip_trailing_commas(strip_bom(s)))
+    }
+    pub fn from_str_strict(s: &str) -> Result<Self, String> {
+        //-- as CityJSON::from_str_strict()
+        let v: Value = serde_json::from_str(strip_bom(s)).map_err(|e| e.to_string())?;
+        check_unknown_members(&v, &CITYJSONFEATURE_MEMBERS)?;
+        serde_json::from_value(v).map_err(|e| e.to_string())
+    }
+    pub fn from_value(v: Value) -> Result<Self, serde_json::Error> {
+        //-- no string round-trip when the JSON is already parsed (eg Python/WASM bindings)
+        serde_json::from_value(v)
+    }
+    pub fn to_value(&self) -> Value {
+        //-- serialised by reference, no clone needed
+        serde_json::to_value(self).unwrap()
+    }
+}
+
+pub fn sequence_extent<R: BufRead>(r: R) -> std::io::Result<Option<[f64; 6]>> {
+    //-- the extent of all the features of a CityJSONSeq, one feature at a time in memory;
+    //-- rounded to the precision of the transform, None if there are no vertices
+    let mut lines = r.lines();
+    let header = match lines.next() {
+        Some(l) => CityJSON::from_str(&l?)?,
+        None => return Ok(None),
+    };
+    let mut re: Option<[f64; 6]> = None;
+    for l in lines {
+        let l = l?;
+        if l.trim().is_empty() {
+            continue;
+        }
+        let cjf = CityJSONFeature::from_str(&l)?;
+        if let Some(b) = cjf.bbox(&header.transform) {
+            match &mut re {
+                Some(e) => {
+                    for i in 0..3 {
+                        e[i] = e[i].min(b[i]);
+                        e[i + 3] = e[i + 3].max(b[i + 3]);
+                    }
+                }
+                None => re = Some(b),
+            }
+        }
+    }
+    let f = 10_f64.powi(header.transform.decimals() as i32);
+    Ok(re.map(|e| e.map(|x| (x * f).round() / f)))
+}
+
+fn rename_city_objects(cos: &mut HashMap<String, CityObject>, oldnew: &HashMap<String, String>) {
+    let old = std::mem::take(cos);
+    for (key, mut co) in old {
+        for ids in [&mut co.children, &mut co.parents].into_iter().flatten() {
+            for id in ids.iter_mut() {
+                if let Some(x) = oldnew.get(id) {
+                    *id = x.clone();
+                }
+            }
+        }
+        let newkey = oldnew.get(&key).cloned().unwrap_or(key);
+        cos.insert(newkey, co);
+    }
+}
+
+fn add_missing_transform(j: &mut Value) {
+    //-- vertices with real coordinates (floats) are quantized with a transform: the
+    //-- translate is the minimum, the scale is given by the number of decimals used
+    //-- (at most 6, ie micrometres)
+    if j.get("transform").is_some() {
+        return;
+    }
+    let vs: Vec<Vec<f64>> = match j.get("vertices").and_then(|x| x.as_array()) {
+        Some(a) => a
+            .iter()
+            .map(|v| {
+                v.as_array()
+                    .map(|c| c.iter().filter_map(|x| x.as_f64()).collect())
+                    .unwrap_or_default()
+            })
+            .collect(),
+        None => return,
+    };
+    if vs.iter().any(|v| v.len() != 3) {
+        return;
+    }
+    let mut decimals = 0;
+    let mut translate = [0.0, 0.0, 0.0];
+    if !vs.is_empty() {
+        translate = [f64::MAX, f64::MAX, f64::MAX];
+    }
+    for v in &vs {
+        for i in 0..3 {
+            translate[i] = translate[i].min(v[i]);
+            let s = v[i].to_string();
+            if let Some((_, d)) = s.split_once('.') {
+                decimals = decimals.max(d.len().min(6));
+            }
+        }
+    }
+    let scale = 10f64.powi(-(decimals as i32));
+    let t = Transform {
+        scale: vec![scale, scale, scale],
+        translate: translate.to_vec(),
+    };
+    let qs: Vec<Vec<i64>> = vs.iter().map(|v| t.quantize(v)).collect();
+    j["vertices"] = json!(qs);
+    j["transform"] = json!(t);
+}
+
+const CITYJSON_MEMBERS: [&str; 9] = [
+    "type",
+    "version",
+    "transform",
+    "CityObjects",
+    "vertices",
+    "metadata",
+    "appearance",
+    "geometry-templates",
+    "extensions",
+];
+const CITYJSONFEATURE_MEMBERS: [&str; 6] = [
+    "type",
+    "id",
+    "CityObjects",
+    "vertices",
+    "appearance",
+    "geometry-templates",
+];
+const CITYOBJECT_MEMBERS: [&str; 8] = [
+    "type",
+    "geographicalExtent",
+    "attributes",
+    "geometry",
+    "children",
+    "parents",
+    "children_roles",
+    "address",
+];
+
+//-- the types of the CityObjects (v2.0, the v1.1 ones are a subset)
+const FIRST_LEVEL_TYPES: [&str; 16] = [
+    "Bridge",
+    "Building",
+    "CityFurniture",
+    "CityObjectGroup",
+    "GenericCityObject",
+    "LandUse",
+    "OtherConstruction",
+    "PlantCover",
+    "Railway",
+    "Road",
+    "SolitaryVegetationObject",
+    "TINRelief",
+    "TransportSquare",
+    "Tunnel",
+    "WaterBody",
+    "Waterway",
+];
+const SECOND_LEVEL_TYPES: [&str; 17] = [
+    "BridgePart",
+    "BridgeInstallation",
+    "BridgeConstructiveElement",
+    "BridgeRoom",
+    "BridgeFurniture",
+    "BuildingPart",
+    "BuildingInstallation",
+    "BuildingConstructiveElement",
+    "BuildingFurniture",
+    "BuildingStorey",
+    "BuildingRoom",
+    "BuildingUnit",
+    "TunnelPart",
+    "TunnelInstallation",
+    "TunnelConstructiveElement",
+    "TunnelHollowSpace",
+    "TunnelFurniture",
+];
+
+fn check_unknown_members(j: &Value, known: &[&str]) -> Result<(), String> {
+    let unknown = |o: &Value, known: &[&str]| -> Vec<String> {
+        o.as_object()
+            .map(|m| {
+                m.keys()
+                    .filter(|k| !k.starts_with('+') && !known.contains(&k.as_str()))
+                    .map(|k| format!("\"{}\"", k))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let mut re: Vec<String> = unknown(j, known);
+    if let Some(cos) = j.get("CityObjects").and_then(|x| x.as_object()) {
+        let mut ids: Vec<&String> = cos.keys().collect();
+        ids.sort();
+        for id in ids {
+            for k in unknown(&cos[id], &CITYOBJECT_MEMBERS) {
+                re.push(format!("{} (CityObject #{})", k, id));
+            }
+        }
+    }
+    match re.is_empty() {
+        true => Ok(()),
+        false => Err(format!("unknown members: {}", re.join(", "))),
+    }
+}
+
+fn strip_bom(s: &str) -> &str {
+    s.strip_prefix('\u{feff}').unwrap_or(s)
+}
+
+fn strip_trailing_commas(s: &str) -> String {
+    //-- removes the commas directly followed by a '}' or a ']' (outside strings)
+    let mut re = String::with_capacity(s.len());
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+        } else if c == '"' {
+            in_string = true;
+        } else if c == ',' {
+            let next = s[i + 1..].trim_start().chars().next();
+            if next == Some('}') || next == Some(']') {
+                continue;
+            }
+        }
+        re.push(c);
+    }
+    re
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CityObject {
+    #[serde(rename = "type")]
+    pub thetype: String,
+    #[serde(rename = "geographicalExtent")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub geographical_extent: Option<Vec<f64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attributes: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub geometry: Option<Vec<Geometry>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub children: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parents: Option<Vec<String>>,
+    #[serde(flatten)]
+    other: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TypeLevel {
+    //-- eg Building, can be a feature on its own
+    FirstLevel,
+    //-- eg BuildingPart, must have a 1st-level parent
+    SecondLevel,
+    //-- "+Foo", its level is defined by the schema of the Extension
+    Extension,
+    Unknown,
+}
+
+impl CityObject {
+    pub fn is_group(&self) -> bool {
+        self.thetype == "CityObjectGroup"
+    }
+    pub fn new(thetype: &str) -> Self {
+        CityObject {
+            thetype: thetype.to_string(),
+            geographical_extent: None,
+            attributes: None,
+            geometry: None,
+            children: None,
+            parents: None,
+            other: json!(null),
+        }
+    }
+    pub fn add_geometry(&mut self, g: Geometry) {
+        self.geometry.get_or_insert_with(Vec::new).push(g);
+    }
+    pub fn set_geometry(&mut self, gs: Vec<Geometry>) {
+        //-- an empty list removes the "geometry"
+        self.geometry = if gs.is_empty() { None } else { Some(gs) };
+    }
+    pub fn is_toplevel(&self) -> bool {
+        match &self.parents {
+            Some(x) => x.is_empty(),
+            None => true,
+        }
+    }
+    pub fn bbox(&self, vertices: &[Vec<i64>], transform: &Transform) -> Option<[f64; 6]> {
+        //-- the "geographicalExtent" if there is one, otherwise computed from the vertices of
+        //-- its geometries; None if it has no geometry
+        if let Some(e) = self.geographical_extent.as_ref().filter(|e| e.len() == 6) {
+            return Some([e[0], e[1], e[2], e[3], e[4], e[5]]);
+        }
+        let mut re: Option<[f64; 6]> = None;
+        for g in self.geometry.iter().flatten() {
+            for i in g.vertex_indices() {
+                let p = match vertices.get(i) {
+                    Some(v) => transform.real_coordinates(v),
+                    None => continue,
+                };
+                let b = re.get_or_insert([p[0], p[1], p[2], p[0], p[1], p[2]]);
+                for j in 0..3 {
+                    b[j] = b[j].min(p[j]);
+                    b[j + 3] = b[j + 3].max(p[j]);
+                }
+            }
+        }
+        re
+    }
+    pub fn centroid(&self, vertices: &[Vec<i64>], transform: &Transform) -> Option<[f64; 3]> {
+        //-- average of the centroids of its geometries, None if it has no geometry
+        let cs: Vec<[f64; 3]> = self
+            .geometry
+            .iter()
+            .flatten()
+            .filter_map(|g| g.centroid(vertices, transform))
+            .collect();
+        if cs.is_empty() {
+            return None;
+        }
+        let mut re = [0.0, 0.0, 0.0];
+        for c in &cs {
+            for i in 0..3 {
+                re[i] += c[i] / cs.len() as f64;
+            }
+        }
+        Some(re)
+    }
+    fn semantic_surfaces(&self) -> impl Iterator<Item = &Value> {
+        self.geometry
+            .iter()
+            .flatten()
+            .filter_map(|g| g.semantics.as_ref())
+            .filter_map(|s| s.get("surfaces").and_then(|x| x.as_array()))
+            .flatten()
+    }
+    pub fn geometry_for_lod(&self, target: f64) -> Option<&Geometry> {
+        //-- the geometry whose LoD is the closest to target (the lowest one in case of a
+        //-- tie), those without a valid LoD are ignored
+        self.geometry
+            .iter()
+            .flatten()
+            .filter_map(|g| g.lod_f64().map(|l| ((l - target).abs(), l, g)))
+            .min_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)))
+            .map(|(_, _, g)| g)
+    }
+    pub fn aggregate_semantic_attribute(&self, key: &str) -> Vec<Value> {
+        //-- the values of that attribute of all the semantic surfaces, of all geometries
+        self.semantic_surfaces()
+            .filter_map(|sf| sf.get(key).cloned())
+            .collect()
+    }
+    pub fn material_summary(&self, appearance: Option<&Appearance>) -> AppearanceSummary {
+        //-- the names of the materials and the images of the textures used by the geometries,
+        //-- the indices not in the appearance are "missing"
+        let mut re = AppearanceSummary::default();
+        let ms = appearance.map(|a| a.material_names()).unwrap_or_default();
+        let ts = appearance.map(|a| a.texture_names()).unwrap_or_default();
+        for g in self.geometry.iter().flatten() {
+            for i in g.material_indices() {
+                match ms.get(i) {
+                    Some(m) => re.materials.insert(m.clone()),
+                    None => re.missing_materials.insert(i),
+                };
+            }
+            for i in g.texture_indices() {
+                match ts.get(i) {
+                    Some(t) => re.textures.insert(t.clone()),
+                    None => re.missing_textures.insert(i),
+                };
+            }
+        }
+        re
+    }
+    pub fn attribute_keys(&self) -> Vec<String> {
+        //-- sorted
+        let mut re: Vec<String> = match &self.attributes {
+            Some(Value::Object(m)) => m.keys().cloned().collect(),
+            _ => Vec::new(),
+        };
+        re.sort();
+        re
+    }
+    pub fn semantic_surface_types(&self) -> Vec<String> {
+        //-- the distinct types of semantic surfaces (sorted)
+        let mut re: Vec<String> = self
+            .aggregate_semantic_attribute("type")
+            .iter()
+            .filter_map(|t| t.as_str().map(|x| x.to_string()))
+            .collect();
+        re.sort();
+        re.dedup();
+        re
+    }
+    pub fn dedup_geometries(&mut self) {
+        //-- only the exact copies (same boundaries, semantics, appearance) are removed,
+        //-- the first one is kept
+        if let Some(gs) = &mut self.geometry {
+            let mut re: Vec<Geometry> = Vec::with_capacity(gs.len());
+            for g in gs.drain(..) {
+                if !re.contains(&g) {
+                    re.push(g);
+                }
+            }
+            *gs = re;
+        }
+    }
+    pub fn is_extension_type(&self) -> bool {
+        self.thetype.starts_with('+')
+    }
+    pub fn is_first_level_type(&self) -> bool {
+        FIRST_LEVEL_TYPES.contains(&self.thetype.as_str())
+    }
+    pub fn is_second_level_type(&self) -> bool {
+        SECOND_LEVEL_TYPES.contains(&self.thetype.as_str())
+    }
+    pub fn type_level(&self) -> TypeLevel {
+        if self.is_first_level_type() {
+            TypeLevel::FirstLevel
+        } else if self.is_second_level_type() {
+            TypeLevel::SecondLevel
+        } else if self.is_extension_type() {
+            TypeLevel::Extension
+        } else {
+            TypeLevel::Unknown
+        }
+    }
+    pub fn get_children_keys(&self) -> Vec<String> {
+        let mut re: Vec<String> = Vec::new();
+        if let Some(x) = &self.children {
+            for each in x {
+                re.push(each.to_string());
+            }
+        }
+        re
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum GeometryType {
+    MultiPoint,
+    MultiLineString,
+    MultiSurface,
+    CompositeSurface,
+    Solid,
+    MultiSolid,
+    CompositeSolid,
+    GeometryInstance,
+}
+impl GeometryType {
+    pub fn depth(&self) -> usize {
+        //-- the depth of the arrays of the boundaries, eg 3 for a MultiSurface
+        match self {
+            GeometryType::MultiPoint | GeometryType::GeometryInstance => 1,
+            GeometryType::MultiLineString => 2,
+            GeometryType::MultiSurface | GeometryType::CompositeSurface => 3,
+            GeometryType::Solid => 4,
+            GeometryType::MultiSolid | GeometryType::CompositeSolid => 5,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Geometry {
+    #[serde(rename = "type")]
+    pub thetype: GeometryType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lod: Option<String>,
+    pub boundaries: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub semantics: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub material: Option<HashMap<String, Material>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub texture: Option<HashMap<String, Texture>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template: Option<usize>,
+    #[serde(rename = "transformationMatrix")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transformation_matrix: Option<Value>,
+}
+impl Geometry {
+    pub fn new(
+        thetype: GeometryType,
+        lod: Option<&str>,
+        boundaries: impl Into<Value>,
+    ) -> Result<Self, String> {
+        //-- the boundaries from nested Vecs, eg a Vec<Vec<Vec<u32>>> for a MultiSurface, their
+        //-- depth must be the one of the type
+        let boundaries: Value = boundaries.into();
+        match boundaries_depth(&boundaries) {
+            Ok(Some(d)) if d != thetype.depth() => {
+                return Err(format!(
+                    "the boundaries of a {:?} have a depth of {}, not {}",
+                    thetype,
+                    d,
+                    thetype.depth()
+                ))
+            }
+            Err(e) => return Err(e),
+            _ => (),
+        }
+        Ok(Geometry {
+            thetype,
+            lod: lod.map(|l| l.to_string()),
+            boundaries,
+            semantics: None,
+            material: None,
+            texture: None,
+            template: None,
+            transformation_matrix: None,
+        })
+    }
+    pub fn boundaries_as<T: serde::de::DeserializeOwned>(&self) -> Result<T, String> {
+        //-- the boundaries as nested Vecs, eg let b: Vec<Vec<Vec<u32>>> for a MultiSurface;
+        //-- an error if their depth is not uniform or not the one of T
+        boundaries_depth(&self.boundaries)?;
+        serde_json::from_value(self.boundaries.clone())
+            .map_err(|e| format!("invalid boundaries for a {:?}: {}", self.thetype, e))
+    }
+    pub fn lod_f64(&self) -> Option<f64> {
+        self.lod.as_ref().and_then(|l| l.parse::<f64>().ok())
+    }
+    pub fn matrix(&self) -> Result<[f64; 16], String> {
+        //-- the "transformationMatrix" (row-major), mandatory for a GeometryInstance; the
+        //-- identity for the other geometries
+        let m = match &self.transformation_matrix {
+            Some(x) => x,
+            None if self.thetype == GeometryType::GeometryInstance => {
+                return Err("the GeometryInstance has no \"transformationMatrix\"".to_string())
+            }
+            None => return Ok(IDENTITY),
+        };
+        serde_json::from_value::<[f64; 16]>(m.clone())
+            .map_err(|_| "the \"transformationMatrix\" is not an array of 16 numbers".to_string())
+    }
+    pub fn apply_matrix(&self, p: [f64; 3]) -> [f64; 3] {
+        //-- a missing or invalid matrix is the identity, matrix() tells which one it is
+        apply_matrix(&self.matrix().unwrap_or(IDENTITY), p)
+    }
+    pub fn select_theme(&mut self, material: Option<&str>, texture: Option<&str>) {
+        //-- the other themes are removed (None: not modified)
+        if let (Some(t), Some(m)) = (material, &mut self.material) {
+            m.retain(|k, _| k == t);
+            if m.is_empty() {
+                self.material = None;
+            }
+        }
+        if let (Some(t), Some(m)) = (texture, &mut self.texture) {
+            m.retain(|k, _| k == t);
+            if m.is_empty() {
+                self.texture = None;
+            }
+        }
+    }
+    pub fn get_surfaces(&self) -> Vec<Vec<Vec<usize>>> {
+        //-- all the surfaces (each a list of rings) of the geometry, whatever its type
+        match self.thetype {
+            GeometryType::MultiSurface | GeometryType::CompositeSurface => {
+                serde_json::from_value(self.boundaries.clone()).unwrap()
+            }
+            GeometryType::Solid => {
+                let a: Vec<Vec<Vec<Vec<usize>>>> =
+                    serde_json::from_value(self.boundaries.clone()).unwrap();
+                a.into_iter().flatten().collect()
+            }
+            GeometryType::MultiSolid | GeometryType::CompositeSolid => {
+                let a: Vec<Vec<Vec<Vec<Vec<usize>>>>> =
+                    serde_json::from_value(self.boundaries.clone()).unwrap();
+                a.into_iter().flatten().flatten().collect()
+            }
+            _ => vec![],
+        }
+    }
+    pub fn vertex_indices(&self) -> Vec<usize> {
+        //-- the unique vertex indices referenced by the boundaries (sorted)
+        fn collect(v: &Value, re: &mut Vec<usize>) {
+            match v {
+                Value::Array(a) => a.iter().for_each(|x| collect(x, re)),
+                Value::Number(n) => {
+                    if let Some(i) = n.as_u64() {
+                        re.push(i as usize);
+                    }
+                }
+                _ => (),
+            }
+        }
+        let mut re: Vec<usize> = Vec::new();
+        collect(&self.boundaries, &mut re);
+        re.sort_unstable();
+        re.dedup();
+        re
+    }
+    pub fn material_indices(&self) -> Vec<usize> {
+        //-- the unique materials referenced, all themes (sorted)
+        fn collect(v: &Value, re: &mut Vec<usize>) {
+            match v {
+                Value::Array(a) => a.iter().for_each(|x| collect(x, re)),
+                Value::Number(n) => re.extend(n.as_u64().map(|i| i as usize)),
+                _ => (),
             }
-            None => return true,
         }
+        let mut re: Vec<usize> = Vec::new();
+        for m in self.material.iter().flat_map(|x| x.values()) {
+            re.extend(m.value);
+            if let Some(v) = &m.values {
+                collect(v, &mut re);
+            }
+        }
+        re.sort_unstable();
+        re.dedup();
+        re
     }
-    pub fn get_children_keys(&self) -> Vec<String> {
-        let mut re: Vec<String> = Vec::new();
-        match &self.children {
-            Some(x) => {
-                for each in x {
-                    re.push(each.to_string());
+    pub fn surface_materials(&self, theme: &str) -> Vec<Option<usize>> {
+        //-- the material of each surface of get_surfaces() for that theme (None: no material)
+        fn collect(v: &Value, re: &mut Vec<Option<usize>>) {
+            match v {
+                Value::Array(a) => a.iter().for_each(|x| collect(x, re)),
+                x => re.push(x.as_u64().map(|i| i as usize)),
+            }
+        }
+        let n = self.get_surfaces().len();
+        let m = match self.material.as_ref().and_then(|x| x.get(theme)) {
+            Some(x) => x,
+            None => return vec![None; n],
+        };
+        let mut re: Vec<Option<usize>> = Vec::new();
+        match (&m.values, m.value) {
+            (Some(v), _) => collect(v, &mut re),
+            (None, Some(i)) => re = vec![Some(i); n],
+            (None, None) => (),
+        }
+        re.resize(n, None);
+        re
+    }
+    pub fn texture_indices(&self) -> Vec<usize> {
+        //-- the unique textures referenced (the 1st index of each ring), all themes (sorted)
+        fn collect(v: &Value, re: &mut Vec<usize>) {
+            if let Value::Array(a) = v {
+                if a.iter().any(|x| x.is_array()) {
+                    a.iter().for_each(|x| collect(x, re));
+                } else if let Some(i) = a.first().and_then(|x| x.as_u64()) {
+                    re.push(i as usize);
                 }
             }
-            None => (),
         }
+        let mut re: Vec<usize> = Vec::new();
+        for t in self.texture.iter().flat_map(|x| x.values()) {
+            if let Some(v) = &t.values {
+                collect(v, &mut re);
+            }
+        }
+        re.sort_unstable();
+        re.dedup();
         re
     }
-}
-
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
-pub enum GeometryType {
-    MultiPoint,
-    MultiLineString,
-    MultiSurface,
-    CompositeSurface,
-    Solid,
-    MultiSolid,
-    CompositeSolid,
-    GeometryInstance,
-}
-
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct Geometry {
-    #[serde(rename = "type")]
-    pub thetype: GeometryType,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub lod: Option<String>,
-    pub boundaries: Value,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub semantics: Option<Value>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub material: Option<HashMap<String, Material>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub texture: Option<HashMap<String, Texture>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub template: Option<usize>,
-    #[serde(rename = "transformationMatrix")]
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub transformation_matrix: Option<Value>,
-}
-impl Geometry {
+    pub fn is_empty(&self) -> bool {
+        //-- no vertex is referenced, eg "boundaries": [] or [[[]]]
+        self.vertex_indices().is_empty()
+    }
+    pub fn centroid(&self, vertices: &[Vec<i64>], transform: &Transform) -> Option<[f64; 3]> {
+        //-- in the CRS of the file, None if no (valid) vertices are referenced
+        let vs: Vec<[f64; 3]> = self
+            .vertex_indices()
+            .iter()
+            .filter_map(|i| vertices.get(*i))
+            .map(|v| transform.real_coordinates(v))
+            .collect();
+        if vs.is_empty() {
+            return None;
+        }
+        let mut re = [0.0, 0.0, 0.0];
+        for v in &vs {
+            for i in 0..3 {
+                re[i] += v[i] / vs.len() as f64;
+            }
+        }
+        Some(re)
+    }
     pub fn update_geometry_boundaries(&mut self, violdnew: &mut HashMap<usize, usize>) {
         match self.thetype {
             GeometryType::MultiPoint => {
                 let a: Vec<usize> = serde_json::from_value(self.boundaries.clone()).unwrap();
                 let mut a2 = a.clone();
                 for (i, x) in a.iter().enumerate() {
-                    let kk = violdnew.get(&x);
-                    if kk.is_none() {
+                    let kk = violdnew.get(x);
+                    if let Some(kk) = kk {
+                        a2[i] = *kk;
+                    } else {
                         let l = violdnew.len();
                         violdnew.insert(*x, l);
                         a2[i] = l;
-                    } else {
-                        let kk = kk.unwrap();
-                        a2[i] = *kk;
                     }
                 }
                 self.boundaries = serde_json::to_value(&a2).unwrap();
@@ -346,14 +2581,13 @@ impl Geometry {
                 for (i, x) in a.iter().enumerate() {
                     for (j, y) in x.iter().enumerate() {
                         // r.push(z);
-                        let kk = violdnew.get(&y);
-                        if kk.is_none() {
+                        let kk = violdnew.get(y);
+                        if let Some(kk) = kk {
+                            a2[i][j] = *kk;
+                        } else {
                             let l = violdnew.len();
                             violdnew.insert(*y, l);
                             a2[i][j] = l;
-                        } else {
-                            let kk = kk.unwrap();
-                            a2[i][j] = *kk;
                         }
                     }
                 }
@@ -366,14 +2600,13 @@ impl Geometry {
                 for (i, x) in a.iter().enumerate() {
                     for (j, y) in x.iter().enumerate() {
                         for (k, z) in y.iter().enumerate() {
-                            let kk = violdnew.get(&z);
-                            if kk.is_none() {
+                            let kk = violdnew.get(z);
+                            if let Some(kk) = kk {
+                                a2[i][j][k] = *kk;
+                            } else {
                                 let l = violdnew.len();
                                 violdnew.insert(*z, l);
                                 a2[i][j][k] = l;
-                            } else {
-                                let kk = kk.unwrap();
-                                a2[i][j][k] = *kk;
                             }
                         }
                     }
@@ -388,14 +2621,13 @@ impl Geometry {
                     for (j, y) in x.iter().enumerate() {
                         for (k, z) in y.iter().enumerate() {
                             for (l, zz) in z.iter().enumerate() {
-                                let kk = violdnew.get(&zz);
-                                if kk.is_none() {
+                                let kk = violdnew.get(zz);
+                                if let Some(kk) = kk {
+                                    a2[i][j][k][l] = *kk;
+                                } else {
                                     let l2 = violdnew.len();
                                     violdnew.insert(*zz, l2);
                                     a2[i][j][k][l] = l2;
-                                } else {
-                                    let kk = kk.unwrap();
-                                    a2[i][j][k][l] = *kk;
                                 }
                             }
                         }
@@ -412,14 +2644,13 @@ impl Geometry {
                         for (k, z) in y.iter().enumerate() {
                             for (l, zz) in z.iter().enumerate() {
                                 for (m, zzz) in zz.iter().enumerate() {
-                                    let kk = violdnew.get(&zzz);
-                                    if kk.is_none() {
+                                    let kk = violdnew.get(zzz);
+                                    if let Some(kk) = kk {
+                                        a2[i][j][k][l][m] = *kk;
+                                    } else {
                                         let l2 = violdnew.len();
                                         violdnew.insert(*zzz, l2);
                                         a2[i][j][k][l][m] = l2;
-                                    } else {
-                                        let kk = kk.unwrap();
-                                        a2[i][j][k][l][m] = *kk;
                                     }
                                 }
                             }
@@ -432,14 +2663,13 @@ impl Geometry {
                 let a: Vec<usize> = serde_json::from_value(self.boundaries.clone()).unwrap();
                 let mut a2 = a.clone();
                 for (i, x) in a.iter().enumerate() {
-                    let kk = violdnew.get(&x);
-                    if kk.is_none() {
+                    let kk = violdnew.get(x);
+                    if let Some(kk) = kk {
+                        a2[i] = *kk;
+                    } else {
                         let l = violdnew.len();
                         violdnew.insert(*x, l);
                         a2[i] = l;
-                    } else {
-                        let kk = kk.unwrap();
-                        a2[i] = *kk;
                     }
                 }
                 self.boundaries = serde_json::to_value(&a2).unwrap();
@@ -525,208 +2755,439 @@ impl Geometry {
     }
 
     pub fn update_material(&mut self, m_oldnew: &mut HashMap<usize, usize>) {
-        match &mut self.material {
-            Some(x) => {
-                for (_key, mat) in &mut *x {
-                    //-- material.value
-                    if mat.value.is_some() {
-                        let thevalue: usize = mat.value.unwrap();
-                        let r = m_oldnew.get(&thevalue);
-                        if r.is_none() {
-                            let l = m_oldnew.len();
-                            m_oldnew.insert(thevalue, l);
-                            mat.value = Some(l);
-                        } else {
-                            let r2 = r.unwrap();
-                            mat.value = Some(*r2);
-                        }
-                        continue;
+        if let Some(x) = &mut self.material {
+            //-- themes sorted so that the new indices are always the same
+            let mut themes: Vec<(&String, &mut Material)> = x.iter_mut().collect();
+            themes.sort_by(|a, b| a.0.cmp(b.0));
+            for (_, mat) in themes {
+                //-- material.value
+                if mat.value.is_some() {
+                    let thevalue: usize = mat.value.unwrap();
+                    let r = m_oldnew.get(&thevalue);
+                    if let Some(r2) = r {
+                        mat.value = Some(*r2);
+                    } else {
+                        let l = m_oldnew.len();
+                        m_oldnew.insert(thevalue, l);
+                        mat.value = Some(l);
                     }
-                    //-- else it's material.values (which differs per geom type)
-                    match self.thetype {
-                        GeometryType::MultiPoint | GeometryType::MultiLineString => (),
-                        GeometryType::MultiSurface | GeometryType::CompositeSurface => {
-                            if mat.values.is_some() {
-                                let a: Vec<Option<usize>> =
-                                    serde_json::from_value(mat.values.take().into()).unwrap();
-                                let mut a2 = a.clone();
-                                for (i, x) in a.iter().enumerate() {
-                                    if x.is_some() {
-                                        let y2 = m_oldnew.get(&x.unwrap());
-                                        if y2.is_none() {
-                                            let l = m_oldnew.len();
-                                            m_oldnew.insert(x.unwrap(), l);
-                                            a2[i] = Some(l);
-                                        } else {
-                                            let y2 = y2.unwrap();
-                                            a2[i] = Some(*y2);
-                                        }
+                    continue;
+                }
+                //-- else it's material.values (which differs per geom type)
+                match self.thetype {
+                    //-- no surfaces, the material of a GeometryInstance is that of its template
+                    GeometryType::MultiPoint
+                    | GeometryType::MultiLineString
+                    | GeometryType::GeometryInstance => (),
+                    GeometryType::MultiSurface | GeometryType::CompositeSurface => {
+                        if mat.values.is_some() {
+                            let a: Vec<Option<usize>> =
+                                serde_json::from_value(mat.values.take().into()).unwrap();
+                            let mut a2 = a.clone();
+                            for (i, x) in a.iter().enumerate() {
+                                if x.is_some() {
+                                    let y2 = m_oldnew.get(&x.unwrap());
+                                    if let Some(y2) = y2 {
+                                        a2[i] = Some(*y2);
+                                    } else {
+                                        let l = m_oldnew.len();
+                                        m_oldnew.insert(x.unwrap(), l);
+                                        a2[i] = Some(l);
                                     }
                                 }
-                                mat.values = Some(serde_json::to_value(&a2).unwrap());
                             }
+                            mat.values = Some(serde_json::to_value(&a2).unwrap());
                         }
-                        GeometryType::Solid => {
-                            if mat.values.is_some() {
-                                let a: Vec<Vec<Option<usize>>> =
-                                    serde_json::from_value(mat.values.take().into()).unwrap();
-                                let mut a2 = a.clone();
-                                for (i, x) in a.iter().enumerate() {
-                                    for (j, y) in x.iter().enumerate() {
-                                        if y.is_some() {
-                                            let y2 = m_oldnew.get(&y.unwrap());
-                                            if y2.is_none() {
-                                                let l = m_oldnew.len();
-                                                m_oldnew.insert(y.unwrap(), l);
-                                                a2[i][j] = Some(l);
-                                            } else {
-                                                let y2 = y2.unwrap();
-                                                a2[i][j] = Some(*y2);
-                                            }
+                    }
+                    GeometryType::Solid => {
+                        if mat.values.is_some() {
+                            let a: Vec<Vec<Option<usize>>> =
+                                serde_json::from_value(mat.values.take().into()).unwrap();
+                            let mut a2 = a.clone();
+                            for (i, x) in a.iter().enumerate() {
+                                for (j, y) in x.iter().enumerate() {
+                                    if y.is_some() {
+                                        let y2 = m_oldnew.get(&y.unwrap());
+                                        if let Some(y2) = y2 {
+                                            a2[i][j] = Some(*y2);
+                                        } else {
+                                            let l = m_oldnew.len();
+                                            m_oldnew.insert(y.unwrap(), l);
+                                            a2[i][j] = Some(l);
                                         }
                                     }
                                 }
-                                mat.values = Some(serde_json::to_value(&a2).unwrap());
                             }
+                            mat.values = Some(serde_json::to_value(&a2).unwrap());
                         }
-                        GeometryType::MultiSolid | GeometryType::CompositeSolid => {
-                            if mat.values.is_some() {
-                                let a: Vec<Vec<Vec<Option<usize>>>> =
-                                    serde_json::from_value(mat.values.take().into()).unwrap();
-                                let mut a2 = a.clone();
-                                for (i, x) in a.iter().enumerate() {
-                                    for (j, y) in x.iter().enumerate() {
-                                        for (k, z) in y.iter().enumerate() {
-                                            if z.is_some() {
-                                                let y2 = m_oldnew.get(&z.unwrap());
-                                                if y2.is_none() {
-                                                    let l = m_oldnew.len();
-                                                    m_oldnew.insert(z.unwrap(), l);
-                                                    a2[i][j][k] = Some(l);
-                                                } else {
-                                                    let y2 = y2.unwrap();
-                                                    a2[i][j][k] = Some(*y2);
-                                                }
+                    }
+                    GeometryType::MultiSolid | GeometryType::CompositeSolid => {
+                        if mat.values.is_some() {
+                            let a: Vec<Vec<Vec<Option<usize>>>> =
+                                serde_json::from_value(mat.values.take().into()).unwrap();
+                            let mut a2 = a.clone();
+                            for (i, x) in a.iter().enumerate() {
+                                for (j, y) in x.iter().enumerate() {
+                                    for (k, z) in y.iter().enumerate() {
+                                        if z.is_some() {
+                                            let y2 = m_oldnew.get(&z.unwrap());
+                                            if let Some(y2) = y2 {
+                                                a2[i][j][k] = Some(*y2);
+                                            } else {
+                                                let l = m_oldnew.len();
+                                                m_oldnew.insert(z.unwrap(), l);
+                                                a2[i][j][k] = Some(l);
                                             }
                                         }
                                     }
                                 }
-                                mat.values = Some(serde_json::to_value(&a2).unwrap());
                             }
+                            mat.values = Some(serde_json::to_value(&a2).unwrap());
                         }
-                        GeometryType::GeometryInstance => todo!(),
                     }
                 }
-                self.material = Some(x.clone());
             }
-            None => (),
+            self.material = Some(x.clone());
         }
     }
     pub fn update_texture(
         &mut self,
         t_oldnew: &mut HashMap<usize, usize>,
         t_v_oldnew: &mut HashMap<usize, usize>,
-        offset: usize,
     ) {
-        match &mut self.texture {
-            Some(x) => {
-                for (_key, tex) in &mut *x {
-                    match self.thetype {
-                        GeometryType::MultiSurface | GeometryType::CompositeSurface => {
-                            let a: Vec<Vec<Vec<Option<usize>>>> =
-                                serde_json::from_value(tex.values.take().into()).unwrap();
-                            let mut a2 = a.clone();
-                            for (i, x) in a.iter().enumerate() {
-                                for (j, y) in x.iter().enumerate() {
-                                    for (k, z) in y.iter().enumerate() {
-                                        if z.is_some() {
-                                            let thevalue: usize = z.unwrap();
-                                            if k == 0 {
+        //-- the 1st index of each ring is the texture, the others the texture vertices
+        if let Some(x) = &mut self.texture {
+            let mut themes: Vec<(&String, &mut Texture)> = x.iter_mut().collect();
+            themes.sort_by(|a, b| a.0.cmp(b.0));
+            for (_, tex) in themes {
+                match self.thetype {
+                    GeometryType::MultiSurface | GeometryType::CompositeSurface => {
+                        let a: Vec<Vec<Vec<Option<usize>>>> =
+                            serde_json::from_value(tex.values.take().into()).unwrap();
+                        let mut a2 = a.clone();
+                        for (i, x) in a.iter().enumerate() {
+                            for (j, y) in x.iter().enumerate() {
+                                for (k, z) in y.iter().enumerate() {
+                                    if z.is_some() {
+                                        let thevalue: usize = z.unwrap();
+                                        if k == 0 {
+                                            let y2 = t_oldnew.get(&thevalue);
+                                            if let Some(y2) = y2 {
+                                                a2[i][j][k] = Some(*y2);
+                                            } else {
+                                                let l = t_oldnew.len();
+                                                t_oldnew.insert(thevalue, l);
+                                                a2[i][j][k] = Some(l);
+                                            }
+                                        } else {
+                                            let y2 = t_v_oldnew.get(&thevalue);
+                                            if let Some(y2) = y2 {
+                                                a2[i][j][k] = Some(*y2);
+                                            } else {
+                                                let l = t_v_oldnew.len();
+                                                t_v_oldnew.insert(thevalue, l);
+                                                a2[i][j][k] = Some(l);
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                        tex.values = Some(serde_json::to_value(&a2).unwrap());
+                    }
+                    GeometryType::Solid => {
+                        let a: Vec<Vec<Vec<Vec<Option<usize>>>>> =
+                            serde_json::from_value(tex.values.take().into()).unwrap();
+                        let mut a2 = a.clone();
+                        for (i, x) in a.iter().enumerate() {
+                            for (j, y) in x.iter().enumerate() {
+                                for (k, z) in y.iter().enumerate() {
+                                    for (l, zz) in z.iter().enumerate() {
+                                        if zz.is_some() {
+                                            let thevalue: usize = zz.unwrap();
+                                            if l == 0 {
                                                 let y2 = t_oldnew.get(&thevalue);
-                                                if y2.is_none() {
-                                                    let l = t_oldnew.len();
-                                                    t_oldnew.insert(thevalue, l);
-                                                    a2[i][j][k] = Some(l);
+                                                if let Some(y2) = y2 {
+                                                    a2[i][j][k][l] = Some(*y2);
                                                 } else {
-                                                    let y2 = y2.unwrap();
-                                                    a2[i][j][k] = Some(*y2);
+                                                    let l2 = t_oldnew.len();
+                                                    t_oldnew.insert(thevalue, l2);
+                                                    a2[i][j][k][l] = Some(l2);
                                                 }
                                             } else {
                                                 let y2 = t_v_oldnew.get(&thevalue);
-                                                if y2.is_none() {
-                                                    let l = t_v_oldnew.len();
-                                                    t_v_oldnew.insert(thevalue, l + offset);
-                                                    a2[i][j][k] = Some(l);
+                                                if let Some(y2) = y2 {
+                                                    a2[i][j][k][l] = Some(*y2);
                                                 } else {
-                                                    let y2 = y2.unwrap();
-                                                    a2[i][j][k] = Some(*y2);
+                                                    let l2 = t_v_oldnew.len();
+                                                    t_v_oldnew.insert(thevalue, l2);
+                                                    a2[i][j][k][l] = Some(l2);
                                                 }
                                             }
                                         }
                                     }
                                 }
                             }
-                            tex.values = Some(serde_json::to_value(&a2).unwrap());
                         }
-                        GeometryType::Solid => {
-                            let a: Vec<Vec<Vec<Vec<Option<usize>>>>> =
-                                serde_json::from_value(tex.values.take().into()).unwrap();
-                            let mut a2 = a.clone();
-                            for (i, x) in a.iter().enumerate() {
+                        tex.values = Some(serde_json::to_value(&a2).unwrap());
+                    }
+                    GeometryType::MultiSolid | GeometryType::CompositeSolid => {
+                        let a: Vec<Vec<Vec<TextureRings>>> =
+                            serde_json::from_value(tex.values.take().into()).unwrap();
+                        let mut a2 = a.clone();
+                        for (h, w) in a.iter().enumerate() {
+                            for (i, x) in w.iter().enumerate() {
                                 for (j, y) in x.iter().enumerate() {
                                     for (k, z) in y.iter().enumerate() {
                                         for (l, zz) in z.iter().enumerate() {
-                                            if zz.is_some() {
-                                                let thevalue: usize = zz.unwrap();
-                                                if l == 0 {
-                                                    let y2 = t_oldnew.get(&thevalue);
-                                                    if y2.is_none() {
-                                                        let l2 = t_oldnew.len();
-                                                        t_oldnew.insert(thevalue, l2);
-                                                        a2[i][j][k][l] = Some(l2);
-                                                    } else {
-                                                        let y2 = y2.unwrap();
-                                                        a2[i][j][k][l] = Some(*y2);
-                                                    }
+                                            if let Some(thevalue) = *zz {
+                                                let m = if l == 0 {
+                                                    &mut *t_oldnew
                                                 } else {
-                                                    let y2 = t_v_oldnew.get(&thevalue);
-                                                    if y2.is_none() {
-                                                        let l2 = t_v_oldnew.len();
-                                                        t_v_oldnew.insert(thevalue, l2 + offset);
-                                                        a2[i][j][k][l] = Some(l2);
-                                                    } else {
-                                                        let y2 = y2.unwrap();
-                                                        a2[i][j][k][l] = Some(*y2);
-                                                    }
-                                                }
+                                                    &mut *t_v_oldnew
+                                                };
+                                                let l2 = m.len();
+                                                a2[h][i][j][k][l] =
+                                                    Some(*m.entry(thevalue).or_insert(l2));
                                             }
                                         }
                                     }
                                 }
                             }
-                            tex.values = Some(serde_json::to_value(&a2).unwrap());
                         }
-                        _ => todo!(),
+                        tex.values = Some(serde_json::to_value(&a2).unwrap());
                     }
+                    //-- points and lines cannot be textured
+                    _ => (),
                 }
             }
-            None => (),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Vertex {
-    x: i64,
-    y: i64,
-    z: i64,
+//-- the texture values of one surface: for each ring [texture, uv, uv, ...]
+type TextureRings = Vec<Vec<Option<usize>>>;
+
+const IDENTITY: [f64; 16] = [
+    1., 0., 0., 0., 0., 1., 0., 0., 0., 0., 1., 0., 0., 0., 0., 1.,
+];
+
+fn apply_matrix(m: &[f64; 16], p: [f64; 3]) -> [f64; 3] {
+    //-- 4x4 matrix, row-major, in homogeneous coordinates (w is 1 for an affine matrix)
+    let r = |i: usize| m[i] * p[0] + m[i + 1] * p[1] + m[i + 2] * p[2] + m[i + 3];
+    let w = r(12);
+    if w == 0.0 || w == 1.0 {
+        [r(0), r(4), r(8)]
+    } else {
+        [r(0) / w, r(4) / w, r(8) / w]
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum CrsKind {
+    Geographic,
+    Projected,
+    Compound,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReferenceSystem {
+    pub authority: String,
+    pub version: String,
+    pub code: String,
+}
+impl ReferenceSystem {
+    pub fn from_url(url: &str) -> Result<Self, String> {
+        //-- eg "https://www.opengis.net/def/crs/EPSG/0/7415"
+        let re = match url.split_once("/crs/") {
+            Some((_, x)) => x.split('/').collect::<Vec<&str>>(),
+            None => return Err(format!("\"{}\" is not an OGC CRS URL", url)),
+        };
+        if re.len() != 3 || re.iter().any(|x| x.is_empty()) {
+            return Err(format!(
+                "\"{}\" should end with /crs/{{authority}}/{{version}}/{{code}}",
+                url
+            ));
+        }
+        let rs = ReferenceSystem {
+            authority: re[0].to_string(),
+            version: re[1].to_string(),
+            code: re[2].to_string(),
+        };
+        match rs.authority.as_str() {
+            "EPSG" => {
+                if rs.code.parse::<u32>().is_err() {
+                    return Err(format!("EPSG code \"{}\" is not a number", rs.code));
+                }
+            }
+            "OGC" => (),
+            _ => return Err(format!("unknown CRS authority \"{}\"", rs.authority)),
+        }
+        Ok(rs)
+    }
+    pub fn kind(&self) -> Option<CrsKind> {
+        //-- only for a few common CRSs, None if unknown
+        match (self.authority.as_str(), self.code.as_str()) {
+            ("EPSG", "4326") | ("EPSG", "4979") | ("OGC", "CRS84") | ("OGC", "CRS84h") => {
+                Some(CrsKind::Geographic)
+            }
+            ("EPSG", "28992") | ("EPSG", "3857") | ("EPSG", "2056") | ("EPSG", "25832") => {
+                Some(CrsKind::Projected)
+            }
+            ("EPSG", "7415") | ("EPSG", "5555") | ("EPSG", "7423") => Some(CrsKind::Compound),
+            _ => None,
+        }
+    }
+    pub fn is_geographic(&self) -> bool {
+        self.kind() == Some(CrsKind::Geographic)
+    }
+    pub fn is_projected(&self) -> bool {
+        //-- compound CRSs have a projected horizontal CRS (eg 7415 = 28992 + NAP)
+        matches!(
+            self.kind(),
+            Some(CrsKind::Projected) | Some(CrsKind::Compound)
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Address {
+    #[serde(rename = "thoroughfareNumber")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thoroughfare_number: Option<String>,
+    #[serde(rename = "thoroughfareName")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thoroughfare_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locality: Option<String>,
+    #[serde(rename = "postalCode")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub postal_code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PointOfContact {
+    #[serde(rename = "contactName")]
+    pub contact_name: String,
+    #[serde(rename = "emailAddress")]
+    pub email_address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phone: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<Address>,
+    #[serde(rename = "contactType")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contact_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub organization: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub website: Option<String>,
+}
+impl PointOfContact {
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut re: Vec<String> = Vec::new();
+        if self.contact_name.trim().is_empty() {
+            re.push("\"contactName\" is empty".to_string());
+        }
+        //-- not RFC 5322, only something@domain.tld
+        let ok = match self.email_address.split_once('@') {
+            Some((a, b)) => {
+                !a.is_empty()
+                    && !b.contains('@')
+                    && b.split('.').count() > 1
+                    && b.split('.').all(|x| !x.is_empty())
+                    && !self.email_address.contains(char::is_whitespace)
+            }
+            None => false,
+        };
+        if !ok {
+            re.push(format!(
+                "\"emailAddress\" \"{}\" is not an email address",
+                self.email_address
+            ));
+        }
+        if let Some(t) = &self.contact_type {
+            if t != "individual" && t != "organization" {
+                re.push(format!(
+                    "\"contactType\" \"{}\" is not \"individual\" or \"organization\"",
+                    t
+                ));
+            }
+        }
+        if re.is_empty() {
+            Ok(())
+        } else {
+            Err(re)
+        }
+    }
+}
+
+pub struct PointOfContactBuilder {
+    poc: PointOfContact,
+}
+impl PointOfContactBuilder {
+    pub fn new(contact_name: &str, email_address: &str) -> Self {
+        //-- the 2 properties required by the specs
+        PointOfContactBuilder {
+            poc: PointOfContact {
+                contact_name: contact_name.to_string(),
+                email_address: email_address.to_string(),
+                phone: None,
+                address: None,
+                contact_type: None,
+                role: None,
+                organization: None,
+                website: None,
+            },
+        }
+    }
+    pub fn phone(mut self, x: &str) -> Self {
+        self.poc.phone = Some(x.to_string());
+        self
+    }
+    pub fn address(mut self, x: Address) -> Self {
+        self.poc.address = Some(x);
+        self
+    }
+    pub fn contact_type(mut self, x: &str) -> Self {
+        self.poc.contact_type = Some(x.to_string());
+        self
+    }
+    pub fn role(mut self, x: &str) -> Self {
+        self.poc.role = Some(x.to_string());
+        self
+    }
+    pub fn organization(mut self, x: &str) -> Self {
+        self.poc.organization = Some(x.to_string());
+        self
+    }
+    pub fn website(mut self, x: &str) -> Self {
+        self.poc.website = Some(x.to_string());
+        self
+    }
+    pub fn build(self) -> Result<PointOfContact, Vec<String>> {
+        self.poc.validate()?;
+        Ok(self.poc)
+    }
+}
+
+//-- the tolerance to compare 2 transforms
+pub const TRANSFORM_EPSILON: f64 = 1e-9;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Transform {
     pub scale: Vec<f64>,
     pub translate: Vec<f64>,
 }
+impl Default for Transform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 impl Transform {
     pub fn new() -> Self {
         Transform {
@@ -734,6 +3195,36 @@ impl Transform {
             translate: vec![0., 0., 0.],
         }
     }
+    pub fn real_coordinates(&self, v: &[i64]) -> [f64; 3] {
+        [
+            (v[0] as f64 * self.scale[0]) + self.translate[0],
+            (v[1] as f64 * self.scale[1]) + self.translate[1],
+            (v[2] as f64 * self.scale[2]) + self.translate[2],
+        ]
+    }
+    pub fn quantize(&self, p: &[f64]) -> Vec<i64> {
+        vec![
+            ((p[0] - self.translate[0]) / self.scale[0]).round() as i64,
+            ((p[1] - self.translate[1]) / self.scale[1]).round() as i64,
+            ((p[2] - self.translate[2]) / self.scale[2]).round() as i64,
+        ]
+    }
+    pub fn approx_eq(&self, other: &Transform, eps: f64) -> bool {
+        //-- the scale and translate differ by at most eps (eg floats printed differently)
+        let close = |a: &[f64], b: &[f64]| {
+            a.len() == b.len() && a.iter().zip(b).all(|(x, y)| (x - y).abs() <= eps)
+        };
+        close(&self.scale, &other.scale) && close(&self.translate, &other.translate)
+    }
+    pub fn decimals(&self) -> usize {
+        //-- number of decimals needed to print the coordinates without loss
+        let s = self.scale.iter().cloned().fold(f64::MAX, f64::min);
+        if s >= 1.0 {
+            0
+        } else {
+            (-s.log10()).ceil() as usize
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -742,8 +3233,85 @@ pub struct GeometryTemplates {
     #[serde(rename = "vertices-templates")]
     pub vertices_templates: Value,
 }
+impl GeometryTemplates {
+    pub fn new() -> Self {
+        GeometryTemplates {
+            templates: Vec::new(),
+            vertices_templates: json!([]),
+        }
+    }
+    pub fn vertices(&self) -> Vec<Vec<f64>> {
+        serde_json::from_value(self.vertices_templates.clone()).unwrap_or_default()
+    }
+    pub fn add_template(&mut self, mut g: Geometry, vts: Vec<Vec<f64>>) -> usize {
+        //-- g uses the vertices vts (its indices start at 0); returns the index of the
+        //-- template, an identical one (same geometry and coordinates) is reused
+        let all = self.vertices();
+        let key = |g: &Geometry, vs: &[Vec<f64>]| {
+            let mut j = serde_json::to_value(g).unwrap();
+            j["boundaries"] = with_coordinates(&g.boundaries, vs);
+            j
+        };
+        let k = key(&g, &vts);
+        if let Some(i) = self.templates.iter().position(|t| key(t, &all) == k) {
+            return i;
+        }
+        g.offset_geometry_boundaries(all.len());
+        if let Value::Array(a) = &mut self.vertices_templates {
+            a.extend(vts.into_iter().map(|v| json!(v)));
+        }
+        self.templates.push(g);
+        self.templates.len() - 1
+    }
+}
+impl Default for GeometryTemplates {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+fn boundaries_depth(v: &Value) -> Result<Option<usize>, String> {
+    //-- the uniform depth of the arrays (None: only empty arrays), an error if it differs
+    //-- between the branches or if a leaf is not a vertex index
+    match v {
+        Value::Number(n) if n.is_u64() => Ok(Some(0)),
+        Value::Array(a) => {
+            let mut re: Option<usize> = None;
+            for x in a {
+                if let Some(d) = boundaries_depth(x)? {
+                    if re.is_some_and(|r| r != d) {
+                        return Err("the depth of the boundaries is not uniform".to_string());
+                    }
+                    re = Some(d);
+                }
+            }
+            Ok(re.map(|d| d + 1))
+        }
+        x => Err(format!("{} is not a vertex index in the boundaries", x)),
+    }
+}
+
+fn with_coordinates(boundaries: &Value, vs: &[Vec<f64>]) -> Value {
+    //-- the indices of the boundaries replaced by the coordinates of the vertices
+    match boundaries {
+        Value::Array(a) => Value::Array(a.iter().map(|x| with_coordinates(x, vs)).collect()),
+        Value::Number(n) => match n.as_u64().and_then(|i| vs.get(i as usize)) {
+            Some(v) => json!(v),
+            None => Value::Null,
+        },
+        x => x.clone(),
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Material {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub values: Option<Value>,
@@ -751,7 +3319,7 @@ pub struct Material {
     pub value: Option<usize>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Texture {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub values: Option<Value>,
@@ -773,7 +3341,57 @@ pub struct Appearance {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub default_theme_material: Option<String>,
 }
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct AppearanceSummary {
+    pub materials: BTreeSet<String>,
+    pub textures: BTreeSet<String>,
+    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
+    pub missing_materials: BTreeSet<usize>,
+    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
+    pub missing_textures: BTreeSet<usize>,
+}
+impl AppearanceSummary {
+    pub fn extend(&mut self, other: AppearanceSummary) {
+        self.materials.extend(other.materials);
+        self.textures.extend(other.textures);
+        self.missing_materials.extend(other.missing_materials);
+        self.missing_textures.extend(other.missing_textures);
+    }
+}
+
+fn appearance_names(l: Option<&Vec<Value>>, key: &str) -> Vec<String> {
+    l.iter()
+        .flat_map(|x| x.iter())
+        .enumerate()
+        .map(|(i, j)| match j.get(key).and_then(|x| x.as_str()) {
+            Some(x) => x.to_string(),
+            None => format!("#{}", i),
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AppearanceRemap {
+    pub materials: HashMap<usize, usize>,
+    pub textures: HashMap<usize, usize>,
+    pub vertices_texture: HashMap<usize, usize>,
+    pub conflicts: Vec<String>,
+}
+
+impl Default for Appearance {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 impl Appearance {
+    pub fn material_names(&self) -> Vec<String> {
+        //-- the "name" of each material, "#i" if it has none
+        appearance_names(self.materials.as_ref(), "name")
+    }
+    pub fn texture_names(&self) -> Vec<String> {
+        //-- the "image" of each texture, "#i" if it has none
+        appearance_names(self.textures.as_ref(), "image")
+    }
     pub fn new() -> Self {
         Appearance {
             materials: None,
@@ -783,6 +3401,35 @@ impl Appearance {
             default_theme_material: None,
         }
     }
+    pub fn is_empty(&self) -> bool {
+        self.materials.is_none()
+            && self.textures.is_none()
+            && self.vertices_texture.is_none()
+            && self.default_theme_texture.is_none()
+            && self.default_theme_material.is_none()
+    }
+    pub fn merge(&mut self, other: &Appearance) -> AppearanceRemap {
+        //-- the materials/textures already present are reused, the texture vertices are
+        //-- appended; returns the old->new indices to update the geometries of other
+        let mut re = AppearanceRemap {
+            conflicts: self.add_default_themes(other),
+            ..Default::default()
+        };
+        for (i, m) in other.materials.iter().flatten().enumerate() {
+            re.materials.insert(i, self.add_material(m.clone()));
+        }
+        for (i, t) in other.textures.iter().flatten().enumerate() {
+            re.textures.insert(i, self.add_texture(t.clone()));
+        }
+        if let Some(vs) = &other.vertices_texture {
+            let t_offset = self.vertices_texture.as_ref().map_or(0, |x| x.len());
+            for i in 0..vs.len() {
+                re.vertices_texture.insert(i, i + t_offset);
+            }
+            self.add_vertices_texture(vs.clone());
+        }
+        re
+    }
     pub fn add_material(&mut self, jm: Value) -> usize {
         let re = match &mut self.materials {
             Some(x) => match x.iter().position(|e| *e == jm) {
@@ -793,9 +3440,7 @@ impl Appearance {
                 }
             },
             None => {
-                let mut ls: Vec<Value> = Vec::new();
-                ls.push(jm);
-                self.materials = Some(ls);
+                self.materials = Some(vec![jm]);
                 0
             }
         };
@@ -811,14 +3456,37 @@ impl Appearance {
                 }
             },
             None => {
-                let mut ls: Vec<Value> = Vec::new();
-                ls.push(jm);
-                self.textures = Some(ls);
+                self.textures = Some(vec![jm]);
                 0
             }
         };
         re
     }
+    pub fn add_default_themes(&mut self, other: &Appearance) -> Vec<String> {
+        //-- the first default theme found is kept, the conflicting ones are returned
+        let mut conflicts: Vec<String> = Vec::new();
+        if let Some(t) = &other.default_theme_material {
+            match &self.default_theme_material {
+                Some(x) if x != t => conflicts.push(format!(
+                    "conflicting default-theme-material (\"{}\" and \"{}\"), \"{}\" is kept",
+                    x, t, x
+                )),
+                Some(_) => (),
+                None => self.default_theme_material = Some(t.clone()),
+            }
+        }
+        if let Some(t) = &other.default_theme_texture {
+            match &self.default_theme_texture {
+                Some(x) if x != t => conflicts.push(format!(
+                    "conflicting default-theme-texture (\"{}\" and \"{}\"), \"{}\" is kept",
+                    x, t, x
+                )),
+                Some(_) => (),
+                None => self.default_theme_texture = Some(t.clone()),
+            }
+        }
+        conflicts
+    }
     pub fn add_vertices_texture(&mut self, mut vs: Vec<Vec<f64>>) {
         match &mut self.vertices_texture {
             Some(x) => {
@@ -0,0 +1,218 @@
+use crate::cityjson::{CityJSON, CityObject, Geometry, GeometryType, Transform};
+use crate::conv::Options;
+use serde_json::{json, Map, Value};
+
+pub fn to_geojson(cj: &CityJSON, flat: bool, opts: &Options) -> Value {
+    //-- a FeatureCollection with one Feature per CityObject having surfaces, with its highest
+    //-- LoD (or opts.lod) as a (Multi)Polygon; the coordinates are those of the CRS of the file
+    //-- (not necessarily WGS84), and the properties are the type and the attributes (an
+    //-- attribute "type" is renamed "attributes.type")
+    let f = 10_f64.powi(opts.decimals(cj) as i32);
+    let coords = |vi: usize| -> Value {
+        let p = cj.transform.real_coordinates(&cj.vertices[vi]);
+        let p: Vec<f64> = p.iter().map(|x| (x * f).round() / f).collect();
+        match flat {
+            true => json!([p[0], p[1]]),
+            false => json!(p),
+        }
+    };
+    let mut ids: Vec<&String> = cj.city_objects.keys().collect();
+    ids.sort();
+    let mut features: Vec<Value> = Vec::new();
+    for id in ids {
+        let co = &cj.city_objects[id];
+        let mut polygons: Vec<Value> = Vec::new();
+        for g in opts.geometries(co) {
+            for surface in opts.surfaces(cj, g) {
+                //-- rings are closed in GeoJSON
+                let rings: Vec<Value> = surface
+                    .iter()
+                    .map(|r| r.iter().chain(r.first()).map(|vi| coords(*vi)).collect())
+                    .collect();
+                polygons.push(Value::Array(rings));
+            }
+        }
+        let geometry = match polygons.len() {
+            0 => continue,
+            1 => json!({"type": "Polygon", "coordinates": polygons.pop().unwrap()}),
+            _ => json!({"type": "MultiPolygon", "coordinates": polygons}),
+        };
+        let mut properties = Map::new();
+        properties.insert("type".to_string(), json!(co.thetype));
+        if let Some(Value::Object(m)) = &co.attributes {
+            for (k, v) in m {
+                match k.as_str() {
+                    "type" => properties.insert("attributes.type".to_string(), v.clone()),
+                    _ => properties.insert(k.clone(), v.clone()),
+                };
+            }
+        }
+        features.push(json!({
+            "type": "Feature",
+            "id": id,
+            "geometry": geometry,
+            "properties": properties
+        }));
+    }
+    json!({"type": "FeatureCollection", "features": features})
+}
+
+pub fn from_geojson(fc: &Value, height_attr: &str) -> Result<CityJSON, String> {
+    //-- each Polygon/MultiPolygon Feature of a FeatureCollection becomes a Building with a
+    //-- LoD1 Solid (a MultiSolid for a MultiPolygon): the footprint (at its lowest z, 0 if 2D)
+    //-- is extruded by the property height_attr; the features without a (positive) height or
+    //-- with another geometry type are skipped. all the properties become the attributes
+    let features = match fc.get("features").and_then(|x| x.as_array()) {
+        Some(x) if fc.get("type") == Some(&json!("FeatureCollection")) => x,
+        _ => return Err("the input is not a GeoJSON FeatureCollection".to_string()),
+    };
+    let mut cj = CityJSON::new();
+    let mut points: Vec<[f64; 3]> = Vec::new();
+    for (i, f) in features.iter().enumerate() {
+        let h = match f.get("properties").and_then(|p| p.get(height_attr)) {
+            Some(x) if x.as_f64().is_some_and(|h| h > 0.0) => x.as_f64().unwrap(),
+            _ => continue,
+        };
+        let g = f.get("geometry").unwrap_or(&Value::Null);
+        let polygons: Vec<Vec<Vec<Vec<f64>>>> = match g.get("type").and_then(|x| x.as_str()) {
+            Some("Polygon") => vec![parse_coordinates(g, i)?],
+            Some("MultiPolygon") => parse_coordinates(g, i)?,
+            _ => continue,
+        };
+        let mut solids: Vec<Vec<Vec<Vec<Vec<usize>>>>> = Vec::new();
+        let mut values: Vec<Vec<usize>> = Vec::new();
+        for polygon in polygons {
+            let (shell, v) = extrude(&polygon, h, &mut points);
+            if !shell.is_empty() {
+                solids.push(vec![shell]);
+                values.push(v);
+            }
+        }
+        if solids.is_empty() {
+            continue;
+        }
+        let surfaces =
+            json!([{"type": "GroundSurface"}, {"type": "RoofSurface"}, {"type": "WallSurface"}]);
+        let geometry = match solids.len() {
+            1 => Geometry {
+                thetype: GeometryType::Solid,
+                lod: Some("1".to_string()),
+                boundaries: json!(solids[0]),
+                semantics: Some(json!({"surfaces": surfaces, "values": [values[0]]})),
+                material: None,
+                texture: None,
+                template: None,
+                transformation_matrix: None,
+            },
+            _ => Geometry {
+                thetype: GeometryType::MultiSolid,
+                lod: Some("1".to_string()),
+                boundaries: json!(solids),
+                semantics: Some(json!({
+                    "surfaces": surfaces,
+                    "values": values.iter().map(|v| vec![v]).collect::<Vec<_>>()
+                })),
+                material: None,
+                texture: None,
+                template: None,
+                transformation_matrix: None,
+            },
+        };
+        let mut co = CityObject::new("Building");
+        co.add_geometry(geometry);
+        if let Some(Value::Object(m)) = f.get("properties") {
+            co.attributes = Some(Value::Object(m.clone()));
+        }
+        let id = match f.get("id") {
+            Some(Value::String(x)) => x.clone(),
+            Some(Value::Number(x)) => x.to_string(),
+            _ => format!("feature-{}", i),
+        };
+        if cj.city_objects.contains_key(&id) {
+            return Err(format!("the id \"{}\" is used by 2 features", id));
+        }
+        cj.add_co(id, co);
+    }
+    //-- millimetres, with the translate at the minimum
+    let mut t = Transform::new();
+    t.scale = vec![0.001, 0.001, 0.001];
+    t.translate = (0..3)
+        .map(|k| points.iter().map(|p| p[k]).fold(f64::MAX, f64::min))
+        .map(|x| if x == f64::MAX { 0.0 } else { x })
+        .collect();
+    cj.vertices = points.iter().map(|p| t.quantize(p)).collect();
+    cj.transform = t;
+    cj.remove_duplicate_vertices();
+    Ok(cj)
+}
+
+fn parse_coordinates<T: serde::de::DeserializeOwned>(g: &Value, i: usize) -> Result<T, String> {
+    serde_json::from_value(g.get("coordinates").cloned().unwrap_or(Value::Null))
+        .map_err(|_| format!("the coordinates of the feature #{} are invalid", i))
+}
+
+fn extrude(
+    polygon: &[Vec<Vec<f64>>],
+    h: f64,
+    points: &mut Vec<[f64; 3]>,
+) -> (Vec<Vec<Vec<usize>>>, Vec<usize>) {
+    //-- the shell (ground, roof, walls, with the normals pointing outwards) and its semantic
+    //-- values; the exterior ring is made counter-clockwise and the holes clockwise
+    let mut rings: Vec<Vec<[f64; 2]>> = Vec::new();
+    for (k, r) in polygon.iter().enumerate() {
+        let mut ring: Vec<[f64; 2]> = r
+            .iter()
+            .filter(|p| p.len() >= 2)
+            .map(|p| [p[0], p[1]])
+            .collect();
+        //-- closed in GeoJSON, not in CityJSON
+        if ring.len() > 1 && ring.first() == ring.last() {
+            ring.pop();
+        }
+        if ring.len() < 3 {
+            if k == 0 {
+                return (vec![], vec![]);
+            }
+            continue;
+        }
+        let area: f64 = (0..ring.len())
+            .map(|j| {
+                let (a, b) = (ring[j], ring[(j + 1) % ring.len()]);
+                a[0] * b[1] - b[0] * a[1]
+            })
+            .sum();
+        if (k == 0) != (area > 0.0) {
+            ring.reverse();
+        }
+        rings.push(ring);
+    }
+    let z = polygon
+        .iter()
+        .flatten()
+        .filter_map(|p| p.get(2))
+        .cloned()
+        .fold(f64::MAX, f64::min);
+    let z = if z == f64::MAX { 0.0 } else { z };
+    let mut bottom: Vec<Vec<usize>> = Vec::new();
+    let mut top: Vec<Vec<usize>> = Vec::new();
+    for r in &rings {
+        let start = points.len();
+        points.extend(r.iter().map(|p| [p[0], p[1], z]));
+        points.extend(r.iter().map(|p| [p[0], p[1], z + h]));
+        let n = r.len();
+        bottom.push((start..start + n).rev().collect());
+        top.push((start + n..start + 2 * n).collect());
+    }
+    let mut shell: Vec<Vec<Vec<usize>>> = vec![bottom.clone(), top.clone()];
+    let mut values: Vec<usize> = vec![0, 1];
+    for (b, t) in bottom.iter().zip(&top) {
+        //-- bottom is reversed: b[n-1-j] is below t[j]
+        let n = t.len();
+        for j in 0..n {
+            let (j2, nb) = ((j + 1) % n, n - 1);
+            shell.push(vec![vec![b[nb - j], b[nb - j2], t[j2], t[j]]]);
+            values.push(2);
+        }
+    }
+    (shell, values)
+}
@@ -0,0 +1,111 @@
+use crate::cityjson::{triangulate_ring, CityJSON, CityObject, Geometry};
+
+pub mod geojson;
+pub mod obj;
+#[cfg(feature = "arrow")]
+pub mod parquet;
+pub mod wkt;
+
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    //-- the geometries of this LoD (eg "2.2"), None for the highest LoD of each CityObject
+    pub lod: Option<String>,
+    //-- each surface becomes triangles (the holes are ignored)
+    pub triangulate: bool,
+    //-- number of decimals of the coordinates, None for those of the transform
+    pub precision: Option<usize>,
+    //-- the diffuseColor of the material of each surface is given to its vertices (obj only)
+    pub vertex_colors: bool,
+}
+
+impl Options {
+    pub fn decimals(&self, cj: &CityJSON) -> usize {
+        self.precision.unwrap_or_else(|| cj.transform.decimals())
+    }
+    pub fn warnings(&self, cj: &CityJSON) -> Vec<String> {
+        //-- what is lost when converting cj: the CityObjects where find_highest_lod_geometry()
+        //-- cannot choose between several geometries
+        if self.lod.is_some() {
+            return vec![];
+        }
+        let mut re: Vec<String> = Vec::new();
+        for (id, co) in cj.iter_city_objects() {
+            let n = co
+                .geometry
+                .iter()
+                .flatten()
+                .filter(|g| !g.is_empty())
+                .count();
+            if n > 1 && find_highest_lod_geometry(co)[0].lod_f64().is_none() {
+                re.push(format!(
+                    "{} #{} has {} geometries without a valid LoD, only the 1st one is used",
+                    co.thetype, id, n
+                ));
+            }
+        }
+        re
+    }
+    pub fn geometries<'a>(&self, co: &'a CityObject) -> Vec<&'a Geometry> {
+        match &self.lod {
+            Some(l) => find_lod_geometry(co, l),
+            None => find_highest_lod_geometry(co),
+        }
+    }
+    pub fn surfaces(&self, cj: &CityJSON, g: &Geometry) -> Vec<Vec<Vec<usize>>> {
+        //-- the surfaces as lists of rings (the 1st is the exterior), the empty ones are skipped
+        self.indexed_surfaces(cj, g)
+            .into_iter()
+            .map(|(_, s)| s)
+            .collect()
+    }
+    pub fn indexed_surfaces(&self, cj: &CityJSON, g: &Geometry) -> Vec<(usize, Vec<Vec<usize>>)> {
+        //-- as surfaces(), with the index of each in Geometry::get_surfaces() (a triangle has
+        //-- the index of its surface)
+        let surfaces = g
+            .get_surfaces()
+            .into_iter()
+            .enumerate()
+            .filter(|(_, s)| s.first().is_some_and(|r| !r.is_empty()));
+        if !self.triangulate {
+            return surfaces.collect();
+        }
+        let vs: Vec<[f64; 3]> = cj
+            .vertices
+            .iter()
+            .map(|v| cj.transform.real_coordinates(v))
+            .collect();
+        surfaces
+            .flat_map(|(i, s)| {
+                triangulate_ring(&s[0], &vs)
+                    .into_iter()
+                    .map(move |t| (i, vec![t.to_vec()]))
+            })
+            .collect()
+    }
+}
+
+pub fn find_highest_lod_geometry(co: &CityObject) -> Vec<&Geometry> {
+    //-- all the geometries with the highest LoD (those without a valid LoD are ignored); if
+    //-- no LoD can be parsed then only the 1st geometry is returned, since they are probably
+    //-- the same object at different LoDs (the empty geometries are ignored)
+    let gs: Vec<&Geometry> = match &co.geometry {
+        Some(x) => x.iter().filter(|g| !g.is_empty()).collect(),
+        None => return vec![],
+    };
+    let highest = gs.iter().filter_map(|g| g.lod_f64()).reduce(f64::max);
+    match highest {
+        Some(h) => gs.into_iter().filter(|g| g.lod_f64() == Some(h)).collect(),
+        None => gs.into_iter().take(1).collect(),
+    }
+}
+
+pub fn find_lod_geometry<'a>(co: &'a CityObject, lod: &str) -> Vec<&'a Geometry> {
+    //-- the (non-empty) geometries of this LoD, "2" and "2.0" are the same
+    let l = lod.parse::<f64>().ok();
+    co.geometry
+        .iter()
+        .flatten()
+        .filter(|g| !g.is_empty())
+        .filter(|g| g.lod.as_deref() == Some(lod) || (l.is_some() && g.lod_f64() == l))
+        .collect()
+}
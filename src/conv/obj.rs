@@ -0,0 +1,111 @@
+use crate::cityjson::{CityJSON, Geometry};
+use crate::conv::Options;
+use std::collections::HashMap;
+
+//-- the colour of the surfaces without a material (or without a diffuseColor)
+const DEFAULT_COLOR: [f64; 3] = [0.8, 0.8, 0.8];
+
+pub fn to_obj(cj: &CityJSON, origin: Option<[f64; 3]>, opts: &Options) -> String {
+    //-- one object ("o") per CityObject having surfaces, with its highest LoD (or opts.lod)
+    //-- OBJ has no holes: only the exterior ring of each surface is kept.
+    //-- with opts.vertex_colors, the vertices are "v x y z r g b" and a vertex is output once
+    //-- per colour of the surfaces using it (so that the colours don't bleed)
+    let d = opts.decimals(cj);
+    let mut re = String::new();
+    //-- (translate - origin) is small, so no precision is lost with large coordinates
+    let o = origin.unwrap_or([0.0, 0.0, 0.0]);
+    if let Some(o) = origin {
+        //-- not rounded since it has to be added back exactly
+        re.push_str(&format!("# origin: {} {} {}\n", o[0], o[1], o[2]));
+    }
+    let colors: Option<Vec<[f64; 3]>> = opts.vertex_colors.then(|| material_colors(cj));
+    //-- the vertices output, with their colour
+    let mut vertices: Vec<(usize, Option<[f64; 3]>)> = Vec::new();
+    let mut index: HashMap<(usize, [u64; 3]), usize> = HashMap::new();
+    let mut objects = String::new();
+    let mut ids: Vec<&String> = cj.city_objects.keys().collect();
+    ids.sort();
+    for id in ids {
+        let co = &cj.city_objects[id];
+        let mut faces: Vec<String> = Vec::new();
+        for g in opts.geometries(co) {
+            let ms = match &colors {
+                Some(_) => theme(cj, g).map(|t| g.surface_materials(t)),
+                None => None,
+            };
+            for (si, surface) in opts.indexed_surfaces(cj, g) {
+                let f: Vec<usize> = match &colors {
+                    None => surface[0].clone(),
+                    Some(cs) => {
+                        let c = ms
+                            .as_ref()
+                            .and_then(|ms| ms[si])
+                            .and_then(|m| cs.get(m))
+                            .cloned()
+                            .unwrap_or(DEFAULT_COLOR);
+                        surface[0]
+                            .iter()
+                            .map(|vi| {
+                                *index.entry((*vi, c.map(f64::to_bits))).or_insert_with(|| {
+                                    vertices.push((*vi, Some(c)));
+                                    vertices.len() - 1
+                                })
+                            })
+                            .collect()
+                    }
+                };
+                let f: Vec<String> = f.iter().map(|vi| (vi + 1).to_string()).collect();
+                faces.push(format!("f {}\n", f.join(" ")));
+            }
+        }
+        if faces.is_empty() {
+            continue;
+        }
+        objects.push_str(&format!("o {}\n", id));
+        objects.push_str(&faces.concat());
+    }
+    if colors.is_none() {
+        vertices = (0..cj.vertices.len()).map(|i| (i, None)).collect();
+    }
+    let t = &cj.transform;
+    for (vi, c) in vertices {
+        let v = &cj.vertices[vi];
+        let mut p = [0.0; 3];
+        for i in 0..3 {
+            p[i] = (v[i] as f64 * t.scale[i]) + (t.translate[i] - o[i]);
+        }
+        re.push_str(&format!("v {:.*} {:.*} {:.*}", d, p[0], d, p[1], d, p[2]));
+        if let Some(c) = c {
+            re.push_str(&format!(" {} {} {}", c[0], c[1], c[2]));
+        }
+        re.push('\n');
+    }
+    re.push_str(&objects);
+    re
+}
+
+fn material_colors(cj: &CityJSON) -> Vec<[f64; 3]> {
+    //-- the diffuseColor of each material of the appearance
+    let ms = cj.appearance.as_ref().and_then(|a| a.materials.as_ref());
+    ms.into_iter()
+        .flatten()
+        .map(|m| {
+            m.get("diffuseColor")
+                .and_then(|c| serde_json::from_value::<[f64; 3]>(c.clone()).ok())
+                .unwrap_or(DEFAULT_COLOR)
+        })
+        .collect()
+}
+
+fn theme<'a>(cj: &'a CityJSON, g: &'a Geometry) -> Option<&'a str> {
+    //-- the default material theme if the geometry has it, otherwise its 1st one
+    let ms = g.material.as_ref()?;
+    let default = cj
+        .appearance
+        .as_ref()
+        .and_then(|a| a.default_theme_material.as_deref());
+    match default {
+        Some(t) if ms.contains_key(t) => Some(t),
+        _ => ms.keys().min().map(|x| x.as_str()),
+    }
+}
@@ -0,0 +1,131 @@
+//-- the attributes of the features of a CityJSONSeq as a Parquet table: one row per feature
+//-- (its main CityObject) with the columns id, type, centroid_x/y/z, and one column per
+//-- attribute (the union of the keys of all features, null if missing).
+//-- the input is read once and only the attributes are kept in memory; the type of a column
+//-- is inferred from its values: Boolean, Int64, Float64, otherwise Utf8 (arrays/objects and
+//-- columns with mixed types are stored as JSON strings)
+
+use crate::cityjson::{CityJSON, CityJSONFeature, CjseqError};
+use arrow_array::{ArrayRef, BooleanArray, Float64Array, Int64Array, RecordBatch, StringArray};
+use arrow_schema::{DataType, Field, Schema};
+use parquet::arrow::ArrowWriter;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+use std::str::FromStr;
+use std::sync::Arc;
+
+const RESERVED: [&str; 5] = ["id", "type", "centroid_x", "centroid_y", "centroid_z"];
+
+struct Row {
+    id: String,
+    thetype: Option<String>,
+    centroid: [f64; 3],
+    attributes: serde_json::Map<String, Value>,
+}
+
+pub fn to_parquet<R: BufRead, W: Write + Send>(r: R, w: W) -> Result<usize, CjseqError> {
+    //-- returns the number of rows written
+    let mut lines = r.lines();
+    let header = match lines.next() {
+        Some(l) => CityJSON::from_str(&l.map_err(|e| e.to_string())?).map_err(|e| e.to_string())?,
+        None => return Err(CjseqError::EmptyInput),
+    };
+    let mut rows: Vec<Row> = Vec::new();
+    for l in lines {
+        let l = l.map_err(|e| e.to_string())?;
+        if l.trim().is_empty() {
+            continue;
+        }
+        let cjf = CityJSONFeature::from_str(&l).map_err(|e| e.to_string())?;
+        let co = cjf.city_objects.get(&cjf.id);
+        rows.push(Row {
+            id: cjf.id.clone(),
+            thetype: co.map(|co| co.thetype.clone()),
+            centroid: cjf.main_centroid(&header.transform),
+            attributes: match co.and_then(|co| co.attributes.as_ref()) {
+                Some(Value::Object(m)) => m.clone(),
+                _ => serde_json::Map::new(),
+            },
+        });
+    }
+    //-- the union of the attributes, with their types
+    let mut keys: BTreeMap<String, DataType> = BTreeMap::new();
+    for row in &rows {
+        for (k, v) in &row.attributes {
+            let t = match v {
+                Value::Null => continue,
+                Value::Bool(_) => DataType::Boolean,
+                Value::Number(n) if n.is_i64() => DataType::Int64,
+                Value::Number(_) => DataType::Float64,
+                _ => DataType::Utf8,
+            };
+            let e = keys.entry(k.clone()).or_insert(t.clone());
+            *e = match (&*e, &t) {
+                (a, b) if a == b => t,
+                (DataType::Int64, DataType::Float64) | (DataType::Float64, DataType::Int64) => {
+                    DataType::Float64
+                }
+                _ => DataType::Utf8,
+            };
+        }
+    }
+    let mut fields: Vec<Field> = vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new("type", DataType::Utf8, true),
+        Field::new("centroid_x", DataType::Float64, false),
+        Field::new("centroid_y", DataType::Float64, false),
+        Field::new("centroid_z", DataType::Float64, false),
+    ];
+    let mut columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(rows.iter().map(|r| &r.id))),
+        Arc::new(StringArray::from_iter(
+            rows.iter().map(|r| r.thetype.as_ref()),
+        )),
+        Arc::new(Float64Array::from_iter_values(
+            rows.iter().map(|r| r.centroid[0]),
+        )),
+        Arc::new(Float64Array::from_iter_values(
+            rows.iter().map(|r| r.centroid[1]),
+        )),
+        Arc::new(Float64Array::from_iter_values(
+            rows.iter().map(|r| r.centroid[2]),
+        )),
+    ];
+    for (k, t) in &keys {
+        //-- an attribute with the name of one of the columns above is prefixed
+        let name = if RESERVED.contains(&k.as_str()) {
+            format!("attributes.{}", k)
+        } else {
+            k.clone()
+        };
+        let vs = rows
+            .iter()
+            .map(|r| r.attributes.get(k).filter(|v| !v.is_null()));
+        let a: ArrayRef = match t {
+            DataType::Boolean => Arc::new(BooleanArray::from_iter(
+                vs.map(|v| v.and_then(|x| x.as_bool())),
+            )),
+            DataType::Int64 => Arc::new(Int64Array::from_iter(
+                vs.map(|v| v.and_then(|x| x.as_i64())),
+            )),
+            DataType::Float64 => Arc::new(Float64Array::from_iter(
+                vs.map(|v| v.and_then(|x| x.as_f64())),
+            )),
+            _ => Arc::new(StringArray::from_iter(vs.map(|v| {
+                v.map(|x| match x {
+                    Value::String(s) => s.clone(),
+                    _ => x.to_string(),
+                })
+            }))),
+        };
+        fields.push(Field::new(name, t.clone(), true));
+        columns.push(a);
+    }
+    let batch =
+        RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).map_err(|e| e.to_string())?;
+    let mut writer = ArrowWriter::try_new(w, batch.schema(), None).map_err(|e| e.to_string())?;
+    writer.write(&batch).map_err(|e| e.to_string())?;
+    writer.close().map_err(|e| e.to_string())?;
+    Ok(rows.len())
+}
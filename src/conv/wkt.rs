@@ -0,0 +1,51 @@
+use crate::cityjson::CityJSON;
+use crate::conv::Options;
+
+pub fn to_wkt_rows(cj: &CityJSON, flat: bool, opts: &Options) -> Vec<(String, String)> {
+    //-- one (id, WKT) per CityObject having surfaces, with its highest LoD (or opts.lod)
+    let mut ids: Vec<&String> = cj.city_objects.keys().collect();
+    ids.sort();
+    let mut re: Vec<(String, String)> = Vec::new();
+    for id in ids {
+        let co = &cj.city_objects[id];
+        let mut polygons: Vec<String> = Vec::new();
+        for g in opts.geometries(co) {
+            for surface in opts.surfaces(cj, g) {
+                polygons.push(polygon_wkt(cj, &surface, flat, opts.decimals(cj)));
+            }
+        }
+        let wkt = match polygons.len() {
+            0 => continue,
+            1 => format!("POLYGON{} {}", dim(flat), polygons[0]),
+            _ => format!("MULTIPOLYGON{} ({})", dim(flat), polygons.join(", ")),
+        };
+        re.push((id.to_string(), wkt));
+    }
+    re
+}
+
+fn dim(flat: bool) -> &'static str {
+    if flat {
+        ""
+    } else {
+        " Z"
+    }
+}
+
+fn polygon_wkt(cj: &CityJSON, surface: &[Vec<usize>], flat: bool, d: usize) -> String {
+    //-- first ring is the exterior, the others are the holes; rings are closed in WKT
+    let mut rings: Vec<String> = Vec::new();
+    for ring in surface {
+        let mut pts: Vec<String> = Vec::new();
+        for vi in ring.iter().chain(ring.first()) {
+            let p = cj.transform.real_coordinates(&cj.vertices[*vi]);
+            if flat {
+                pts.push(format!("{:.*} {:.*}", d, p[0], d, p[1]));
+            } else {
+                pts.push(format!("{:.*} {:.*} {:.*}", d, p[0], d, p[1], d, p[2]));
+            }
+        }
+        rings.push(format!("({})", pts.join(", ")));
+    }
+    format!("({})", rings.join(", "))
+}
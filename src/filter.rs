@@ -0,0 +1,291 @@
+//-- a small expression language to select the features of a CityJSONSeq, eg
+//--   type == "Building" && (attr.height > 10 || attr.roofType == "flat")
+//-- variables: id, type (of the main CityObject), attr.xxx (its attributes, attr.a.b for
+//-- nested ones), centroid.x|y|z, bbox.minx|miny|minz|maxx|maxy|maxz
+//-- operators: == != < <= > >= && || ! and parentheses
+
+use crate::cityjson::{CityJSONFeature, Transform};
+use serde_json::Value;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Num(f64),
+    Str(String),
+    Op(String),
+    LParen,
+    RParen,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Val {
+    Num(f64),
+    Str(String),
+    Bool(bool),
+    Null,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Or(Box<Node>, Box<Node>),
+    And(Box<Node>, Box<Node>),
+    Not(Box<Node>),
+    Cmp(String, Box<Node>, Box<Node>),
+    Lit(Val),
+    Var(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Expr {
+    root: Node,
+}
+
+impl Expr {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let tokens = tokenize(s)?;
+        let mut p = Parser { tokens, pos: 0 };
+        let root = p.or()?;
+        if let Some(t) = p.tokens.get(p.pos) {
+            return Err(format!("unexpected {:?} in the expression", t));
+        }
+        Ok(Expr { root })
+    }
+    pub fn eval(&self, cjf: &CityJSONFeature, transform: &Transform) -> bool {
+        let ctx = Context {
+            cjf,
+            transform,
+            centroid: std::cell::OnceCell::new(),
+            bbox: std::cell::OnceCell::new(),
+        };
+        truthy(&ctx.eval(&self.root))
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let cs: Vec<char> = s.chars().collect();
+    let mut re: Vec<Token> = Vec::new();
+    let mut i = 0;
+    while i < cs.len() {
+        let c = cs[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '(' {
+            re.push(Token::LParen);
+            i += 1;
+        } else if c == ')' {
+            re.push(Token::RParen);
+            i += 1;
+        } else if c == '"' || c == '\'' {
+            let mut st = String::new();
+            i += 1;
+            while i < cs.len() && cs[i] != c {
+                if cs[i] == '\\' && i + 1 < cs.len() {
+                    i += 1;
+                }
+                st.push(cs[i]);
+                i += 1;
+            }
+            if i == cs.len() {
+                return Err("unterminated string in the expression".to_string());
+            }
+            re.push(Token::Str(st));
+            i += 1;
+        } else if c.is_ascii_digit() || (c == '-' && i + 1 < cs.len() && cs[i + 1].is_ascii_digit())
+        {
+            let start = i;
+            i += 1;
+            while i < cs.len() && (cs[i].is_ascii_digit() || ".eE".contains(cs[i])) {
+                i += 1;
+            }
+            let n: String = cs[start..i].iter().collect();
+            match n.parse::<f64>() {
+                Ok(x) => re.push(Token::Num(x)),
+                Err(_) => return Err(format!("invalid number \"{}\" in the expression", n)),
+            }
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < cs.len() && (cs[i].is_alphanumeric() || "_.-:".contains(cs[i])) {
+                i += 1;
+            }
+            re.push(Token::Ident(cs[start..i].iter().collect()));
+        } else {
+            let two: String = cs[i..(i + 2).min(cs.len())].iter().collect();
+            if ["==", "!=", "<=", ">=", "&&", "||"].contains(&two.as_str()) {
+                re.push(Token::Op(two));
+                i += 2;
+            } else if "<>!".contains(c) {
+                re.push(Token::Op(c.to_string()));
+                i += 1;
+            } else {
+                return Err(format!("unexpected character '{}' in the expression", c));
+            }
+        }
+    }
+    Ok(re)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek_op(&self, op: &str) -> bool {
+        matches!(self.tokens.get(self.pos), Some(Token::Op(x)) if x == op)
+    }
+    fn or(&mut self) -> Result<Node, String> {
+        let mut n = self.and()?;
+        while self.peek_op("||") {
+            self.pos += 1;
+            n = Node::Or(Box::new(n), Box::new(self.and()?));
+        }
+        Ok(n)
+    }
+    fn and(&mut self) -> Result<Node, String> {
+        let mut n = self.not()?;
+        while self.peek_op("&&") {
+            self.pos += 1;
+            n = Node::And(Box::new(n), Box::new(self.not()?));
+        }
+        Ok(n)
+    }
+    fn not(&mut self) -> Result<Node, String> {
+        if self.peek_op("!") {
+            self.pos += 1;
+            return Ok(Node::Not(Box::new(self.not()?)));
+        }
+        self.cmp()
+    }
+    fn cmp(&mut self) -> Result<Node, String> {
+        let n = self.primary()?;
+        for op in ["==", "!=", "<=", ">=", "<", ">"] {
+            if self.peek_op(op) {
+                self.pos += 1;
+                return Ok(Node::Cmp(
+                    op.to_string(),
+                    Box::new(n),
+                    Box::new(self.primary()?),
+                ));
+            }
+        }
+        Ok(n)
+    }
+    fn primary(&mut self) -> Result<Node, String> {
+        let t = match self.tokens.get(self.pos) {
+            Some(t) => t.clone(),
+            None => return Err("unexpected end of the expression".to_string()),
+        };
+        self.pos += 1;
+        match t {
+            Token::LParen => {
+                let n = self.or()?;
+                match self.tokens.get(self.pos) {
+                    Some(Token::RParen) => {
+                        self.pos += 1;
+                        Ok(n)
+                    }
+                    _ => Err("missing ')' in the expression".to_string()),
+                }
+            }
+            Token::Num(x) => Ok(Node::Lit(Val::Num(x))),
+            Token::Str(x) => Ok(Node::Lit(Val::Str(x))),
+            Token::Ident(x) => match x.as_str() {
+                "true" => Ok(Node::Lit(Val::Bool(true))),
+                "false" => Ok(Node::Lit(Val::Bool(false))),
+                "null" => Ok(Node::Lit(Val::Null)),
+                "id" | "type" | "centroid.x" | "centroid.y" | "centroid.z" | "bbox.minx"
+                | "bbox.miny" | "bbox.minz" | "bbox.maxx" | "bbox.maxy" | "bbox.maxz" => {
+                    Ok(Node::Var(x))
+                }
+                _ if x.starts_with("attr.") && x.len() > 5 => Ok(Node::Var(x)),
+                _ => Err(format!("unknown variable \"{}\" in the expression", x)),
+            },
+            t => Err(format!("unexpected {:?} in the expression", t)),
+        }
+    }
+}
+
+struct Context<'a> {
+    cjf: &'a CityJSONFeature,
+    transform: &'a Transform,
+    centroid: std::cell::OnceCell<[f64; 3]>,
+    bbox: std::cell::OnceCell<Option<[f64; 6]>>,
+}
+
+impl Context<'_> {
+    fn eval(&self, n: &Node) -> Val {
+        match n {
+            Node::Or(a, b) => Val::Bool(truthy(&self.eval(a)) || truthy(&self.eval(b))),
+            Node::And(a, b) => Val::Bool(truthy(&self.eval(a)) && truthy(&self.eval(b))),
+            Node::Not(a) => Val::Bool(!truthy(&self.eval(a))),
+            Node::Cmp(op, a, b) => Val::Bool(compare(op, &self.eval(a), &self.eval(b))),
+            Node::Lit(v) => v.clone(),
+            Node::Var(x) => self.var(x),
+        }
+    }
+    fn var(&self, x: &str) -> Val {
+        let co = self.cjf.city_objects.get(&self.cjf.id);
+        match x {
+            "id" => Val::Str(self.cjf.id.clone()),
+            "type" => co.map_or(Val::Null, |co| Val::Str(co.thetype.clone())),
+            _ if x.starts_with("centroid.") => {
+                let c = self
+                    .centroid
+                    .get_or_init(|| self.cjf.main_centroid(self.transform));
+                Val::Num(c["xyz".find(&x[9..]).unwrap()])
+            }
+            _ if x.starts_with("bbox.") => {
+                let b = self.bbox.get_or_init(|| self.cjf.bbox(self.transform));
+                let i = ["minx", "miny", "minz", "maxx", "maxy", "maxz"]
+                    .iter()
+                    .position(|k| *k == &x[5..])
+                    .unwrap();
+                b.map_or(Val::Null, |b| Val::Num(b[i]))
+            }
+            _ => {
+                let mut v = co.and_then(|co| co.attributes.as_ref());
+                for k in x[5..].split('.') {
+                    v = v.and_then(|a| a.get(k));
+                }
+                match v {
+                    Some(Value::Number(n)) => Val::Num(n.as_f64().unwrap_or(f64::NAN)),
+                    Some(Value::String(s)) => Val::Str(s.clone()),
+                    Some(Value::Bool(b)) => Val::Bool(*b),
+                    Some(Value::Null) | None => Val::Null,
+                    Some(j) => Val::Str(j.to_string()),
+                }
+            }
+        }
+    }
+}
+
+fn truthy(v: &Val) -> bool {
+    match v {
+        Val::Bool(b) => *b,
+        Val::Num(n) => *n != 0.0,
+        Val::Str(s) => !s.is_empty(),
+        Val::Null => false,
+    }
+}
+
+fn compare(op: &str, a: &Val, b: &Val) -> bool {
+    //-- values of different types are never equal, and cannot be ordered
+    let ord = match (a, b) {
+        (Val::Num(x), Val::Num(y)) => x.partial_cmp(y),
+        (Val::Str(x), Val::Str(y)) => Some(x.cmp(y)),
+        (Val::Bool(x), Val::Bool(y)) if op == "==" || op == "!=" => Some(x.cmp(y)),
+        (Val::Null, Val::Null) if op == "==" || op == "!=" => Some(std::cmp::Ordering::Equal),
+        _ => None,
+    };
+    match (op, ord) {
+        ("!=", None) => true,
+        (_, None) => false,
+        ("==", Some(o)) => o.is_eq(),
+        ("!=", Some(o)) => o.is_ne(),
+        ("<", Some(o)) => o.is_lt(),
+        ("<=", Some(o)) => o.is_le(),
+        (">", Some(o)) => o.is_gt(),
+        (">=", Some(o)) => o.is_ge(),
+        _ => false,
+    }
+}
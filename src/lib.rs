@@ -0,0 +1,7 @@
+pub mod cityjson;
+pub mod conv;
+pub mod filter;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+pub mod stream;
+pub mod validation;
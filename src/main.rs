@@ -1,32 +1,44 @@
-use crate::cityjson::Appearance;
-use crate::cityjson::CityJSON;
-use crate::cityjson::CityJSONFeature;
-use crate::cityjson::CityObject;
-use crate::cityjson::GeometryTemplates;
-use crate::cityjson::Transform;
-use serde_json::{json, Value};
+use cjseq::cityjson::AppearanceSummary;
+use cjseq::cityjson::CityJSON;
+use cjseq::cityjson::CityJSONFeature;
+use cjseq::cityjson::CjseqError;
+use cjseq::cityjson::CollectReport;
+use cjseq::cityjson::DupStrategy;
+use cjseq::cityjson::SortingStrategy;
+use cjseq::cityjson::Stats;
+use cjseq::cityjson::Transform;
+use cjseq::cityjson::TypeStats;
+use cjseq::cityjson::TRANSFORM_EPSILON;
+use cjseq::conv;
+use cjseq::filter::Expr;
+use cjseq::stream::CatStream;
 
 extern crate clap;
 
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::fmt;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::BufRead;
 use std::io::BufReader;
-use std::io::{self, Read, Write};
-use std::path::PathBuf;
+use std::io::BufWriter;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-use std::collections::HashMap;
-
-mod cityjson;
-
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 
 #[derive(Parser)]
 #[command(version, about = "Create/process/modify CityJSONSeq files", long_about = None)]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// What to do with an invalid line (or feature for cat)
+    #[arg(long, value_enum, global = true, default_value_t = OnError::Abort)]
+    on_error: OnError,
 }
 
 #[derive(Subcommand)]
@@ -36,12 +48,166 @@ enum Commands {
         /// CityJSONSeq input file
         #[arg(short, long)]
         file: Option<PathBuf>,
+        /// Tolerate trailing commas in the input, and accept a CityJSON without "transform":
+        /// its real coordinates are then quantized (at most 6 decimals, the others are rounded)
+        #[arg(long)]
+        lenient: bool,
+        /// Reject the unknown members of the CityJSON and of the CityObjects (eg typos)
+        #[arg(long, conflicts_with = "lenient")]
+        strict: bool,
+        #[command(flatten)]
+        lines: CatLines,
+        /// Read the file twice instead of loading it in memory (for huge files)
+        #[arg(long, requires = "file", conflicts_with_all = ["lenient", "strict", "local_templates"])]
+        stream: bool,
+        /// One CityJSONSeq file per cell of a grid over the dataset (by centroid),
+        /// eg "out_1_0.city.jsonl" for the column 1 and the row 0
+        #[arg(long, value_name = "COLSxROWS", value_parser = parse_grid, conflicts_with_all = ["stream", "pretty_features", "local_templates"])]
+        grid: Option<Grid>,
+        #[command(flatten)]
+        output: OutputFiles,
+        #[command(flatten)]
+        transforms: TransformArgs,
     },
     /// CityJSON ==> CityJSONSeq
     Collect {
+        /// CityJSONSeq input file (can be repeated, eg for tiles)
+        #[arg(short, long)]
+        file: Vec<PathBuf>,
+        /// Tolerate trailing commas in the input, and accept a CityJSON without "transform":
+        /// its real coordinates are then quantized (at most 6 decimals, the others are rounded)
+        #[arg(long)]
+        lenient: bool,
+        /// Reject the unknown members of the CityJSON, the CityJSONFeatures and the CityObjects
+        #[arg(long, conflicts_with = "lenient")]
+        strict: bool,
+        #[command(flatten)]
+        options: CollectOptions,
+        #[command(flatten)]
+        transforms: TransformArgs,
+    },
+    /// Split a CityJSON into one CityJSON file per type of (top-level) CityObject,
+    /// eg "out_Building.city.json"
+    Split {
         /// CityJSON input file
         #[arg(short, long)]
         file: Option<PathBuf>,
+        #[command(flatten)]
+        output: OutputFiles,
+    },
+    /// Information about a CityJSON or a CityJSONSeq
+    #[command(alias = "stats")]
+    #[command(group(clap::ArgGroup::new("what").required(true).args(["appearance", "per_type"])))]
+    Info {
+        /// CityJSON or CityJSONSeq input file
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+        /// The materials and textures used by each type of CityObject, and those unused
+        #[arg(long)]
+        appearance: bool,
+        /// A table with, for each type of CityObject, the count, vertices, average number
+        /// of geometries, LoDs, and if textured
+        #[arg(long, conflicts_with = "appearance")]
+        per_type: bool,
+        /// The --per-type table as JSON
+        #[arg(long, requires = "per_type")]
+        json: bool,
+    },
+    /// CityJSON or CityJSONSeq ==> other formats
+    #[command(alias = "export")]
+    Convert {
+        /// CityJSON or CityJSONSeq input file (stdin if not given)
+        #[arg(short, long, short_alias = 'f', alias = "file")]
+        input: Option<PathBuf>,
+        /// Output file (stdout if not given)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Output format
+        #[arg(long, value_enum)]
+        format: ExportFormat,
+        /// Drop the z-coordinates (wkt and geojson only)
+        #[arg(long = "2d")]
+        flat: bool,
+        /// Subtract the translate of the file from the coordinates (obj only)
+        #[arg(long)]
+        local_origin: bool,
+        /// Use the geometries of this LoD instead of the highest one, eg "2.2"
+        #[arg(long)]
+        lod: Option<String>,
+        /// Triangulate the surfaces (their holes are ignored)
+        #[arg(long)]
+        triangulate: bool,
+        /// Number of decimals of the coordinates (default: those of the transform)
+        #[arg(long)]
+        precision: Option<usize>,
+        /// Colour the vertices with the diffuseColor of the materials (obj only)
+        #[arg(long)]
+        vertex_colors: bool,
+    },
+    /// Validate a CityJSON or a CityJSONSeq (which is first collected)
+    #[command(group(clap::ArgGroup::new("checks").required(true).multiple(true)))]
+    Validate {
+        /// CityJSON or CityJSONSeq input file
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+        #[command(flatten)]
+        checks: ValidateChecks,
+    },
+    /// Append the features of a CityJSONSeq to an existing CityJSONSeq file
+    Append {
+        /// Existing CityJSONSeq file, modified in place
+        file: PathBuf,
+        /// CityJSONSeq with the new features (stdin if not given)
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+        /// Extend the geographicalExtent of the header (the file is rewritten)
+        #[arg(long)]
+        update_extent: bool,
+    },
+    /// Canonical version of a CityJSON (or CityJSONSeq), for diffing and caching
+    Normalize {
+        /// CityJSON or CityJSONSeq input file
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+        /// Report what would be removed, without writing any output
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Extent (minx, miny, minz, maxx, maxy, maxz) of a CityJSONSeq, without collecting it
+    Extent {
+        /// CityJSONSeq input file
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+    },
+    /// Only the header (metadata, CRS, transform...) of a CityJSON or a CityJSONSeq
+    Header {
+        /// CityJSON or CityJSONSeq input file
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+    },
+    /// Sort the features of a CityJSONSeq (the lines are not modified)
+    Reorder {
+        /// CityJSONSeq input file
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+        /// Order of the features
+        #[arg(long, value_enum, default_value = "alphabetical")]
+        order: FeatureOrder,
+        /// Seed of --order random, to always get the same order (default: a random one)
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    /// Hash of the content of a CityJSON or a CityJSONSeq, independent of the ordering
+    Hash {
+        /// CityJSON or CityJSONSeq input file
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+    },
+    /// Quick check that every line of a CityJSONSeq is valid JSON
+    Check {
+        /// CityJSONSeq input file
+        #[arg(short, long)]
+        file: Option<PathBuf>,
     },
     /// Filter a CityJSONSeq
     Filter {
@@ -66,33 +232,379 @@ enum Commands {
         /// 1/X chances of a given feature being kept
         #[arg(long, value_name = "X", value_parser = clap::value_parser!(u32).range(1..), group = "exclusive")]
         random: Option<u32>,
+        /// Keep the features for which the expression is true,
+        /// eg 'type == "Building" && attr.height > 10'
+        #[arg(long, value_name = "EXPR", group = "exclusive")]
+        expr: Option<String>,
+        /// Remove the children from the features output (eg the BuildingParts of a Building)
+        #[arg(long, conflicts_with = "children_only")]
+        no_children: bool,
+        /// Keep only the geometries of the children in the features output
+        #[arg(long)]
+        children_only: bool,
+        /// Set the geographicalExtent of the header to that of the features kept
+        /// (the output is buffered)
+        #[arg(long)]
+        update_extent: bool,
     },
 }
 
+#[derive(Clone, ValueEnum)]
+enum ExportFormat {
+    /// TSV with the id and the WKT of each CityObject (highest LoD)
+    Wkt,
+    /// Wavefront OBJ, one object per CityObject (highest LoD)
+    Obj,
+    /// GeoJSON FeatureCollection, one Feature per CityObject (highest LoD)
+    Geojson,
+    /// Parquet table of the attributes, one row per feature (input is a CityJSONSeq)
+    Parquet,
+}
+
+/// How the features are collected
+#[derive(Args)]
+struct CollectOptions {
+    /// Sort the vertices (x, y, z) so that the output doesn't depend on the order of the features
+    #[arg(long)]
+    canonical_vertices: bool,
+    /// What to do with a CityObject id that is already used (default: the last one is kept)
+    #[arg(long, value_enum)]
+    duplicate_ids: Option<DuplicateIds>,
+    /// Merge the duplicate vertices of the whole file, or only those of each CityObject
+    #[arg(long, value_enum, default_value = "global")]
+    dedup_scope: DedupScope,
+    /// Keep the vertices of the features as they are: no merging, same transform
+    #[arg(long, conflicts_with_all = ["canonical_vertices", "dedup_scope"])]
+    faithful: bool,
+    /// Merge the textures with the same content (image and properties), even if their
+    /// paths differ
+    #[arg(long)]
+    dedup_textures: bool,
+    /// Remove the vertices not used by any geometry
+    #[arg(long, conflicts_with = "faithful")]
+    prune_vertices: bool,
+    /// Print on stderr the number of vertices and the translate before/after the vertices
+    /// are merged
+    #[arg(long)]
+    stats: bool,
+    /// The header (metadata, transform, CRS) is this CityJSON file without CityObjects, all
+    /// the lines of the input are then CityJSONFeatures
+    #[arg(long, value_name = "FILE")]
+    header: Option<PathBuf>,
+    /// The input is a JSON array of CityJSONFeatures (the 1st element can be the CityJSON
+    /// with the metadata) instead of a CityJSONSeq
+    #[arg(long)]
+    array: bool,
+}
+
+#[derive(Clone, PartialEq, ValueEnum)]
+enum DedupScope {
+    Global,
+    Object,
+}
+
+#[derive(Clone, Copy)]
+struct Grid {
+    cols: u32,
+    rows: u32,
+}
+
+fn parse_grid(s: &str) -> Result<Grid, String> {
+    let e = || {
+        format!(
+            "\"{}\" is not a grid, eg \"4x3\" for 4 columns and 3 rows",
+            s
+        )
+    };
+    let (c, r) = s.split_once('x').ok_or_else(e)?;
+    match (c.parse::<u32>(), r.parse::<u32>()) {
+        (Ok(cols), Ok(rows)) if cols > 0 && rows > 0 => Ok(Grid { cols, rows }),
+        _ => Err(e()),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Info {
+    Appearance,
+    PerType,
+    PerTypeJson,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ParseMode {
+    Default,
+    //-- trailing commas and no "transform"
+    Lenient,
+    //-- no unknown members
+    Strict,
+}
+
+impl ParseMode {
+    fn new(lenient: bool, strict: bool) -> Self {
+        match (lenient, strict) {
+            (true, _) => ParseMode::Lenient,
+            (_, true) => ParseMode::Strict,
+            _ => ParseMode::Default,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Children {
+    All,
+    Removed,
+    Only,
+}
+
+#[derive(Clone, ValueEnum)]
+enum FeatureOrder {
+    /// By id
+    Alphabetical,
+    /// By type of the main CityObject, then by id
+    Type,
+    /// Shuffled (reproducible with --seed)
+    Random,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum OnError {
+    /// Stop at the first error
+    Abort,
+    /// Skip the line (a warning is printed)
+    Skip,
+    /// Skip the line, and print a JSON report of all the errors at the end
+    Collect,
+}
+
+//-- the errors of the lines of a CityJSONSeq (or of the features for cat), following --on-error
+struct ErrorPolicy {
+    on_error: OnError,
+    errors: Vec<serde_json::Value>,
+}
+impl ErrorPolicy {
+    fn new(on_error: OnError) -> Self {
+        ErrorPolicy {
+            on_error,
+            errors: Vec::new(),
+        }
+    }
+    fn handle_line(&mut self, i: usize, e: MyError) -> Result<(), MyError> {
+        //-- i is the index of the line (0-based)
+        self.handle(
+            format!("line {}", i + 1),
+            serde_json::json!({"line": i + 1}),
+            e,
+        )
+    }
+    fn handle_feature(&mut self, id: &str, e: MyError) -> Result<(), MyError> {
+        self.handle(format!("feature #{}", id), serde_json::json!({"id": id}), e)
+    }
+    fn handle(
+        &mut self,
+        what: String,
+        mut at: serde_json::Value,
+        e: MyError,
+    ) -> Result<(), MyError> {
+        match self.on_error {
+            OnError::Abort => return Err(e),
+            OnError::Skip => eprintln!("Warning: {} skipped: {}", what, e),
+            OnError::Collect => {
+                at["error"] = serde_json::json!(e.to_string());
+                self.errors.push(at);
+            }
+        }
+        Ok(())
+    }
+    fn finish(self) -> Result<(), MyError> {
+        if self.errors.is_empty() {
+            return Ok(());
+        }
+        let n = self.errors.len();
+        eprintln!("{}", serde_json::json!({ "errors": self.errors }));
+        Err(MyError::CityJson(format!("{} error(s) found", n)))
+    }
+}
+
+#[derive(Clone, ValueEnum)]
+enum DuplicateIds {
+    /// Stop with an error
+    Error,
+    /// Rename the new CityObject ("id_1", "id_2", ...)
+    SuffixNumeric,
+    /// Keep the first one, the feature is skipped
+    KeepFirst,
+}
+
+/// The checks performed by validate
+#[derive(Args)]
+struct ValidateChecks {
+    /// Validate against the official CityJSON schema
+    #[arg(long, group = "checks")]
+    schema: bool,
+    /// Check that the Extensions used ("+Foo") are declared in "extensions"
+    #[arg(long, group = "checks")]
+    extensions: bool,
+    /// Check that the "referenceSystem" is a valid OGC CRS URL (eg EPSG code is a number)
+    #[arg(long, group = "checks")]
+    crs: bool,
+    /// Check that cat then collect gives back the same CityJSON
+    #[arg(long, group = "checks")]
+    roundtrip: bool,
+    /// Check that each Solid has at least 1 shell, and each shell at least 4 surfaces
+    #[arg(long, group = "checks")]
+    solids: bool,
+    /// With --solids, also check that the interior shells are inside the exterior one (bbox)
+    #[arg(long, requires = "solids")]
+    interior_shells: bool,
+    /// Check that the semantics have one value per surface, each a valid surface index
+    #[arg(long, group = "checks")]
+    semantics: bool,
+    /// Check that no vertices collapse if the CityJSON is quantized with this scale
+    #[arg(long, value_name = "SCALE", group = "checks")]
+    scale: Option<f64>,
+    /// Check that the CityObjects of a type all have the same attributes (eg before an export)
+    #[arg(long, group = "checks")]
+    attributes: bool,
+    /// Check that each CityObject (or one of its children) has a geometry with at least this LoD
+    #[arg(long, value_name = "LOD", group = "checks")]
+    min_lod: Option<f64>,
+    /// Check the fields that differ between the versions (eg a LoD is a string since v1.1)
+    #[arg(long, group = "checks")]
+    version_fields: bool,
+}
+
+/// How cat writes the features
+#[derive(Args, Clone, Copy)]
+struct CatLines {
+    /// Pretty-print each line, for debugging (the output is not a valid CityJSONSeq)
+    #[arg(long)]
+    pretty_features: bool,
+    /// Each feature gets a copy of the geometry-templates it uses, instead of those being
+    /// in the 1st line (an extension, not in the specifications)
+    #[arg(long)]
+    local_templates: bool,
+}
+
+/// Where the commands with several output files write them
+#[derive(Args)]
+struct OutputFiles {
+    /// Directory of the output files (created if needed) [default: the current directory]
+    #[arg(long, value_name = "DIR")]
+    output_dir: Option<PathBuf>,
+    /// Name of the output files, with "{type}" (split) or "{col}", "{row}" and "{index}"
+    /// (cat --grid), eg "tile_{index}.city.jsonl"
+    #[arg(long, value_name = "TEMPLATE")]
+    name_template: Option<String>,
+}
+
+impl OutputFiles {
+    fn any(&self) -> bool {
+        self.output_dir.is_some() || self.name_template.is_some()
+    }
+    fn path(&self, default: &str, vars: &[(&str, String)]) -> Result<PathBuf, MyError> {
+        //-- the template (or the default one) with the variables replaced, in the output dir
+        let mut name = self.name_template.as_deref().unwrap_or(default).to_string();
+        for (k, v) in vars {
+            name = name.replace(&format!("{{{}}}", k), v);
+        }
+        if name.contains('{') || name.contains('}') || name.is_empty() {
+            return Err(MyError::CityJson(format!(
+                "invalid --name-template \"{}\", the variables are {}",
+                self.name_template.as_deref().unwrap_or(default),
+                vars.iter()
+                    .map(|(k, _)| format!("{{{}}}", k))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )));
+        }
+        let dir = self.output_dir.as_deref().unwrap_or(Path::new("."));
+        fs::create_dir_all(dir)?;
+        Ok(dir.join(name))
+    }
+}
+
+/// Modifications applied to the CityJSON object before it is output
+#[derive(Args)]
+struct TransformArgs {
+    /// Rename all the CityObjects to short sequential ids ("0", "1", ...)
+    #[arg(long)]
+    compact_indices: bool,
+    /// Write the map new id ==> original id to this JSON file
+    #[arg(long, requires = "compact_indices")]
+    id_map: Option<PathBuf>,
+    /// Replace the GeometryInstances by explicit geometries (geometry-templates are removed)
+    #[arg(long)]
+    expand_templates: bool,
+    /// Embed the texture images in the file (as base64 data URIs)
+    #[arg(long)]
+    inline_textures: bool,
+    /// Remove the geometries without any vertex (eg "boundaries": [])
+    #[arg(long)]
+    drop_empty_geometry: bool,
+    /// Remove this attribute from all the CityObjects (can be repeated)
+    #[arg(long, value_name = "KEY", conflicts_with = "keep_attr")]
+    drop_attr: Vec<String>,
+    /// Keep only this attribute in all the CityObjects (can be repeated)
+    #[arg(long, value_name = "KEY")]
+    keep_attr: Vec<String>,
+    /// Remove the Extension CityObjects and attributes ("+Foo"), this is lossy
+    #[arg(long)]
+    strip_extensions: bool,
+    /// Remove the materials and textures (and the texture vertices)
+    #[arg(long, conflicts_with_all = ["theme", "inline_textures"])]
+    drop_appearance: bool,
+    /// Keep only the materials and textures of this theme (without NAME: the default themes)
+    #[arg(long, value_name = "NAME", num_args = 0..=1, default_missing_value = "")]
+    theme: Option<String>,
+    /// Report what the options above would remove, without writing any output
+    #[arg(long)]
+    dry_run: bool,
+}
+impl TransformArgs {
+    fn any(&self) -> bool {
+        self.theme.is_some()
+            || self.drop_appearance
+            || self.compact_indices
+            || self.expand_templates
+            || self.inline_textures
+            || self.drop_empty_geometry
+            || !self.drop_attr.is_empty()
+            || !self.keep_attr.is_empty()
+            || self.strip_extensions
+    }
+}
+
 #[derive(Debug)]
 enum MyError {
-    IoError(std::io::Error),
-    JsonError(serde_json::Error),
-    CityJsonError(String),
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    CityJson(String),
+    Cjseq(CjseqError),
 }
 impl fmt::Display for MyError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            MyError::JsonError(json_error) => write!(f, "Error (JSON): {}", json_error),
-            MyError::IoError(io_error) => write!(f, "Error (io): {}", io_error),
-            MyError::CityJsonError(cjson_error) => write!(f, "Error (CityJSON): {}", cjson_error),
+            MyError::Json(json_error) => write!(f, "Error (JSON): {}", json_error),
+            MyError::Io(io_error) => write!(f, "Error (io): {}", io_error),
+            MyError::CityJson(cjson_error) => write!(f, "Error (CityJSON): {}", cjson_error),
+            MyError::Cjseq(cjseq_error) => write!(f, "Error (CityJSON): {}", cjseq_error),
         }
     }
 }
 impl std::error::Error for MyError {}
 impl From<serde_json::Error> for MyError {
     fn from(err: serde_json::Error) -> Self {
-        MyError::JsonError(err)
+        MyError::Json(err)
     }
 }
 impl From<std::io::Error> for MyError {
     fn from(err: std::io::Error) -> Self {
-        MyError::IoError(err)
+        MyError::Io(err)
+    }
+}
+impl From<CjseqError> for MyError {
+    fn from(err: CjseqError) -> Self {
+        MyError::Cjseq(err)
     }
 }
 
@@ -101,30 +613,231 @@ fn main() {
 
     match &cli.command {
         //-- cat
-        Commands::Cat { file } => match file {
+        Commands::Cat {
+            file,
+            lenient,
+            strict,
+            lines,
+            stream,
+            grid,
+            output,
+            transforms,
+        } => match file {
+            _ if output.any() && grid.is_none() => {
+                eprintln!("--output-dir and --name-template can only be used with --grid");
+                std::process::exit(1);
+            }
+            Some(x) if *stream => {
+                if let Err(e) = cat_streaming(x, lines.pretty_features, transforms) {
+                    eprintln!("{e}");
+                    std::process::exit(1);
+                }
+            }
             Some(x) => {
-                if let Err(e) = cat_from_file(x) {
+                let re = cat_from_file(
+                    x,
+                    ParseMode::new(*lenient, *strict),
+                    *lines,
+                    *grid,
+                    output,
+                    transforms,
+                    cli.on_error,
+                );
+                if let Err(e) = re {
                     eprintln!("{e}");
                     std::process::exit(1);
                 }
             }
             None => {
-                if let Err(e) = cat_from_stdin() {
+                let re = cat_from_stdin(
+                    ParseMode::new(*lenient, *strict),
+                    *lines,
+                    *grid,
+                    output,
+                    transforms,
+                    cli.on_error,
+                );
+                if let Err(e) = re {
                     eprintln!("{e}");
                     std::process::exit(1);
                 }
             }
         },
         //-- collect
-        Commands::Collect { file } => match file {
+        Commands::Collect {
+            file,
+            lenient,
+            strict,
+            options,
+            transforms,
+        } => {
+            let mode = ParseMode::new(*lenient, *strict);
+            let re = if file.is_empty() {
+                collect_from_stdin(mode, options, transforms, cli.on_error)
+            } else {
+                collect_from_files(file, mode, options, transforms, cli.on_error)
+            };
+            if let Err(e) = re {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+        //-- split
+        Commands::Split { file, output } => {
+            let re = match file {
+                Some(x) => split_from_file(x, output),
+                None => split_from_stdin(output),
+            };
+            if let Err(e) = re {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+        //-- info
+        Commands::Info {
+            file,
+            appearance: _,
+            per_type,
+            json,
+        } => {
+            let what = match (*per_type, *json) {
+                (false, _) => Info::Appearance,
+                (true, false) => Info::PerType,
+                (true, true) => Info::PerTypeJson,
+            };
+            let re = match file {
+                Some(x) => info_from_file(x, what),
+                None => info_from_stdin(what),
+            };
+            if let Err(e) = re {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+        //-- convert
+        Commands::Convert {
+            input,
+            output,
+            format,
+            flat,
+            local_origin,
+            lod,
+            triangulate,
+            precision,
+            vertex_colors,
+        } => {
+            let opts = conv::Options {
+                lod: lod.clone(),
+                triangulate: *triangulate,
+                precision: *precision,
+                vertex_colors: *vertex_colors,
+            };
+            let re = convert(input, output, format, *flat, *local_origin, &opts);
+            if let Err(e) = re {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+        //-- validate
+        Commands::Validate { file, checks } => {
+            let re = match file {
+                Some(x) => validate_from_file(x, checks),
+                None => validate_from_stdin(checks),
+            };
+            if let Err(e) = re {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+        //-- append
+        Commands::Append {
+            file,
+            input,
+            update_extent,
+        } => {
+            let re = match input {
+                Some(x) => File::open(x)
+                    .map_err(MyError::from)
+                    .and_then(|f| append(file, BufReader::new(f), *update_extent)),
+                None => append(file, std::io::stdin().lock(), *update_extent),
+            };
+            if let Err(e) = re {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+        //-- normalize
+        Commands::Normalize { file, dry_run } => {
+            let re = match file {
+                Some(x) => normalize_from_file(x, *dry_run),
+                None => normalize_from_stdin(*dry_run),
+            };
+            if let Err(e) = re {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+        //-- extent
+        Commands::Extent { file } => {
+            let re = match file {
+                Some(x) => File::open(x)
+                    .map_err(MyError::from)
+                    .and_then(|f| extent(BufReader::new(f))),
+                None => extent(std::io::stdin().lock()),
+            };
+            if let Err(e) = re {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+        //-- header
+        Commands::Header { file } => {
+            let re = match file {
+                Some(x) => File::open(x)
+                    .map_err(MyError::from)
+                    .and_then(|f| header(BufReader::new(f))),
+                None => header(std::io::stdin().lock()),
+            };
+            if let Err(e) = re {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+        //-- reorder
+        Commands::Reorder { file, order, seed } => {
+            let seed = seed.unwrap_or_else(|| rand::thread_rng().gen());
+            let re = match file {
+                Some(x) => File::open(x)
+                    .map_err(MyError::from)
+                    .and_then(|f| reorder(BufReader::new(f), order, seed, cli.on_error)),
+                None => reorder(std::io::stdin().lock(), order, seed, cli.on_error),
+            };
+            if let Err(e) = re {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+        //-- hash
+        Commands::Hash { file } => {
+            let re = match file {
+                Some(x) => hash_from_file(x),
+                None => hash_from_stdin(),
+            };
+            if let Err(e) = re {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+        //-- check
+        Commands::Check { file } => match file {
             Some(x) => {
-                if let Err(e) = collect_from_file(x) {
+                if let Err(e) = check_from_file(x) {
                     eprintln!("{e}");
                     std::process::exit(1);
                 }
             }
             None => {
-                if let Err(e) = collect_from_stdin() {
+                if let Err(e) = check_from_stdin() {
                     eprintln!("{e}");
                     std::process::exit(1);
                 }
@@ -137,28 +850,48 @@ fn main() {
             exclude,
             radius,
             random,
+            expr,
+            no_children,
+            children_only,
+            update_extent,
         } => {
+            let children = if *no_children {
+                Children::Removed
+            } else if *children_only {
+                Children::Only
+            } else {
+                Children::All
+            };
+            let out = || FilterOutput::new(children, *update_extent);
             if bbox.is_some() {
-                if let Err(e) = filter_bbox(*exclude, &bbox.clone().unwrap()) {
+                if let Err(e) = filter_bbox(*exclude, &bbox.clone().unwrap(), out(), cli.on_error) {
                     eprintln!("{e}");
                     std::process::exit(1);
                 }
             }
             if cotype.is_some() {
-                if let Err(e) = filter_cotype(*exclude, cotype.clone().unwrap()) {
+                if let Err(e) =
+                    filter_cotype(*exclude, cotype.clone().unwrap(), out(), cli.on_error)
+                {
                     eprintln!("{e}");
                     std::process::exit(1);
                 }
             }
             if radius.is_some() {
                 let p: Vec<f64> = radius.clone().unwrap();
-                if let Err(e) = filter_radius(*exclude, p[0], p[1], p[2]) {
+                if let Err(e) = filter_radius(*exclude, p[0], p[1], p[2], out(), cli.on_error) {
                     eprintln!("{e}");
                     std::process::exit(1);
                 }
             }
             if random.is_some() {
-                if let Err(e) = filter_random(*exclude, random.unwrap()) {
+                if let Err(e) = filter_random(*exclude, random.unwrap(), out()) {
+                    eprintln!("{e}");
+                    std::process::exit(1);
+                }
+            }
+            if let Some(expr) = expr {
+                if let Err(e) = filter_expr(*exclude, expr, out(), cli.on_error) {
                     eprintln!("{e}");
                     std::process::exit(1);
                 }
@@ -167,316 +900,1460 @@ fn main() {
     }
 }
 
-fn filter_random(exclude: bool, rand_factor: u32) -> Result<(), MyError> {
+fn with_line_number(i: usize, line: io::Result<String>) -> Result<String, MyError> {
+    //-- eg invalid UTF-8, the error says which line
+    line.map_err(|e| MyError::Io(io::Error::new(e.kind(), format!("line {}: {}", i + 1, e))))
+}
+
+fn filter_random(exclude: bool, rand_factor: u32, mut out: FilterOutput) -> Result<(), MyError> {
     let stdin = std::io::stdin();
     let mut rng = rand::thread_rng();
     for (i, line) in stdin.lock().lines().enumerate() {
         let mut w: bool = false;
-        let l = line.unwrap();
+        let l = with_line_number(i, line)?;
         if i == 0 {
-            io::stdout().write_all(&format!("{}\n", l).as_bytes())?;
+            out.header(&l)?;
         } else {
             let r: u32 = rng.gen_range(1..=rand_factor);
             if r == 1 {
                 w = true;
             }
-            if (w == true && !exclude) || (w == false && exclude) {
-                io::stdout().write_all(&format!("{}\n", l).as_bytes())?;
+            if (w && !exclude) || (!w && exclude) {
+                out.feature_line(&l)?;
             }
         }
     }
-    Ok(())
+    out.finish()
+}
+
+struct FilterOutput {
+    children: Children,
+    //-- with update_extent the features are buffered, and the header (with the new extent)
+    //-- is written first in finish()
+    header: Option<CityJSON>,
+    update_extent: bool,
+    lines: Vec<String>,
+    extent: Option<[f64; 6]>,
 }
 
-fn filter_cotype(exclude: bool, cotype: String) -> Result<(), MyError> {
+impl FilterOutput {
+    fn new(children: Children, update_extent: bool) -> Self {
+        FilterOutput {
+            children,
+            header: None,
+            update_extent,
+            lines: Vec::new(),
+            extent: None,
+        }
+    }
+    fn header(&mut self, l: &str) -> Result<&CityJSON, MyError> {
+        let cj = CityJSON::from_str(l)?;
+        if !self.update_extent {
+            io::stdout().write_all(format!("{}\n", l).as_bytes())?;
+        }
+        Ok(self.header.insert(cj))
+    }
+    fn feature_line(&mut self, l: &str) -> Result<(), MyError> {
+        //-- the line is parsed only if needed
+        if self.children == Children::All && !self.update_extent {
+            return self.write(l.to_string());
+        }
+        self.feature(l, &CityJSONFeature::from_str(l)?)
+    }
+    fn feature(&mut self, l: &str, cjf: &CityJSONFeature) -> Result<(), MyError> {
+        //-- the line is output as is when the feature is not modified
+        let mut cjf = match self.children {
+            Children::All if !self.update_extent => return self.write(l.to_string()),
+            Children::All => return self.add(l.to_string(), cjf),
+            _ => cjf.clone(),
+        };
+        if self.children == Children::Removed {
+            cjf.remove_children();
+        } else {
+            cjf.keep_only_children();
+        }
+        let l = serde_json::to_string(&cjf)?;
+        match self.update_extent {
+            true => self.add(l, &cjf),
+            false => self.write(l),
+        }
+    }
+    fn add(&mut self, l: String, cjf: &CityJSONFeature) -> Result<(), MyError> {
+        if let Some(b) = cjf.bbox(&self.header.as_ref().unwrap().transform) {
+            self.extent = Some(union_extent(self.extent, b));
+        }
+        self.lines.push(l);
+        Ok(())
+    }
+    fn write(&mut self, l: String) -> Result<(), MyError> {
+        io::stdout().write_all(format!("{}\n", l).as_bytes())?;
+        Ok(())
+    }
+    fn finish(self) -> Result<(), MyError> {
+        if !self.update_extent {
+            return Ok(());
+        }
+        //-- without features kept the extent of the header is not modified
+        let mut header = match self.header {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+        if let Some(e) = self.extent {
+            header.set_geographical_extent(&e);
+        }
+        let mut out = BufWriter::new(io::stdout().lock());
+        writeln!(out, "{}", serde_json::to_string(&header)?)?;
+        for l in &self.lines {
+            writeln!(out, "{}", l)?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+}
+
+fn union_extent(e: Option<[f64; 6]>, b: [f64; 6]) -> [f64; 6] {
+    match e {
+        Some(e) => [
+            e[0].min(b[0]),
+            e[1].min(b[1]),
+            e[2].min(b[2]),
+            e[3].max(b[3]),
+            e[4].max(b[4]),
+            e[5].max(b[5]),
+        ],
+        None => b,
+    }
+}
+
+fn filter_cotype(
+    exclude: bool,
+    cotype: String,
+    mut out: FilterOutput,
+    on_error: OnError,
+) -> Result<(), MyError> {
+    let mut errors = ErrorPolicy::new(on_error);
     let stdin = std::io::stdin();
     for (i, line) in stdin.lock().lines().enumerate() {
         let mut w: bool = false;
-        let l = line.unwrap();
+        let l = with_line_number(i, line)?;
         if i == 0 {
-            io::stdout().write_all(&format!("{}\n", l).as_bytes())?;
+            out.header(&l)?;
         } else {
-            let cjf: CityJSONFeature = serde_json::from_str(&l)?;
+            let cjf = match CityJSONFeature::from_str(&l) {
+                Ok(x) => x,
+                Err(e) => {
+                    errors.handle_line(i, e.into())?;
+                    continue;
+                }
+            };
             if cjf.city_objects[&cjf.id].thetype == cotype {
                 w = true;
             }
-            if (w == true && !exclude) || (w == false && exclude) {
-                io::stdout().write_all(&format!("{}\n", l).as_bytes())?;
+            if (w && !exclude) || (!w && exclude) {
+                out.feature(&l, &cjf)?;
             }
         }
     }
-    Ok(())
+    //-- the output is flushed first (with --update-extent it is buffered)
+    out.finish()?;
+    errors.finish()
 }
 
-fn filter_bbox(exclude: bool, bbox: &Vec<f64>) -> Result<(), MyError> {
+fn filter_bbox(
+    exclude: bool,
+    bbox: &[f64],
+    mut out: FilterOutput,
+    on_error: OnError,
+) -> Result<(), MyError> {
+    let mut errors = ErrorPolicy::new(on_error);
     let stdin = std::io::stdin();
     let mut transform: Transform = Transform::new();
     for (i, line) in stdin.lock().lines().enumerate() {
         let mut w: bool = false;
-        let l = line.unwrap();
+        let l = with_line_number(i, line)?;
         if i == 0 {
-            io::stdout().write_all(&format!("{}\n", l).as_bytes())?;
-            let cj: CityJSON = serde_json::from_str(&l)?;
-            transform = cj.transform;
+            transform = out.header(&l)?.transform.clone();
         } else {
-            let cjf: CityJSONFeature = serde_json::from_str(&l)?;
-            let ci = cjf.centroid();
-            let cx = (ci[0] * transform.scale[0]) + transform.translate[0];
-            let cy = (ci[1] * transform.scale[1]) + transform.translate[1];
+            let cjf = match CityJSONFeature::from_str(&l) {
+                Ok(x) => x,
+                Err(e) => {
+                    errors.handle_line(i, e.into())?;
+                    continue;
+                }
+            };
+            let [cx, cy, _] = cjf.main_centroid(&transform);
             if (cx > bbox[0]) && (cx < bbox[2]) && (cy > bbox[1]) && (cy < bbox[3]) {
                 w = true;
             }
-            if (w == true && !exclude) || (w == false && exclude) {
-                io::stdout().write_all(&format!("{}\n", l).as_bytes())?;
+            if (w && !exclude) || (!w && exclude) {
+                out.feature(&l, &cjf)?;
             }
         }
     }
-    Ok(())
+    out.finish()?;
+    errors.finish()
 }
 
-fn filter_radius(exclude: bool, x: f64, y: f64, r: f64) -> Result<(), MyError> {
+fn filter_radius(
+    exclude: bool,
+    x: f64,
+    y: f64,
+    r: f64,
+    mut out: FilterOutput,
+    on_error: OnError,
+) -> Result<(), MyError> {
+    let mut errors = ErrorPolicy::new(on_error);
     let stdin = std::io::stdin();
     let mut transform: Transform = Transform::new();
     for (i, line) in stdin.lock().lines().enumerate() {
         let mut w: bool = false;
-        let l = line.unwrap();
+        let l = with_line_number(i, line)?;
         if i == 0 {
-            io::stdout().write_all(&format!("{}\n", l).as_bytes())?;
-            let cj: CityJSON = serde_json::from_str(&l)?;
-            transform = cj.transform;
+            transform = out.header(&l)?.transform.clone();
         } else {
-            let cjf: CityJSONFeature = serde_json::from_str(&l)?;
-            let ci = cjf.centroid();
-            let cx = (ci[0] * transform.scale[0]) + transform.translate[0];
-            let cy = (ci[1] * transform.scale[1]) + transform.translate[1];
+            let cjf = match CityJSONFeature::from_str(&l) {
+                Ok(x) => x,
+                Err(e) => {
+                    errors.handle_line(i, e.into())?;
+                    continue;
+                }
+            };
+            let [cx, cy, _] = cjf.main_centroid(&transform);
             let d2 = (cx - x).powf(2.0) + (cy - y).powf(2.0);
             if d2 <= (r * r) {
                 w = true;
             }
-            if (w == true && !exclude) || (w == false && exclude) {
-                io::stdout().write_all(&format!("{}\n", l).as_bytes())?;
+            if (w && !exclude) || (!w && exclude) {
+                out.feature(&l, &cjf)?;
             }
         }
     }
-    Ok(())
+    out.finish()?;
+    errors.finish()
 }
 
-fn collect_from_stdin() -> Result<(), MyError> {
+fn filter_expr(
+    exclude: bool,
+    expr: &str,
+    mut out: FilterOutput,
+    on_error: OnError,
+) -> Result<(), MyError> {
+    let mut errors = ErrorPolicy::new(on_error);
+    let expr = Expr::parse(expr).map_err(MyError::CityJson)?;
     let stdin = std::io::stdin();
-    let mut cjj: CityJSON = CityJSON::new();
+    let mut transform: Transform = Transform::new();
     for (i, line) in stdin.lock().lines().enumerate() {
-        let l = line.unwrap();
+        let l = with_line_number(i, line)?;
         if i == 0 {
-            cjj = serde_json::from_str(&l)?;
+            transform = out.header(&l)?.transform.clone();
         } else {
-            let cjf: CityJSONFeature = serde_json::from_str(&l)?;
-            cjj.add_one_cjf(cjf);
+            let cjf = match CityJSONFeature::from_str(&l) {
+                Ok(x) => x,
+                Err(e) => {
+                    errors.handle_line(i, e.into())?;
+                    continue;
+                }
+            };
+            let w = expr.eval(&cjf, &transform);
+            if (w && !exclude) || (!w && exclude) {
+                out.feature(&l, &cjf)?;
+            }
+        }
+    }
+    out.finish()?;
+    errors.finish()
+}
+
+fn split_from_stdin(output: &OutputFiles) -> Result<(), MyError> {
+    let mut input = String::new();
+    std::io::stdin().read_to_string(&mut input)?;
+    let cjj = CityJSON::from_str(&input)?;
+    split(&cjj, output)
+}
+
+fn split_from_file(file: &Path, output: &OutputFiles) -> Result<(), MyError> {
+    let mut input = String::new();
+    File::open(file.canonicalize()?)?.read_to_string(&mut input)?;
+    let cjj = CityJSON::from_str(&input)?;
+    split(&cjj, output)
+}
+
+fn split(cjj: &CityJSON, output: &OutputFiles) -> Result<(), MyError> {
+    let mut names: HashSet<PathBuf> = HashSet::new();
+    for (cotype, cj) in cjj.split_by_type()? {
+        let fname = output.path("out_{type}.city.json", &[("type", cotype)])?;
+        if !names.insert(fname.clone()) {
+            return Err(MyError::CityJson(format!(
+                "several types are written to {}, use {{type}}",
+                fname.display()
+            )));
+        }
+        let mut fout = File::create(&fname)?;
+        fout.write_all(format!("{}\n", serde_json::to_string(&cj)?).as_bytes())?;
+        eprintln!("{} ({} features)", fname.display(), cj.num_features());
+    }
+    Ok(())
+}
+
+fn convert(
+    input: &Option<PathBuf>,
+    output: &Option<PathBuf>,
+    format: &ExportFormat,
+    flat: bool,
+    local_origin: bool,
+    opts: &conv::Options,
+) -> Result<(), MyError> {
+    if local_origin && !matches!(format, ExportFormat::Obj) {
+        return Err(MyError::CityJson(
+            "--local-origin is only supported for obj".to_string(),
+        ));
+    }
+    if flat && matches!(format, ExportFormat::Obj) {
+        return Err(MyError::CityJson(
+            "--2d is not supported for obj".to_string(),
+        ));
+    }
+    let mut r: Box<dyn BufRead> = match input {
+        Some(x) => Box::new(BufReader::new(File::open(x.canonicalize()?)?)),
+        None => Box::new(std::io::stdin().lock()),
+    };
+    //-- the output is created once the options and the input are checked, otherwise an
+    //-- existing file would be truncated for nothing
+    let open_output = || -> Result<Box<dyn Write + Send>, MyError> {
+        match output {
+            Some(x) => Ok(Box::new(File::create(x)?)),
+            None => Ok(Box::new(io::stdout())),
+        }
+    };
+    if let ExportFormat::Parquet = format {
+        if flat
+            || local_origin
+            || opts.lod.is_some()
+            || opts.triangulate
+            || opts.precision.is_some()
+        {
+            return Err(MyError::CityJson(
+                "the options of the geometries are not supported for parquet".to_string(),
+            ));
+        }
+        //-- a CityJSONSeq is streamed, a CityJSON is first converted to one; the 1st line of a
+        //-- CityJSONSeq is a CityJSON without CityObjects, but so can be a one-line CityJSON
+        let mut first = String::new();
+        r.read_line(&mut first)?;
+        let is_seq_header = CityJSON::from_str(&first).is_ok_and(|h| h.city_objects.is_empty());
+        if is_seq_header && !r.fill_buf()?.is_empty() {
+            return export_parquet(io::Cursor::new(first).chain(r), &open_output);
+        }
+        let mut input = first;
+        r.read_to_string(&mut input)?;
+        let mut seq: Vec<u8> = Vec::new();
+        CityJSON::from_str(&input)?.write_cityjsonseq(&mut seq, SortingStrategy::Alphabetical)?;
+        return export_parquet(io::Cursor::new(seq), &open_output);
+    }
+    let mut input = String::new();
+    r.read_to_string(&mut input)?;
+    let cjj = parse_cityjson_or_seq(&input)?;
+    export(&cjj, format, flat, local_origin, opts, open_output()?)
+}
+
+type OpenOutput<'a> = &'a dyn Fn() -> Result<Box<dyn Write + Send>, MyError>;
+
+#[cfg(feature = "arrow")]
+fn export_parquet<R: BufRead>(input: R, open_output: OpenOutput) -> Result<(), MyError> {
+    conv::parquet::to_parquet(input, open_output()?)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "arrow"))]
+fn export_parquet<R: BufRead>(_input: R, _open_output: OpenOutput) -> Result<(), MyError> {
+    Err(MyError::CityJson(
+        "cjseq was compiled without the \"arrow\" feature".to_string(),
+    ))
+}
+
+fn export(
+    cjj: &CityJSON,
+    format: &ExportFormat,
+    flat: bool,
+    local_origin: bool,
+    opts: &conv::Options,
+    w: Box<dyn Write + Send>,
+) -> Result<(), MyError> {
+    let mut out = BufWriter::new(w);
+    print_warnings(&opts.warnings(cjj));
+    match format {
+        ExportFormat::Wkt => {
+            for (id, wkt) in conv::wkt::to_wkt_rows(cjj, flat, opts) {
+                writeln!(out, "{}\t{}", id, wkt)?;
+            }
+        }
+        ExportFormat::Obj => {
+            let origin = if local_origin {
+                Some(cjj.local_origin())
+            } else {
+                None
+            };
+            out.write_all(conv::obj::to_obj(cjj, origin, opts).as_bytes())?;
+        }
+        ExportFormat::Geojson => {
+            writeln!(out, "{}", conv::geojson::to_geojson(cjj, flat, opts))?;
+        }
+        //-- the input is a CityJSONSeq, see convert()
+        ExportFormat::Parquet => unreachable!(),
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn validate_from_stdin(checks: &ValidateChecks) -> Result<(), MyError> {
+    let mut input = String::new();
+    std::io::stdin().read_to_string(&mut input)?;
+    validate(&input, checks)
+}
+
+fn validate_from_file(file: &Path, checks: &ValidateChecks) -> Result<(), MyError> {
+    let mut input = String::new();
+    File::open(file.canonicalize()?)?.read_to_string(&mut input)?;
+    validate(&input, checks)
+}
+
+fn validate(input: &str, checks: &ValidateChecks) -> Result<(), MyError> {
+    let mut errors: Vec<String> = Vec::new();
+    if checks.version_fields {
+        let j = parse_cityjson_or_seq_value(input)?;
+        errors.extend(
+            CityJSON::validate_version_specific_fields(&j)
+                .err()
+                .unwrap_or_default(),
+        );
+    }
+    let cjj = match parse_cityjson_or_seq(input) {
+        Ok(x) => x,
+        //-- eg a numeric LoD, reported with the version-specific errors
+        Err(e) if !errors.is_empty() => {
+            errors.push(e.to_string());
+            return report_validation(errors);
+        }
+        Err(e) => return Err(e),
+    };
+    if checks.schema {
+        errors.extend(validate_schema(&cjj)?);
+    }
+    if checks.extensions {
+        errors.extend(cjj.validate_extensions_declared().err().unwrap_or_default());
+    }
+    if checks.crs {
+        errors.extend(cjj.validate_reference_system().err().unwrap_or_default());
+    }
+    if checks.roundtrip {
+        if let Err(e) = cjj.verify_round_trippable() {
+            errors.push(e);
+        }
+    }
+    if checks.solids {
+        errors.extend(
+            cjj.validate_solids(checks.interior_shells)
+                .err()
+                .unwrap_or_default(),
+        );
+    }
+    if checks.semantics {
+        errors.extend(
+            cjj.validate_semantics_surface_count()
+                .err()
+                .unwrap_or_default(),
+        );
+    }
+    if let Some(s) = checks.scale {
+        let t = Transform {
+            scale: vec![s, s, s],
+            translate: cjj.transform.translate.clone(),
+        };
+        if let Err(e) = cjj.validate_transform_roundtrip(&t) {
+            errors.push(e);
+        }
+    }
+    if checks.attributes {
+        errors.extend(cjj.validate_attribute_schema().err().unwrap_or_default());
+    }
+    if let Some(l) = checks.min_lod {
+        errors.extend(cjj.validate_lod_presence(l).err().unwrap_or_default());
+    }
+    report_validation(errors)
+}
+
+fn report_validation(errors: Vec<String>) -> Result<(), MyError> {
+    if errors.is_empty() {
+        println!("valid");
+        Ok(())
+    } else {
+        for e in &errors {
+            println!("{}", e);
+        }
+        Err(MyError::CityJson(format!(
+            "{} error(s) found",
+            errors.len()
+        )))
+    }
+}
+
+#[cfg(feature = "schema")]
+fn validate_schema(cjj: &CityJSON) -> Result<Vec<String>, MyError> {
+    Ok(cjj.validate_schema().err().unwrap_or_default())
+}
+
+#[cfg(not(feature = "schema"))]
+fn validate_schema(_cjj: &CityJSON) -> Result<Vec<String>, MyError> {
+    Err(MyError::CityJson(
+        "cjseq was compiled without the \"schema\" feature".to_string(),
+    ))
+}
+
+fn extent<R: BufRead>(input: R) -> Result<(), MyError> {
+    match cjseq::cityjson::sequence_extent(input)? {
+        Some(e) => println!("{}", serde_json::to_string(&e)?),
+        None => return Err(MyError::CityJson("no vertices in the input".to_string())),
+    }
+    Ok(())
+}
+
+fn header<R: BufRead>(mut input: R) -> Result<(), MyError> {
+    //-- the 1st line of a CityJSONSeq is output as it is, without reading the features; a
+    //-- CityJSON is read entirely and its get_metadata() is output
+    let mut first = String::new();
+    input.read_line(&mut first)?;
+    if first.trim().is_empty() {
+        return Err(CjseqError::EmptyInput.into());
+    }
+    let cjj = match CityJSON::from_str(&first) {
+        Ok(cjj) if cjj.city_objects.is_empty() => {
+            check_cityjson(&cjj)?;
+            println!("{}", first.trim_end());
+            return Ok(());
+        }
+        Ok(cjj) => cjj,
+        Err(_) => {
+            input.read_to_string(&mut first)?;
+            CityJSON::from_str(&first)?
+        }
+    };
+    check_cityjson(&cjj)?;
+    println!("{}", serde_json::to_string(&cjj.get_metadata())?);
+    Ok(())
+}
+
+fn reorder<R: BufRead>(
+    input: R,
+    order: &FeatureOrder,
+    seed: u64,
+    on_error: OnError,
+) -> Result<(), MyError> {
+    //-- the lines are kept as they are, only the (id, type) of each feature is parsed. the
+    //-- sort is stable: features with the same id stay in the order of the input
+    let mut errors = ErrorPolicy::new(on_error);
+    let mut out = BufWriter::new(io::stdout().lock());
+    let mut features: Vec<((String, String), String)> = Vec::new();
+    let mut header = false;
+    for (i, line) in input.lines().enumerate() {
+        let l = with_line_number(i, line)?;
+        if i == 0 {
+            CityJSON::from_str(&l)?;
+            writeln!(out, "{}", l)?;
+            header = true;
+            continue;
+        }
+        if l.trim().is_empty() {
+            continue;
         }
+        let cjf = match CityJSONFeature::from_str(&l) {
+            Ok(x) => x,
+            Err(e) => {
+                errors.handle_line(i, e.into())?;
+                continue;
+            }
+        };
+        let key = match order {
+            FeatureOrder::Alphabetical | FeatureOrder::Random => (String::new(), cjf.id.clone()),
+            FeatureOrder::Type => match cjf.city_objects.get(&cjf.id) {
+                Some(co) => (co.thetype.clone(), cjf.id.clone()),
+                None => (String::new(), cjf.id.clone()),
+            },
+        };
+        features.push((key, l));
+    }
+    if !header {
+        return Err(CjseqError::EmptyInput.into());
+    }
+    features.sort_by(|a, b| a.0.cmp(&b.0));
+    if let FeatureOrder::Random = order {
+        //-- sorted first, so that the order depends only on the seed
+        features.shuffle(&mut StdRng::seed_from_u64(seed));
+    }
+    for (_, l) in features {
+        writeln!(out, "{}", l)?;
     }
+    out.flush()?;
+    errors.finish()
+}
+
+fn normalize_from_stdin(dry_run: bool) -> Result<(), MyError> {
+    let mut input = String::new();
+    std::io::stdin().read_to_string(&mut input)?;
+    normalize(parse_cityjson_or_seq(&input)?, dry_run)
+}
+
+fn normalize_from_file(file: &Path, dry_run: bool) -> Result<(), MyError> {
+    let mut input = String::new();
+    File::open(file.canonicalize()?)?.read_to_string(&mut input)?;
+    normalize(parse_cityjson_or_seq(&input)?, dry_run)
+}
+
+fn normalize(mut cjj: CityJSON, dry_run: bool) -> Result<(), MyError> {
+    if cjj.thetype != "CityJSON" {
+        return Err(MyError::CityJson("Input file not CityJSON.".to_string()));
+    }
+    let before = cjj.stats();
+    cjj.drop_empty_geometries();
+    cjj.remove_unused_vertices();
+    cjj.canonicalize_vertices();
     cjj.retransform();
-    cjj.remove_duplicate_vertices();
-    io::stdout().write_all(&format!("{}\n", serde_json::to_string(&cjj).unwrap()).as_bytes())?;
+    cjj.prune_appearance();
+    cjj.update_geographical_extent();
+    if dry_run {
+        report_dry_run(&before, &cjj.stats());
+        return Ok(());
+    }
+    //-- the keys are sorted since serde_json::Value uses a BTreeMap
+    io::stdout().write_all(format!("{}\n", serde_json::to_string(&cjj.to_value())?).as_bytes())?;
+    Ok(())
+}
+
+fn info_from_stdin(what: Info) -> Result<(), MyError> {
+    let mut input = String::new();
+    std::io::stdin().read_to_string(&mut input)?;
+    info(&parse_cityjson_or_seq(&input)?, what)
+}
+
+fn info_from_file(file: &Path, what: Info) -> Result<(), MyError> {
+    let mut input = String::new();
+    File::open(file.canonicalize()?)?.read_to_string(&mut input)?;
+    info(&parse_cityjson_or_seq(&input)?, what)
+}
+
+fn info(cjj: &CityJSON, what: Info) -> Result<(), MyError> {
+    match what {
+        Info::Appearance => info_appearance(cjj),
+        Info::PerType => {
+            info_per_type(&cjj.stats().per_type);
+            Ok(())
+        }
+        Info::PerTypeJson => {
+            let j = serde_json::to_value(cjj.stats().per_type)?;
+            println!("{}", serde_json::to_string_pretty(&j)?);
+            Ok(())
+        }
+    }
+}
+
+fn info_per_type(per_type: &BTreeMap<String, TypeStats>) {
+    let mut rows: Vec<[String; 6]> = vec![[
+        "type".to_string(),
+        "count".to_string(),
+        "vertices".to_string(),
+        "avg geometries".to_string(),
+        "LoDs".to_string(),
+        "textured".to_string(),
+    ]];
+    for (t, ts) in per_type {
+        rows.push([
+            t.clone(),
+            ts.count.to_string(),
+            ts.vertices.to_string(),
+            format!("{:.2}", ts.average_geometries()),
+            ts.lods.iter().cloned().collect::<Vec<String>>().join(","),
+            if ts.textured { "yes" } else { "no" }.to_string(),
+        ]);
+    }
+    let widths: Vec<usize> = (0..6)
+        .map(|i| rows.iter().map(|r| r[i].len()).max().unwrap())
+        .collect();
+    for r in rows {
+        let cells: Vec<String> = r
+            .iter()
+            .zip(&widths)
+            .map(|(c, w)| format!("{:<w$}", c, w = *w))
+            .collect();
+        println!("{}", cells.join("  ").trim_end());
+    }
+}
+
+fn info_appearance(cjj: &CityJSON) -> Result<(), MyError> {
+    //-- per type of CityObject, and the materials/textures of the appearance never used
+    let mut types: BTreeMap<String, AppearanceSummary> = BTreeMap::new();
+    for co in cjj.city_objects.values() {
+        let s = co.material_summary(cjj.appearance.as_ref());
+        types.entry(co.thetype.clone()).or_default().extend(s);
+    }
+    let mut used = AppearanceSummary::default();
+    types.values().for_each(|s| used.extend(s.clone()));
+    let unused = |l: Vec<String>, used: &BTreeSet<String>| -> Vec<String> {
+        l.into_iter().filter(|x| !used.contains(x)).collect()
+    };
+    let app = cjj.appearance.as_ref();
+    let j = serde_json::json!({
+        "types": types,
+        "unused_materials": unused(app.map(|a| a.material_names()).unwrap_or_default(), &used.materials),
+        "unused_textures": unused(app.map(|a| a.texture_names()).unwrap_or_default(), &used.textures),
+    });
+    println!("{}", serde_json::to_string_pretty(&j)?);
+    Ok(())
+}
+
+fn hash_from_stdin() -> Result<(), MyError> {
+    let mut input = String::new();
+    std::io::stdin().read_to_string(&mut input)?;
+    println!("{}", parse_cityjson_or_seq(&input)?.content_hash());
+    Ok(())
+}
+
+fn hash_from_file(file: &Path) -> Result<(), MyError> {
+    let mut input = String::new();
+    File::open(file.canonicalize()?)?.read_to_string(&mut input)?;
+    println!("{}", parse_cityjson_or_seq(&input)?.content_hash());
     Ok(())
 }
 
-fn collect_from_file(file: &PathBuf) -> Result<(), MyError> {
+fn parse_cityjson_or_seq(input: &str) -> Result<CityJSON, MyError> {
+    //-- if the first line is a complete JSON object then it's a CityJSONSeq
+    let first = input.lines().next().unwrap_or_default();
+    match CityJSON::from_str(first) {
+        Ok(mut cjj) => {
+            for l in input.lines().skip(1).filter(|l| !l.trim().is_empty()) {
+                let cjf = CityJSONFeature::from_str(l)?;
+                print_warnings(&cjj.add_one_cjf(cjf)?);
+            }
+            Ok(cjj)
+        }
+        Err(_) => Ok(CityJSON::from_str(input)?),
+    }
+}
+
+fn parse_cityjson_or_seq_value(input: &str) -> Result<serde_json::Value, MyError> {
+    //-- as parse_cityjson_or_seq() but only the JSON, the CityObjects of the features are
+    //-- added to those of the 1st line
+    let first = input.lines().next().unwrap_or_default();
+    match serde_json::from_str::<serde_json::Value>(first) {
+        Ok(mut j) if j["type"] == "CityJSON" => {
+            for l in input.lines().skip(1).filter(|l| !l.trim().is_empty()) {
+                let f: serde_json::Value = serde_json::from_str(l)?;
+                if let (Some(cos), Some(fcos)) = (
+                    j["CityObjects"].as_object_mut(),
+                    f["CityObjects"].as_object(),
+                ) {
+                    cos.extend(fcos.clone());
+                }
+            }
+            Ok(j)
+        }
+        _ => Ok(serde_json::from_str(input)?),
+    }
+}
+
+fn append<R: BufRead>(file: &Path, input: R, update_extent: bool) -> Result<(), MyError> {
+    //-- the features are self-contained, only the headers have to be compatible
+    let mut existing = BufReader::new(File::open(file)?).lines();
+    let cj = match existing.next() {
+        Some(l) => CityJSON::from_str(&l?)?,
+        None => return Err(MyError::CityJson(format!("{} is empty", file.display()))),
+    };
+    let mut lines = input.lines();
+    let cj2 = match lines.next() {
+        Some(l) => CityJSON::from_str(&l?)?,
+        None => return Err(CjseqError::EmptyInput.into()),
+    };
+    if !cj.transform.approx_eq(&cj2.transform, TRANSFORM_EPSILON) {
+        return Err(MyError::CityJson(
+            "the transform of the new features differs from that of the file".to_string(),
+        ));
+    }
+    if !cj.crs_compatible(&cj2) {
+        return Err(MyError::CityJson(
+            "the referenceSystem of the new features differs from that of the file".to_string(),
+        ));
+    }
+    let mut newlines: Vec<String> = Vec::new();
+    for line in lines {
+        let l = line?;
+        if l.trim().is_empty() {
+            continue;
+        }
+        let cjf = CityJSONFeature::from_str(&l)?;
+        cjf.check_vertex_indices()?;
+        cjf.check_template_indices(cj.geometry_templates.as_ref())?;
+        newlines.push(l);
+    }
+    if !update_extent {
+        let mut fout = std::fs::OpenOptions::new()
+            .read(true)
+            .append(true)
+            .open(file)?;
+        //-- otherwise the 1st new line would be glued to the last one of the file
+        let mut last = [0u8; 1];
+        fout.seek(SeekFrom::End(-1))?;
+        fout.read_exact(&mut last)?;
+        if last[0] != b'\n' {
+            writeln!(fout)?;
+        }
+        for l in &newlines {
+            writeln!(fout, "{}", l)?;
+        }
+        return Ok(());
+    }
+    //-- the extent is recomputed with all the features and the file is rewritten
+    let mut alllines: Vec<String> = Vec::new();
+    for line in existing {
+        let l = line?;
+        if !l.trim().is_empty() {
+            alllines.push(l);
+        }
+    }
+    alllines.extend(newlines);
+    let mut extent: Option<[f64; 6]> = None;
+    for l in &alllines {
+        let cjf = CityJSONFeature::from_str(l)?;
+        if let Some(b) = cjf.bbox(&cj.transform) {
+            extent = Some(union_extent(extent, b));
+        }
+    }
+    let mut header = cj;
+    if let Some(e) = extent {
+        header.set_geographical_extent(&e);
+    }
+    let tmp = file.with_extension("jsonl.tmp");
+    let mut fout = BufWriter::new(File::create(&tmp)?);
+    writeln!(fout, "{}", serde_json::to_string(&header)?)?;
+    for l in &alllines {
+        writeln!(fout, "{}", l)?;
+    }
+    fout.flush()?;
+    drop(fout);
+    std::fs::rename(&tmp, file)?;
+    Ok(())
+}
+
+fn check_from_stdin() -> Result<(), MyError> {
+    let stdin = std::io::stdin();
+    check(stdin.lock())
+}
+
+fn check_from_file(file: &Path) -> Result<(), MyError> {
     let f = File::open(file.canonicalize()?)?;
-    let br = BufReader::new(f);
+    check(BufReader::new(f))
+}
+
+fn check<R: BufRead>(r: R) -> Result<(), MyError> {
+    //-- only syntax is checked, nothing is deserialised into CityJSON structs
+    let mut n: usize = 0;
+    for (i, line) in r.lines().enumerate() {
+        let l = line?;
+        if let Err(e) = serde_json::from_str::<serde::de::IgnoredAny>(&l) {
+            return Err(MyError::CityJson(format!(
+                "line {} is not valid JSON: {}",
+                i + 1,
+                e
+            )));
+        }
+        n += 1;
+    }
+    println!("{} lines, all valid JSON", n);
+    Ok(())
+}
+
+fn read_cityjson(s: &str, mode: ParseMode) -> Result<CityJSON, MyError> {
+    match mode {
+        ParseMode::Default => Ok(CityJSON::from_str(s)?),
+        ParseMode::Lenient => Ok(CityJSON::from_str_lenient(s)?),
+        ParseMode::Strict => CityJSON::from_str_strict(s).map_err(MyError::CityJson),
+    }
+}
+
+fn read_header(
+    s: &str,
+    mode: ParseMode,
+    duplicate_ids: &Option<DuplicateIds>,
+) -> Result<CityJSON, MyError> {
+    //-- the 1st line of a CityJSONSeq; if it's already a feature then there is no header
+    //-- and a default CityJSON is used (v2.0, no transform)
+    let e = match read_cityjson(s, mode) {
+        Ok(cjj) => return Ok(cjj),
+        Err(e) => e,
+    };
+    match read_cityjsonfeature(s, mode) {
+        Ok(cjf) if cjf.thetype == "CityJSONFeature" => {
+            eprintln!(
+                "Warning: the 1st line is a CityJSONFeature, no metadata/CRS/transform found"
+            );
+            let mut cjj = CityJSON::new();
+            add_cjfeature(&mut cjj, cjf, duplicate_ids)?;
+            Ok(cjj)
+        }
+        _ => Err(e),
+    }
+}
+
+fn array_to_seq(s: &str) -> Result<String, MyError> {
+    //-- collect --array: one line per element, thus a CityJSONSeq if the 1st one is a CityJSON
+    let j: serde_json::Value = serde_json::from_str(s)?;
+    match j {
+        serde_json::Value::Array(a) => Ok(a
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<String>>()
+            .join("\n")),
+        _ => Err(MyError::CityJson(
+            "with --array the input must be a JSON array".to_string(),
+        )),
+    }
+}
+
+fn read_header_file(file: &Path, mode: ParseMode) -> Result<CityJSON, MyError> {
+    //-- collect --header: a CityJSON with only the metadata, as the 1st line of a CityJSONSeq
+    let mut input = String::new();
+    File::open(file.canonicalize()?)?.read_to_string(&mut input)?;
+    let cjj = read_cityjson(&input, mode)
+        .map_err(|e| MyError::CityJson(format!("{}: {}", file.display(), e)))?;
+    check_cityjson(&cjj)?;
+    if !cjj.city_objects.is_empty() || !cjj.vertices.is_empty() {
+        return Err(MyError::CityJson(format!(
+            "{}: the header must have no CityObjects and no vertices ({} and {} found)",
+            file.display(),
+            cjj.city_objects.len(),
+            cjj.vertices.len()
+        )));
+    }
+    Ok(cjj)
+}
+
+fn read_cityjsonfeature(s: &str, mode: ParseMode) -> Result<CityJSONFeature, MyError> {
+    match mode {
+        ParseMode::Default => Ok(CityJSONFeature::from_str(s)?),
+        ParseMode::Lenient => Ok(CityJSONFeature::from_str_lenient(s)?),
+        ParseMode::Strict => CityJSONFeature::from_str_strict(s).map_err(MyError::CityJson),
+    }
+}
+
+fn base_dir(file: &Path) -> Result<PathBuf, MyError> {
+    //-- the paths in the file (eg textures) are relative to its directory
+    let p = file.canonicalize()?;
+    Ok(p.parent().unwrap_or(Path::new(".")).to_path_buf())
+}
+
+fn apply_transforms(
+    cjj: &mut CityJSON,
+    transforms: &TransformArgs,
+    base_dir: &Path,
+) -> Result<(), MyError> {
+    //-- with --dry-run the changes are reported, the caller then writes nothing
+    let before = transforms.dry_run.then(|| cjj.stats());
+    if transforms.inline_textures {
+        cjj.inline_textures(base_dir)?;
+    }
+    if transforms.drop_empty_geometry {
+        cjj.drop_empty_geometries();
+    }
+    if !transforms.drop_attr.is_empty() {
+        cjj.drop_attributes(&transforms.drop_attr);
+    }
+    if !transforms.keep_attr.is_empty() {
+        cjj.keep_attributes(&transforms.keep_attr);
+    }
+    if transforms.strip_extensions {
+        cjj.strip_extensions();
+    }
+    if transforms.drop_appearance {
+        cjj.drop_appearance();
+    }
+    if let Some(t) = &transforms.theme {
+        cjj.select_theme(Some(t.as_str()).filter(|t| !t.is_empty()));
+    }
+    if transforms.expand_templates {
+        cjj.expand_templates().map_err(MyError::CityJson)?;
+    }
+    if transforms.compact_indices {
+        let m = cjj.compact_ids();
+        if let Some(f) = transforms.id_map.as_ref().filter(|_| !transforms.dry_run) {
+            let mut fout = File::create(f)?;
+            fout.write_all(serde_json::to_string(&m)?.as_bytes())?;
+        }
+    }
+    if let Some(before) = before {
+        report_dry_run(&before, &cjj.stats());
+    }
+    Ok(())
+}
+
+fn report_dry_run(before: &Stats, after: &Stats) {
+    //-- only what changes, eg "CityObjects: 12 -> 10 (-2)"
+    let mut n = 0;
+    for ((k, b), (_, a)) in before.rows().iter().zip(after.rows()) {
+        if *b != a {
+            println!("{}: {} -> {} ({:+})", k, b, a, a as i64 - *b as i64);
+            n += 1;
+        }
+    }
+    if n == 0 {
+        println!("nothing would change");
+    }
+}
+
+fn add_cjfeature(
+    cjj: &mut CityJSON,
+    mut cjf: CityJSONFeature,
+    duplicate_ids: &Option<DuplicateIds>,
+) -> Result<(), MyError> {
+    let strategy = match duplicate_ids {
+        Some(DuplicateIds::Error) => DupStrategy::Error,
+        Some(DuplicateIds::SuffixNumeric) => DupStrategy::SuffixNumeric,
+        Some(DuplicateIds::KeepFirst) => DupStrategy::KeepFirst,
+        None => {
+            print_warnings(&cjj.add_one_cjf(cjf)?);
+            return Ok(());
+        }
+    };
+    if cjj
+        .ensure_toplevel_ids_unique(&mut cjf, strategy)
+        .map_err(MyError::CityJson)?
+    {
+        print_warnings(&cjj.add_one_cjf(cjf)?);
+    }
+    Ok(())
+}
+
+fn print_warnings(warnings: &[String]) {
+    for w in warnings {
+        eprintln!("Warning: {}", w);
+    }
+}
+
+fn reserve_from_first_feature(cjj: &mut CityJSON, cjf: &CityJSONFeature, len: usize, size: usize) {
+    //-- the file is assumed to contain features similar to the 1st one; since one vertex takes
+    //-- at least ~10 bytes in the file, no more than size/10 vertices are reserved
+    let n = size / len.max(1);
+    cjj.reserve(
+        n * cjf.city_objects.len(),
+        (n * cjf.vertices.len()).min(size / 10),
+    );
+}
+
+fn dedup_textures(cjj: &mut CityJSON, options: &CollectOptions, base_dir: &Path) {
+    if options.dedup_textures {
+        let n = cjj.dedup_textures(base_dir);
+        if n > 0 {
+            eprintln!("{} duplicate texture(s) merged", n);
+        }
+    }
+}
+
+fn dedup_vertices(
+    cjj: &mut CityJSON,
+    options: &CollectOptions,
+    mut report: CollectReport,
+) -> Result<(), MyError> {
+    //-- report is what was done before (eg retransform())
+    if !options.faithful {
+        if options.prune_vertices {
+            let n = cjj.list_unreferenced_vertices().len();
+            if n > 0 {
+                cjj.remove_unused_vertices();
+                eprintln!("{} unreferenced vertices removed", n);
+            }
+        }
+        let r = match (&options.dedup_scope, options.canonical_vertices) {
+            (DedupScope::Global, false) => cjj.remove_duplicate_vertices(),
+            (DedupScope::Global, true) => {
+                cjj.canonicalize_vertices();
+                CollectReport::new(cjj)
+            }
+            (DedupScope::Object, false) => {
+                cjj.merge_vertices_per_object();
+                CollectReport::new(cjj)
+            }
+            (DedupScope::Object, true) => {
+                return Err(MyError::CityJson(
+                    "--canonical-vertices cannot be used with --dedup-scope object".to_string(),
+                ))
+            }
+        };
+        report = report.then(r);
+    }
+    if options.stats {
+        let r = &report;
+        eprintln!(
+            "vertices: {} -> {} ({:+})",
+            r.vertices_before,
+            r.vertices_after,
+            r.vertices_after as i64 - r.vertices_before as i64
+        );
+        eprintln!(
+            "translate: {:?} -> {:?}",
+            r.translate_before, r.translate_after
+        );
+    }
+    Ok(())
+}
+
+fn collect_from_stdin(
+    mode: ParseMode,
+    options: &CollectOptions,
+    transforms: &TransformArgs,
+    on_error: OnError,
+) -> Result<(), MyError> {
+    let stdin = std::io::stdin();
+    let mut cjj: CityJSON = CityJSON::new();
+    if let Some(h) = &options.header {
+        cjj = read_header_file(h, mode)?;
+    }
+    let mut errors = ErrorPolicy::new(on_error);
+    let mut empty = true;
+    let lines: Box<dyn Iterator<Item = io::Result<String>>> = if options.array {
+        let mut input = String::new();
+        stdin.lock().read_to_string(&mut input)?;
+        Box::new(io::Cursor::new(array_to_seq(&input)?).lines())
+    } else {
+        Box::new(stdin.lock().lines())
+    };
+    for (i, line) in lines.enumerate() {
+        empty = false;
+        if i == 0 && options.header.is_none() {
+            cjj = read_header(&with_line_number(i, line)?, mode, &options.duplicate_ids)?;
+            continue;
+        }
+        let re = with_line_number(i, line)
+            .and_then(|l| read_cityjsonfeature(&l, mode))
+            .and_then(|cjf| add_cjfeature(&mut cjj, cjf, &options.duplicate_ids));
+        if let Err(e) = re {
+            errors.handle_line(i, e)?;
+        }
+    }
+    if empty {
+        return Err(CjseqError::EmptyInput.into());
+    }
+    apply_transforms(&mut cjj, transforms, Path::new("."))?;
+    if transforms.dry_run {
+        return errors.finish();
+    }
+    dedup_textures(&mut cjj, options, Path::new("."));
+    let report = match options.faithful {
+        true => CollectReport::new(&cjj),
+        false => cjj.retransform(),
+    };
+    dedup_vertices(&mut cjj, options, report)?;
+    io::stdout().write_all(format!("{}\n", serde_json::to_string(&cjj).unwrap()).as_bytes())?;
+    errors.finish()
+}
+
+fn collect_from_files(
+    files: &[PathBuf],
+    mode: ParseMode,
+    options: &CollectOptions,
+    transforms: &TransformArgs,
+    on_error: OnError,
+) -> Result<(), MyError> {
+    //-- with several files (eg tiles), the headers after the 1st one must have the same
+    //-- transform and CRS, only their features are added
     let mut cjj: CityJSON = CityJSON::new();
-    for (i, line) in br.lines().enumerate() {
-        match &line {
-            Ok(l) => {
-                if i == 0 {
-                    cjj = serde_json::from_str(&l)?;
+    //-- with --header, the 1st line of each file is a feature
+    let first = match &options.header {
+        Some(h) => {
+            cjj = read_header_file(h, mode)?;
+            0
+        }
+        None => 1,
+    };
+    let mut errors = ErrorPolicy::new(on_error);
+    let mut empty = true;
+    let in_file = |file: &Path, e: MyError| -> MyError {
+        if files.len() == 1 {
+            e
+        } else {
+            MyError::CityJson(format!("{}: {}", file.display(), e))
+        }
+    };
+    for (k, file) in files.iter().enumerate() {
+        let f = File::open(file.canonicalize()?)?;
+        let mut size = f.metadata()?.len() as usize;
+        let br: Box<dyn BufRead> = if options.array {
+            let mut input = String::new();
+            BufReader::new(f).read_to_string(&mut input)?;
+            let seq = array_to_seq(&input).map_err(|e| in_file(file, e))?;
+            size = seq.len();
+            Box::new(io::Cursor::new(seq))
+        } else {
+            Box::new(BufReader::new(f))
+        };
+        for (i, line) in br.lines().enumerate() {
+            empty = false;
+            if i + 1 == first {
+                let l = with_line_number(i, line).map_err(|e| in_file(file, e))?;
+                if k == 0 {
+                    cjj = read_header(&l, mode, &options.duplicate_ids)
+                        .map_err(|e| in_file(file, e))?;
                 } else {
-                    let cjf: CityJSONFeature = serde_json::from_str(&l)?;
-                    cjj.add_one_cjf(cjf);
+                    read_cityjson(&l, mode)
+                        .and_then(|h| check_same_header(&cjj, &h))
+                        .map_err(|e| in_file(file, e))?;
+                }
+                continue;
+            }
+            let re = with_line_number(i, line).and_then(|l| {
+                let cjf = read_cityjsonfeature(&l, mode)?;
+                if i == first && k == 0 {
+                    reserve_from_first_feature(&mut cjj, &cjf, l.len(), size);
                 }
+                add_cjfeature(&mut cjj, cjf, &options.duplicate_ids)
+            });
+            if let Err(e) = re {
+                errors.handle_line(i, in_file(file, e))?;
             }
-            Err(error) => eprintln!("Error reading line: {}", error),
         }
     }
-    cjj.remove_duplicate_vertices();
-    io::stdout().write_all(&format!("{}\n", serde_json::to_string(&cjj).unwrap()).as_bytes())?;
+    if empty {
+        return Err(CjseqError::EmptyInput.into());
+    }
+    let dir = base_dir(&files[0])?;
+    apply_transforms(&mut cjj, transforms, &dir)?;
+    if transforms.dry_run {
+        return errors.finish();
+    }
+    dedup_textures(&mut cjj, options, &dir);
+    let report = CollectReport::new(&cjj);
+    dedup_vertices(&mut cjj, options, report)?;
+    io::stdout().write_all(format!("{}\n", serde_json::to_string(&cjj).unwrap()).as_bytes())?;
+    errors.finish()
+}
+
+fn check_same_header(cjj: &CityJSON, other: &CityJSON) -> Result<(), MyError> {
+    //-- the features of other can then be added to cjj as they are
+    cjj.check_version(other)?;
+    if !other.transform.approx_eq(&cjj.transform, TRANSFORM_EPSILON) {
+        return Err(MyError::CityJson(
+            "the transform differs from that of the 1st file".to_string(),
+        ));
+    }
+    if !cjj.crs_compatible(other) {
+        return Err(MyError::CityJson(
+            "the \"referenceSystem\" differs from that of the 1st file".to_string(),
+        ));
+    }
+    if other.geometry_templates.is_some() {
+        return Err(MyError::CityJson(
+            "only the 1st file can have geometry-templates".to_string(),
+        ));
+    }
     Ok(())
 }
 
-fn cat_from_stdin() -> Result<(), MyError> {
+fn cat_from_stdin(
+    mode: ParseMode,
+    lines: CatLines,
+    grid: Option<Grid>,
+    output: &OutputFiles,
+    transforms: &TransformArgs,
+    on_error: OnError,
+) -> Result<(), MyError> {
     let mut input = String::new();
-    match std::io::stdin().read_to_string(&mut input) {
-        Ok(_) => {
-            let cjj: CityJSON = serde_json::from_str(&input)?;
-            let _ = cat(&cjj)?;
-        }
-        Err(error) => {
-            eprintln!("Error: {}", error);
-        }
+    std::io::stdin().read_to_string(&mut input)?;
+    if input.trim().is_empty() {
+        return Err(CjseqError::EmptyInput.into());
+    }
+    let mut cjj = read_cityjson(&input, mode)?;
+    apply_transforms(&mut cjj, transforms, Path::new("."))?;
+    if transforms.dry_run {
+        return Ok(());
+    }
+    match grid {
+        Some(g) => cat_grid(&cjj, g, output, on_error)?,
+        None => cat(&cjj, lines, on_error)?,
     }
     Ok(())
 }
 
-fn cat_from_file(file: &PathBuf) -> Result<(), MyError> {
-    let f = File::open(file.canonicalize()?)?;
-    let br = BufReader::new(f);
-    let cjj: CityJSON = serde_json::from_reader(br)?;
-    cat(&cjj)?;
+fn cat_from_file(
+    file: &Path,
+    mode: ParseMode,
+    lines: CatLines,
+    grid: Option<Grid>,
+    output: &OutputFiles,
+    transforms: &TransformArgs,
+    on_error: OnError,
+) -> Result<(), MyError> {
+    let mut input = String::new();
+    File::open(file.canonicalize()?)?.read_to_string(&mut input)?;
+    if input.trim().is_empty() {
+        return Err(CjseqError::EmptyInput.into());
+    }
+    let mut cjj = read_cityjson(&input, mode)?;
+    apply_transforms(&mut cjj, transforms, &base_dir(file)?)?;
+    if transforms.dry_run {
+        return Ok(());
+    }
+    match grid {
+        Some(g) => cat_grid(&cjj, g, output, on_error)?,
+        None => cat(&cjj, lines, on_error)?,
+    }
     Ok(())
 }
 
-fn cat(cjj: &CityJSON) -> Result<(), MyError> {
-    if cjj.thetype != "CityJSON" {
-        return Err(MyError::CityJsonError(
-            "Input file not CityJSON.".to_string(),
+fn cat_streaming(file: &Path, pretty: bool, transforms: &TransformArgs) -> Result<(), MyError> {
+    if transforms.any() || transforms.dry_run {
+        return Err(MyError::CityJson(
+            "--stream cannot be used with the options modifying the CityJSON".to_string(),
         ));
     }
+    let s = CatStream::new(BufReader::new(File::open(file.canonicalize()?)?))?;
+    check_cityjson(s.header())?;
+    if pretty {
+        eprintln!("Warning: with --pretty-features the output is not a valid CityJSONSeq");
+    }
+    let mut out = BufWriter::new(io::stdout().lock());
+    out.write_all(format!("{}\n", to_json_string(&s.header().get_metadata(), pretty)).as_bytes())?;
+    s.for_each_feature(|cjf| {
+        out.write_all(format!("{}\n", to_json_string(&cjf, pretty)).as_bytes())
+    })?;
+    out.flush()?;
+    Ok(())
+}
+
+fn to_json_string<T: serde::Serialize>(v: &T, pretty: bool) -> String {
+    if pretty {
+        serde_json::to_string_pretty(v).unwrap()
+    } else {
+        serde_json::to_string(v).unwrap()
+    }
+}
+
+fn check_cityjson(cjj: &CityJSON) -> Result<(), MyError> {
+    if cjj.thetype != "CityJSON" {
+        return Err(MyError::CityJson("Input file not CityJSON.".to_string()));
+    }
     if cjj.version != "1.1" && cjj.version != "2.0" {
-        return Err(MyError::CityJsonError(
+        return Err(MyError::CityJson(
             "Input file not CityJSON v1.1 nor v2.0.".to_string(),
         ));
     }
+    Ok(())
+}
+
+fn cat(cjj: &CityJSON, lines: CatLines, on_error: OnError) -> Result<(), MyError> {
+    check_cityjson(cjj)?;
+    let pretty = lines.pretty_features;
+    let local_templates = lines.local_templates;
+
+    if pretty {
+        eprintln!("Warning: with --pretty-features the output is not a valid CityJSONSeq");
+    }
 
     //-- first line: the CityJSON "metadata"
-    let mut cj1: CityJSON = cjj.get_empty_copy();
-    //-- if geometry-templates have material/textures then these need to be added to 1st line
-    match &cjj.geometry_templates {
-        Some(x) => {
-            let mut gts2: GeometryTemplates = x.clone();
-            let mut m_oldnew: HashMap<usize, usize> = HashMap::new();
-            let mut t_oldnew: HashMap<usize, usize> = HashMap::new();
-            let mut t_v_oldnew: HashMap<usize, usize> = HashMap::new();
-            for g in &mut gts2.templates {
-                g.update_material(&mut m_oldnew);
-                g.update_texture(&mut t_oldnew, &mut t_v_oldnew, 0);
-            }
-            //-- "slice" materials
-            if cjj.appearance.is_some() {
-                let a = cjj.appearance.as_ref().unwrap();
-                let mut acjf: Appearance = Appearance::new();
-                acjf.default_theme_material = a.default_theme_material.clone();
-                acjf.default_theme_texture = a.default_theme_texture.clone();
-                if a.materials.is_some() {
-                    let am = a.materials.as_ref().unwrap();
-                    let mut mats2: Vec<Value> = Vec::new();
-                    mats2.resize(m_oldnew.len(), json!(null));
-                    for (old, new) in &m_oldnew {
-                        mats2[*new] = am[*old].clone();
-                    }
-                    acjf.materials = Some(mats2);
-                }
-                if a.textures.is_some() {
-                    let at = a.textures.as_ref().unwrap();
-                    let mut texs2: Vec<Value> = Vec::new();
-                    texs2.resize(t_oldnew.len(), json!(null));
-                    for (old, new) in &t_oldnew {
-                        texs2[*new] = at[*old].clone();
-                    }
-                    acjf.textures = Some(texs2);
-                }
-                if a.vertices_texture.is_some() {
-                    let atv = a.vertices_texture.as_ref().unwrap();
-                    let mut t_new_vertices: Vec<Vec<f64>> = Vec::new();
-                    t_new_vertices.resize(t_v_oldnew.len(), vec![]);
-                    for (old, new) in &t_v_oldnew {
-                        t_new_vertices[*new] = atv[*old].clone();
-                    }
-                    acjf.vertices_texture = Some(t_new_vertices);
-                }
-                cj1.appearance = Some(acjf);
-            }
-        }
-        None => (),
-    }
-    io::stdout().write_all(&format!("{}\n", serde_json::to_string(&cj1).unwrap()).as_bytes())?;
-
-    //-- the other lines
-    let cos = &cjj.city_objects;
-    for (key, co) in cos {
-        if co.is_toplevel() {
-            let mut cjf = CityJSONFeature::new();
-            let mut co2: CityObject = co.clone();
-            let mut g_vi_oldnew: HashMap<usize, usize> = HashMap::new();
-            let mut m_oldnew: HashMap<usize, usize> = HashMap::new();
-            let mut t_oldnew: HashMap<usize, usize> = HashMap::new();
-            let mut t_v_oldnew: HashMap<usize, usize> = HashMap::new();
-            match &mut co2.geometry {
-                Some(x) => {
-                    for g in x.iter_mut() {
-                        g.update_geometry_boundaries(&mut g_vi_oldnew);
-                        g.update_material(&mut m_oldnew);
-                        g.update_texture(&mut t_oldnew, &mut t_v_oldnew, 0);
-                    }
-                }
-                None => (),
-            }
-            cjf.add_co(key.clone(), co2);
-            cjf.id = key.to_string();
-
-            //-- TODO: to fix: children-of-children?
-            //-- process all the children (only one-level lower)
-            for childkey in co.get_children_keys() {
-                let coc = cos.get(&childkey).unwrap();
-                let mut coc2: CityObject = coc.clone();
-                match &mut coc2.geometry {
-                    Some(x) => {
-                        for g in x.iter_mut() {
-                            g.update_geometry_boundaries(&mut g_vi_oldnew);
-                            g.update_material(&mut m_oldnew);
-                            g.update_texture(&mut t_oldnew, &mut t_v_oldnew, 0);
-                        }
-                    }
-                    None => (),
-                }
-                cjf.add_co(childkey.clone(), coc2);
-            }
-
-            //-- "slice" geometry vertices
-            let allvertices = &cjj.vertices;
-            let mut g_new_vertices: Vec<Vec<i64>> = Vec::new();
-            g_new_vertices.resize(g_vi_oldnew.len(), vec![]);
-            for (old, new) in &g_vi_oldnew {
-                g_new_vertices[*new] = allvertices[*old].clone();
-            }
-            cjf.vertices = g_new_vertices;
-
-            //-- "slice" materials
-            if cjj.appearance.is_some() {
-                let a = cjj.appearance.as_ref().unwrap();
-                let mut acjf: Appearance = Appearance::new();
-                acjf.default_theme_material = a.default_theme_material.clone();
-                acjf.default_theme_texture = a.default_theme_texture.clone();
-                if a.materials.is_some() {
-                    let am = a.materials.as_ref().unwrap();
-                    let mut mats2: Vec<Value> = Vec::new();
-                    mats2.resize(m_oldnew.len(), json!(null));
-                    for (old, new) in &m_oldnew {
-                        mats2[*new] = am[*old].clone();
-                    }
-                    acjf.materials = Some(mats2);
-                }
-                if a.textures.is_some() {
-                    let at = a.textures.as_ref().unwrap();
-                    let mut texs2: Vec<Value> = Vec::new();
-                    texs2.resize(t_oldnew.len(), json!(null));
-                    for (old, new) in &t_oldnew {
-                        texs2[*new] = at[*old].clone();
-                    }
-                    acjf.textures = Some(texs2);
-                }
-                if a.vertices_texture.is_some() {
-                    let atv = a.vertices_texture.as_ref().unwrap();
-                    let mut t_new_vertices: Vec<Vec<f64>> = Vec::new();
-                    t_new_vertices.resize(t_v_oldnew.len(), vec![]);
-                    for (old, new) in &t_v_oldnew {
-                        t_new_vertices[*new] = atv[*old].clone();
-                    }
-                    acjf.vertices_texture = Some(t_new_vertices);
+    let mut cj1: CityJSON = cjj.get_metadata();
+    //-- with local templates the indices of the GeometryInstances are those of the features
+    if local_templates {
+        cj1.geometry_templates = None;
+    }
+    io::stdout().write_all(format!("{}\n", to_json_string(&cj1, pretty)).as_bytes())?;
+
+    //-- the other lines, always in the same order
+    for_each_feature(cjj, on_error, |mut cjf| {
+        if let Some(gts) = cjj.geometry_templates.as_ref().filter(|_| local_templates) {
+            cjf.localize_templates(gts);
+        }
+        io::stdout().write_all(format!("{}\n", to_json_string(&cjf, pretty)).as_bytes())?;
+        Ok(())
+    })
+}
+
+fn cat_grid(
+    cjj: &CityJSON,
+    grid: Grid,
+    output: &OutputFiles,
+    on_error: OnError,
+) -> Result<(), MyError> {
+    //-- a feature is in the cell of its centroid, the files of the empty cells are not created
+    check_cityjson(cjj)?;
+    let b = cjj.bbox().unwrap_or([0.0; 6]);
+    let cell = |v: f64, min: f64, max: f64, n: u32| -> u32 {
+        if max <= min {
+            return 0;
+        }
+        (((v - min) / (max - min) * n as f64).floor().max(0.0) as u32).min(n - 1)
+    };
+    let header = serde_json::to_string(&cjj.get_metadata())?;
+    let mut names: HashSet<PathBuf> = HashSet::new();
+    let mut files: BTreeMap<(u32, u32), (PathBuf, BufWriter<File>, usize)> = BTreeMap::new();
+    for_each_feature(cjj, on_error, |cjf| {
+        let [x, y, _] = cjf.main_centroid(&cjj.transform);
+        let k = (
+            cell(x, b[0], b[3], grid.cols),
+            cell(y, b[1], b[4], grid.rows),
+        );
+        let (_, fout, n) = match files.entry(k) {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => {
+                let fname = output.path(
+                    "out_{col}_{row}.city.jsonl",
+                    &[
+                        ("col", k.0.to_string()),
+                        ("row", k.1.to_string()),
+                        ("index", (k.1 * grid.cols + k.0).to_string()),
+                    ],
+                )?;
+                if names.contains(&fname) {
+                    return Err(MyError::CityJson(format!(
+                        "several cells are written to {}, use {{index}} or {{col}} and {{row}}",
+                        fname.display()
+                    )));
                 }
-                cjf.appearance = Some(acjf);
+                let mut fout = BufWriter::new(File::create(&fname)?);
+                fout.write_all(format!("{}\n", header).as_bytes())?;
+                names.insert(fname.clone());
+                e.insert((fname, fout, 0))
             }
+        };
+        fout.write_all(format!("{}\n", serde_json::to_string(&cjf)?).as_bytes())?;
+        *n += 1;
+        Ok(())
+    })?;
+    for (fname, mut fout, n) in files.into_values() {
+        fout.flush()?;
+        eprintln!("{} ({} features)", fname.display(), n);
+    }
+    Ok(())
+}
 
-            io::stdout()
-                .write_all(&format!("{}\n", serde_json::to_string(&cjf).unwrap()).as_bytes())?;
+fn for_each_feature<F>(cjj: &CityJSON, on_error: OnError, mut f: F) -> Result<(), MyError>
+where
+    F: FnMut(CityJSONFeature) -> Result<(), MyError>,
+{
+    //-- the features of the top-level CityObjects, sorted by id
+    let mut errors = ErrorPolicy::new(on_error);
+    for (key, co) in cjj.iter_city_objects() {
+        if cjj.is_feature_root(co) {
+            let missing = co
+                .get_children_keys()
+                .into_iter()
+                .find(|k| !cjj.city_objects.contains_key(k));
+            if let Some(k) = missing {
+                let e =
+                    MyError::CityJson(format!("child #{} of CityObject #{} is missing", k, key));
+                errors.handle_feature(key, e)?;
+                continue;
+            }
+            let cjf = cjj.get_cjfeature(key).unwrap();
+            if let Err(e) = cjf.check_template_indices(cjj.geometry_templates.as_ref()) {
+                errors.handle_feature(key, e.into())?;
+                continue;
+            }
+            f(cjf)?;
         }
     }
-    Ok(())
+    errors.finish()
 }
@@ -0,0 +1,78 @@
+//-- random access to the lines of a CityJSONSeq file: the file is memory-mapped and the
+//-- offsets of its lines are indexed once, then the nth feature is read without reading the
+//-- lines before it (the OS loads only the pages needed)
+
+use crate::cityjson::{CityJSON, CityJSONFeature};
+use memmap2::Mmap;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+pub struct MappedSeq {
+    mmap: Mmap,
+    //-- the (start, end) of each non-empty line, the 1st one is the CityJSON
+    lines: Vec<(usize, usize)>,
+}
+
+impl MappedSeq {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let f = File::open(path)?;
+        //-- the file must not be modified while it is mapped
+        let mmap = unsafe { Mmap::map(&f)? };
+        let mut lines: Vec<(usize, usize)> = Vec::new();
+        let mut start = 0;
+        for (i, b) in mmap.iter().enumerate() {
+            if *b == b'\n' {
+                lines.push((start, i));
+                start = i + 1;
+            }
+        }
+        lines.push((start, mmap.len()));
+        lines.retain(|(s, e)| mmap[*s..*e].iter().any(|b| !b.is_ascii_whitespace()));
+        Ok(MappedSeq { mmap, lines })
+    }
+    pub fn num_features(&self) -> usize {
+        self.lines.len().saturating_sub(1)
+    }
+    pub fn header_line(&self) -> io::Result<Option<&str>> {
+        self.line(0)
+    }
+    pub fn feature_line(&self, n: usize) -> io::Result<Option<&str>> {
+        //-- n=0 is the 1st feature (the 2nd line)
+        self.line(n + 1)
+    }
+    pub fn header(&self) -> io::Result<Option<CityJSON>> {
+        match self.header_line()? {
+            Some(l) => Ok(Some(CityJSON::from_str(l)?)),
+            None => Ok(None),
+        }
+    }
+    pub fn feature(&self, n: usize) -> io::Result<Option<CityJSONFeature>> {
+        match self.feature_line(n)? {
+            Some(l) => Ok(Some(CityJSONFeature::from_str(l)?)),
+            None => Ok(None),
+        }
+    }
+    fn line(&self, i: usize) -> io::Result<Option<&str>> {
+        let (s, e) = match self.lines.get(i) {
+            Some(x) => *x,
+            None => return Ok(None),
+        };
+        let l = std::str::from_utf8(&self.mmap[s..e])
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid UTF-8"))?;
+        //-- without the BOM of the file and the \r of CRLF
+        Ok(Some(
+            l.trim_start_matches('\u{feff}').trim_end_matches('\r'),
+        ))
+    }
+}
+
+impl CityJSON {
+    pub fn feature_line(path: &Path, n: usize) -> io::Result<Option<String>> {
+        //-- the nth feature of a CityJSONSeq file (n=0 is the 2nd line); the index is built
+        //-- at each call, use MappedSeq to read several features
+        let m = MappedSeq::open(path)?;
+        Ok(m.feature_line(n)?.map(|l| l.to_string()))
+    }
+}
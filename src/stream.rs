@@ -0,0 +1,218 @@
+//-- cat of a (huge) CityJSON file without reading it entirely in memory: the file is
+//-- read twice, the 1st pass keeps everything except the CityObjects (only their
+//-- parents/children are kept), the 2nd pass reads the CityObjects one by one and a
+//-- feature is output as soon as its CityObject and all its children have been read.
+//-- only the vertices (and the appearance) are kept in memory, not the String/Value.
+
+use crate::cityjson::{CityJSON, CityJSONFeature, CityObject};
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, Visitor};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Read, Seek, SeekFrom};
+
+#[derive(Deserialize)]
+struct Links {
+    #[serde(rename = "type")]
+    thetype: String,
+    parents: Option<Vec<String>>,
+    children: Option<Vec<String>>,
+}
+
+pub struct CatStream<R> {
+    reader: R,
+    start: u64,
+    cj: CityJSON,
+    //-- for each CityObject, the features (top-level ids) it is part of
+    features_of: HashMap<String, Vec<String>>,
+    //-- for each feature, the number of its CityObjects not read yet
+    missing: HashMap<String, usize>,
+}
+
+impl<R: Read + Seek> CatStream<R> {
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        //-- 1st pass
+        let start = skip_bom(&mut reader)?;
+        let mut de = serde_json::Deserializer::from_reader(&mut reader);
+        let (mut j, links, vertices) = de.deserialize_map(HeaderVisitor)?;
+        de.end()?;
+        j.insert("CityObjects".to_string(), json!({}));
+        j.insert("vertices".to_string(), json!([]));
+        let mut cj: CityJSON = serde_json::from_value(Value::Object(j))?;
+        cj.vertices = vertices;
+        let mut features_of: HashMap<String, Vec<String>> = HashMap::new();
+        let mut missing: HashMap<String, usize> = HashMap::new();
+        let is_group = |k: &String| links.get(k).is_some_and(|l| l.thetype == "CityObjectGroup");
+        for (id, l) in &links {
+            //-- as CityJSON::is_feature_root(), the members of a group are in their own features
+            if !l.parents.iter().flatten().all(is_group) {
+                continue;
+            }
+            let mut family: Vec<&String> = vec![id];
+            if !is_group(id) {
+                family.extend(l.children.iter().flatten());
+            }
+            family.sort();
+            family.dedup();
+            missing.insert(id.clone(), family.len());
+            for k in family {
+                features_of.entry(k.clone()).or_default().push(id.clone());
+            }
+        }
+        Ok(CatStream {
+            reader,
+            start,
+            cj,
+            features_of,
+            missing,
+        })
+    }
+    pub fn header(&self) -> &CityJSON {
+        //-- the CityJSON without its CityObjects
+        &self.cj
+    }
+    pub fn for_each_feature<F>(mut self, f: F) -> io::Result<()>
+    where
+        F: FnMut(CityJSONFeature) -> io::Result<()>,
+    {
+        //-- 2nd pass, the features are in the order of the file
+        self.reader.seek(SeekFrom::Start(self.start))?;
+        let mut de = serde_json::Deserializer::from_reader(&mut self.reader);
+        let mut state = FeatureState {
+            cj: &mut self.cj,
+            features_of: &self.features_of,
+            missing: &mut self.missing,
+            f,
+        };
+        de.deserialize_map(RootVisitor(&mut state))?;
+        if let Some(id) = self.missing.iter().find(|(_, n)| **n > 0).map(|(k, _)| k) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("a child of CityObject #{} is missing", id),
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn skip_bom<R: Read + Seek>(r: &mut R) -> io::Result<u64> {
+    let mut b = [0u8; 3];
+    let n = r.read(&mut b)?;
+    if n == 3 && b == [0xEF, 0xBB, 0xBF] {
+        return Ok(3);
+    }
+    r.seek(SeekFrom::Start(0))?;
+    Ok(0)
+}
+
+type Header = (Map<String, Value>, HashMap<String, Links>, Vec<Vec<i64>>);
+
+struct HeaderVisitor;
+
+impl<'de> Visitor<'de> for HeaderVisitor {
+    type Value = Header;
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a CityJSON object")
+    }
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Header, A::Error> {
+        let mut j: Map<String, Value> = Map::new();
+        let mut links: HashMap<String, Links> = HashMap::new();
+        let mut vertices: Vec<Vec<i64>> = Vec::new();
+        while let Some(k) = map.next_key::<String>()? {
+            match k.as_str() {
+                "CityObjects" => links = map.next_value()?,
+                "vertices" => vertices = map.next_value()?,
+                _ => {
+                    j.insert(k, map.next_value()?);
+                }
+            }
+        }
+        Ok((j, links, vertices))
+    }
+}
+
+struct FeatureState<'a, F> {
+    cj: &'a mut CityJSON,
+    features_of: &'a HashMap<String, Vec<String>>,
+    missing: &'a mut HashMap<String, usize>,
+    f: F,
+}
+
+impl<F: FnMut(CityJSONFeature) -> io::Result<()>> FeatureState<'_, F> {
+    fn add(&mut self, id: String, co: CityObject) -> io::Result<()> {
+        //-- the CityObjects not part of a feature are never output, as with cat
+        let fs = match self.features_of.get(&id) {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+        self.cj.city_objects.insert(id, co);
+        for fid in fs {
+            let n = self.missing.get_mut(fid).unwrap();
+            if *n == 0 {
+                //-- a duplicate key in the file, the feature is already output
+                continue;
+            }
+            *n -= 1;
+            if *n > 0 {
+                continue;
+            }
+            (self.f)(self.cj.get_cjfeature(fid).unwrap())?;
+            //-- the CityObjects not needed by another feature are released
+            let mut done: Vec<&String> = vec![fid];
+            done.extend(self.cj.city_objects[fid].children.iter().flatten());
+            let done: Vec<String> = done
+                .into_iter()
+                .filter(|k| self.features_of[*k].iter().all(|x| self.missing[x] == 0))
+                .cloned()
+                .collect();
+            for k in done {
+                self.cj.city_objects.remove(&k);
+            }
+        }
+        Ok(())
+    }
+}
+
+struct RootVisitor<'a, 'b, F>(&'a mut FeatureState<'b, F>);
+
+impl<'de, F: FnMut(CityJSONFeature) -> io::Result<()>> Visitor<'de> for RootVisitor<'_, '_, F> {
+    type Value = ();
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a CityJSON object")
+    }
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some(k) = map.next_key::<String>()? {
+            if k == "CityObjects" {
+                map.next_value_seed(CityObjectsSeed(&mut *self.0))?;
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct CityObjectsSeed<'a, 'b, F>(&'a mut FeatureState<'b, F>);
+
+impl<'de, F: FnMut(CityJSONFeature) -> io::Result<()>> DeserializeSeed<'de>
+    for CityObjectsSeed<'_, '_, F>
+{
+    type Value = ();
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<(), D::Error> {
+        d.deserialize_map(self)
+    }
+}
+
+impl<'de, F: FnMut(CityJSONFeature) -> io::Result<()>> Visitor<'de> for CityObjectsSeed<'_, '_, F> {
+    type Value = ();
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("the CityObjects")
+    }
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some((id, co)) = map.next_entry::<String, CityObject>()? {
+            self.0.add(id, co).map_err(de::Error::custom)?;
+        }
+        Ok(())
+    }
+}
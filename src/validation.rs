@@ -0,0 +1,777 @@
+use crate::cityjson::{CityJSON, Geometry, GeometryType, Transform};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+impl CityJSON {
+    #[cfg(feature = "schema")]
+    pub fn validate_schema(&self) -> Result<(), Vec<String>> {
+        //-- the schema of the version of the file is used, errors have their JSON pointer
+        let schema_str = match self.version.as_str() {
+            "1.1" => include_str!("../schemas/11/cityjson.min.schema.json"),
+            "2.0" => include_str!("../schemas/20/cityjson.min.schema.json"),
+            _ => {
+                return Err(vec![format!(
+                    "no schema for CityJSON version \"{}\" (only \"1.1\" and \"2.0\")",
+                    self.version
+                )])
+            }
+        };
+        let schema: serde_json::Value = serde_json::from_str(schema_str).unwrap();
+        let compiled = jsonschema::JSONSchema::options()
+            .with_draft(jsonschema::Draft::Draft7)
+            .compile(&schema)
+            .expect("A valid schema");
+        let j = self.to_value();
+        let mut ls_errors: Vec<String> = Vec::new();
+        if let Err(errors) = compiled.validate(&j) {
+            for error in errors {
+                ls_errors.push(format!("{} [path:{}]", error, error.instance_path));
+            }
+        }
+        if ls_errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ls_errors)
+        }
+    }
+
+    pub fn validate_extensions_declared(&self) -> Result<(), Vec<String>> {
+        //-- each "+Foo" used (CityObject type, attribute, semantic surface, root property)
+        //-- must start with the name of one of the Extensions declared in "extensions"
+        //-- (case-insensitive, since attributes are usually in lowercase)
+        let declared: Vec<&String> = match &self.extensions {
+            Some(Value::Object(m)) => m.keys().collect(),
+            _ => vec![],
+        };
+        let is_declared = |s: &str| {
+            declared
+                .iter()
+                .any(|e| s[1..].to_lowercase().starts_with(&e.to_lowercase()))
+        };
+        let mut ls_errors: Vec<String> = Vec::new();
+        let mut check = |s: &str, location: String| {
+            if s.starts_with('+') && !is_declared(s) {
+                ls_errors.push(format!(
+                    "\"{}\" is not a declared Extension [{}]",
+                    s, location
+                ));
+            }
+        };
+        if let Value::Object(m) = self.to_value() {
+            for k in m.keys() {
+                check(k, "root property".to_string());
+            }
+        }
+        let mut ids: Vec<&String> = self.city_objects.keys().collect();
+        ids.sort();
+        for id in ids {
+            let co = &self.city_objects[id];
+            check(&co.thetype, format!("CityObject #{}", id));
+            if let Some(Value::Object(m)) = &co.attributes {
+                for k in m.keys() {
+                    check(k, format!("attribute of CityObject #{}", id));
+                }
+            }
+            for g in co.geometry.iter().flatten() {
+                let surfaces = g
+                    .semantics
+                    .as_ref()
+                    .and_then(|s| s.get("surfaces"))
+                    .and_then(|s| s.as_array());
+                for sf in surfaces.into_iter().flatten() {
+                    if let Some(t) = sf.get("type").and_then(|t| t.as_str()) {
+                        check(t, format!("semantic surface of CityObject #{}", id));
+                    }
+                }
+            }
+        }
+        if ls_errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ls_errors)
+        }
+    }
+
+    pub fn validate_solids(&self, check_interior_shells: bool) -> Result<(), Vec<String>> {
+        //-- each Solid (also those of MultiSolid/CompositeSolid) must have at least 1 shell, and
+        //-- each shell at least 4 surfaces; with check_interior_shells the bbox of each interior
+        //-- shell must be inside that of the exterior shell
+        let mut ls_errors: Vec<String> = Vec::new();
+        let mut ids: Vec<&String> = self.city_objects.keys().collect();
+        ids.sort();
+        for id in ids {
+            for (gi, g) in self.city_objects[id].geometry.iter().flatten().enumerate() {
+                let solids: Vec<(String, &Value)> = match g.thetype {
+                    GeometryType::Solid => vec![(String::new(), &g.boundaries)],
+                    GeometryType::MultiSolid | GeometryType::CompositeSolid => g
+                        .boundaries
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .enumerate()
+                        .map(|(i, x)| (format!(", solid #{}", i), x))
+                        .collect(),
+                    _ => continue,
+                };
+                for (where_, solid) in solids {
+                    let at = format!("CityObject #{}, geometry #{}{}", id, gi, where_);
+                    let shells: &[Value] = solid.as_array().map_or(&[], |x| x);
+                    if shells.is_empty() {
+                        ls_errors.push(format!("{}: the Solid has no shell", at));
+                        continue;
+                    }
+                    for (si, shell) in shells.iter().enumerate() {
+                        let n = shell.as_array().map_or(0, |x| x.len());
+                        if n < 4 {
+                            ls_errors.push(format!(
+                                "{}, shell #{}: {} surface(s), at least 4 are needed",
+                                at, si, n
+                            ));
+                        }
+                    }
+                    if check_interior_shells {
+                        let exterior = self.shell_bbox(&shells[0]);
+                        for (si, shell) in shells.iter().enumerate().skip(1) {
+                            let inside = match (&exterior, self.shell_bbox(shell)) {
+                                (Some(e), Some(b)) => {
+                                    (0..3).all(|i| b[i] >= e[i] && b[i + 3] <= e[i + 3])
+                                }
+                                _ => true,
+                            };
+                            if !inside {
+                                ls_errors.push(format!(
+                                    "{}, shell #{}: the interior shell is not inside the exterior shell",
+                                    at, si
+                                ));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        if ls_errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ls_errors)
+        }
+    }
+
+    pub fn validate_semantics_surface_count(&self) -> Result<(), Vec<String>> {
+        //-- the "values" of the semantics must have one entry per surface of the geometry, and
+        //-- each must be null or an index in the "surfaces" of the semantics
+        let mut ls_errors: Vec<String> = Vec::new();
+        let mut ids: Vec<&String> = self.city_objects.keys().collect();
+        ids.sort();
+        for id in ids {
+            for (gi, g) in self.city_objects[id].geometry.iter().flatten().enumerate() {
+                let sem = match (&g.thetype, &g.semantics) {
+                    (GeometryType::GeometryInstance, _) | (_, None) => continue,
+                    (_, Some(x)) => x,
+                };
+                let at = format!("CityObject #{}, geometry #{}", id, gi);
+                let nsurfaces = sem["surfaces"].as_array().map_or(0, |x| x.len());
+                let mut values: Vec<&Value> = Vec::new();
+                let mut stack = vec![&sem["values"]];
+                while let Some(v) = stack.pop() {
+                    match v {
+                        Value::Array(a) => stack.extend(a.iter().rev()),
+                        x => values.push(x),
+                    }
+                }
+                let n = match g.thetype {
+                    GeometryType::MultiPoint | GeometryType::MultiLineString => {
+                        g.boundaries.as_array().map_or(0, |x| x.len())
+                    }
+                    _ => g.get_surfaces().len(),
+                };
+                if !sem["values"].is_null() && values.len() != n {
+                    ls_errors.push(format!(
+                        "{}: {} semantic value(s) for {} surface(s)",
+                        at,
+                        values.len(),
+                        n
+                    ));
+                }
+                for (i, v) in values.iter().enumerate() {
+                    if v.is_null() || v.as_u64().is_some_and(|x| (x as usize) < nsurfaces) {
+                        continue;
+                    }
+                    ls_errors.push(format!(
+                        "{}, surface #{}: semantic value {} is not a surface of the semantics ({} surfaces)",
+                        at, i, v, nsurfaces
+                    ));
+                }
+            }
+        }
+        if ls_errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ls_errors)
+        }
+    }
+
+    fn shell_bbox(&self, shell: &Value) -> Option<[i64; 6]> {
+        //-- in the integer coordinates of the file, None if a vertex doesn't exist
+        let mut re: Option<[i64; 6]> = None;
+        let mut stack = vec![shell];
+        while let Some(v) = stack.pop() {
+            match v {
+                Value::Array(a) => stack.extend(a.iter()),
+                Value::Number(n) => {
+                    let p = self.vertices.get(n.as_u64()? as usize)?;
+                    let b = re.get_or_insert([p[0], p[1], p[2], p[0], p[1], p[2]]);
+                    for i in 0..3 {
+                        b[i] = b[i].min(p[i]);
+                        b[i + 3] = b[i + 3].max(p[i]);
+                    }
+                }
+                _ => (),
+            }
+        }
+        re
+    }
+
+    pub fn validate_lod_presence(&self, min_lod: f64) -> Result<(), Vec<String>> {
+        //-- each CityObject (except the groups) must have a geometry with a LoD >= min_lod, or
+        //-- one of its children (eg the BuildingParts of a Building); the LoD of a
+        //-- GeometryInstance is that of its template
+        let lod = |g: &Geometry| -> Option<f64> {
+            match (&g.thetype, g.template, &self.geometry_templates) {
+                (GeometryType::GeometryInstance, Some(t), Some(gts)) => {
+                    gts.templates.get(t).and_then(|x| x.lod_f64())
+                }
+                _ => g.lod_f64(),
+            }
+        };
+        let has_lod = |id: &String| {
+            self.city_objects.get(id).is_some_and(|co| {
+                co.geometry
+                    .iter()
+                    .flatten()
+                    .any(|g| lod(g).is_some_and(|l| l >= min_lod))
+            })
+        };
+        let mut re: Vec<String> = Vec::new();
+        for (id, co) in self.iter_city_objects() {
+            if co.is_group() || has_lod(id) || co.children.iter().flatten().any(has_lod) {
+                continue;
+            }
+            re.push(format!(
+                "CityObject #{} ({}): no geometry with a LoD >= {}",
+                id, co.thetype, min_lod
+            ));
+        }
+        if re.is_empty() {
+            Ok(())
+        } else {
+            Err(re)
+        }
+    }
+
+    pub fn validate_attribute_schema(&self) -> Result<(), Vec<String>> {
+        //-- the CityObjects whose attribute keys differ from those of most of the
+        //-- CityObjects of the same type: a key is expected if more than half of them have it
+        //-- (see attribute_schema())
+        let mut n: HashMap<&str, usize> = HashMap::new();
+        for co in self.city_objects.values() {
+            *n.entry(co.thetype.as_str()).or_default() += 1;
+        }
+        let schema = self.attribute_schema();
+        let mut expected_keys: HashMap<&str, Vec<String>> = HashMap::new();
+        for (t, nt) in &n {
+            let mut ks: Vec<String> = schema
+                .iter()
+                .filter(|(_, a)| a.count_per_type.get(*t).is_some_and(|c| 2 * c > *nt))
+                .map(|(k, _)| k.clone())
+                .collect();
+            ks.sort();
+            expected_keys.insert(t, ks);
+        }
+        let mut re: Vec<String> = Vec::new();
+        for (id, co) in self.iter_city_objects() {
+            let expected = &expected_keys[co.thetype.as_str()];
+            let keys = co.attribute_keys();
+            for k in keys.iter().filter(|k| !expected.contains(k)) {
+                re.push(format!(
+                    "CityObject #{} ({}): unexpected attribute \"{}\"",
+                    id, co.thetype, k
+                ));
+            }
+            for k in expected.iter().filter(|k| !keys.contains(k)) {
+                re.push(format!(
+                    "CityObject #{} ({}): missing attribute \"{}\"",
+                    id, co.thetype, k
+                ));
+            }
+        }
+        if re.is_empty() {
+            Ok(())
+        } else {
+            Err(re)
+        }
+    }
+
+    pub fn validate_version_specific_fields(j: &Value) -> Result<(), Vec<String>> {
+        //-- on the JSON since a CityJSON with such errors cannot always be parsed (eg a
+        //-- numeric LoD); the differences are those of the schemas v1.1 and v2.0
+        let version = j["version"].as_str().unwrap_or_default();
+        let mut re: Vec<String> = Vec::new();
+        let mut check_lods = |gs: &Value, path: String| {
+            for (i, g) in gs.as_array().into_iter().flatten().enumerate() {
+                if g["lod"].is_number() {
+                    re.push(format!(
+                        "the LoD {} is a number, it is a string since v1.1 (eg \"{}\") [path:{}/{}/lod]",
+                        g["lod"], g["lod"], path, i
+                    ));
+                }
+            }
+        };
+        if let Some(cos) = j["CityObjects"].as_object() {
+            let mut ids: Vec<&String> = cos.keys().collect();
+            ids.sort();
+            for id in ids {
+                check_lods(
+                    &cos[id]["geometry"],
+                    format!("/CityObjects/{}/geometry", id),
+                );
+            }
+        }
+        check_lods(
+            &j["geometry-templates"]["templates"],
+            "/geometry-templates/templates".to_string(),
+        );
+        if version == "1.1" {
+            if let Some(cos) = j["CityObjects"].as_object() {
+                let mut ids: Vec<&String> = cos.keys().collect();
+                ids.sort();
+                for id in ids
+                    .into_iter()
+                    .filter(|id| cos[*id]["type"] == "GenericCityObject")
+                {
+                    re.push(format!(
+                        "\"GenericCityObject\" is a type of v2.0, not of v1.1 [path:/CityObjects/{}/type]",
+                        id
+                    ));
+                }
+            }
+        }
+        let address = &j["metadata"]["pointOfContact"]["address"];
+        match version {
+            "1.1" if address.is_object() => re.push(
+                "the address is a string in v1.1 (an object in v2.0) [path:/metadata/pointOfContact/address]"
+                    .to_string(),
+            ),
+            "2.0" if address.is_string() => re.push(
+                "the address is an object in v2.0 (a string in v1.1) [path:/metadata/pointOfContact/address]"
+                    .to_string(),
+            ),
+            _ => (),
+        }
+        if re.is_empty() {
+            Ok(())
+        } else {
+            Err(re)
+        }
+    }
+
+    pub fn validate_reference_system(&self) -> Result<(), Vec<String>> {
+        //-- a missing "referenceSystem" is valid (it is optional)
+        match self.reference_system() {
+            Some(Err(e)) => Err(vec![format!("{} [path:/metadata/referenceSystem]", e)]),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn validate_transform_roundtrip(&self, transform: &Transform) -> Result<(), String> {
+        //-- the vertices that would be merged with another one if the CityJSON was
+        //-- quantized with that transform (eg a coarser scale), thus collapsing geometries
+        let mut distinct: HashSet<&Vec<i64>> = HashSet::new();
+        let mut quantized: HashSet<Vec<i64>> = HashSet::new();
+        for v in &self.vertices {
+            if distinct.insert(v) {
+                quantized.insert(transform.quantize(&self.transform.real_coordinates(v)));
+            }
+        }
+        let n = distinct.len() - quantized.len();
+        if n > 0 {
+            return Err(format!(
+                "{} vertices collapse with the scale {:?}",
+                n, transform.scale
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn verify_round_trippable(&self) -> Result<(), String> {
+        //-- cat then collect, the result must be the same as self: same CityObjects with the
+        //-- same geometries (real coordinates), textures and materials
+        let mut cj = self.get_metadata();
+        let mut keys: Vec<&String> = self
+            .city_objects
+            .iter()
+            .filter(|(_, co)| self.is_feature_root(co))
+            .map(|(k, _)| k)
+            .collect();
+        keys.sort();
+        for k in keys {
+            cj.add_one_cjf(self.get_cjfeature(k).unwrap())
+                .map_err(|e| e.to_string())?;
+        }
+        cj.remove_duplicate_vertices();
+        let c1 = canonical(self);
+        let c2 = canonical(&cj);
+        for (id, co) in &c1 {
+            match c2.get(id) {
+                None => {
+                    return Err(format!(
+                        "CityObject #{} is missing after the round-trip",
+                        id
+                    ))
+                }
+                Some(x) if x != co => {
+                    return Err(format!("CityObject #{} differs after the round-trip", id))
+                }
+                _ => (),
+            }
+        }
+        if let Some(id) = c2.keys().find(|k| !c1.contains_key(*k)) {
+            return Err(format!("CityObject #{} is added by the round-trip", id));
+        }
+        let mut dedup = self.clone();
+        dedup.remove_duplicate_vertices();
+        if dedup.vertices.len() != cj.vertices.len() {
+            return Err(format!(
+                "{} vertices after the round-trip instead of {}",
+                cj.vertices.len(),
+                dedup.vertices.len()
+            ));
+        }
+        let count = |c: &CityJSON| match &c.appearance {
+            Some(a) => (
+                a.materials.as_ref().map_or(0, |x| x.len()),
+                a.textures.as_ref().map_or(0, |x| x.len()),
+            ),
+            None => (0, 0),
+        };
+        if count(self) != count(&cj) {
+            return Err(format!(
+                "(materials, textures) are {:?} after the round-trip instead of {:?}",
+                count(&cj),
+                count(self)
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn validate_and_repair(&self) -> (CityJSON, Vec<String>) {
+        //-- a copy with the problems that can be fixed safely fixed, and what was done:
+        //-- the values of the materials clamped to [0, 1], the geometries with a vertex (or
+        //-- template) that doesn't exist removed, and the children/parents that don't exist
+        //-- removed
+        let mut cj = self.clone();
+        let mut actions: Vec<String> = Vec::new();
+        if let Some(ms) = cj.appearance.as_mut().and_then(|a| a.materials.as_mut()) {
+            for (i, m) in ms.iter_mut().enumerate() {
+                for k in MATERIAL_UNIT_VALUES {
+                    let clamped = match m.get_mut(k) {
+                        Some(Value::Array(a)) => a.iter_mut().map(clamp).filter(|c| *c).count() > 0,
+                        Some(x) => clamp(x),
+                        None => false,
+                    };
+                    if clamped {
+                        actions.push(format!("material #{}: \"{}\" clamped to [0, 1]", i, k));
+                    }
+                }
+            }
+        }
+        let nv = cj.vertices.len();
+        let nt = cj
+            .geometry_templates
+            .as_ref()
+            .map_or(0, |x| x.templates.len());
+        let ids: Vec<String> = cj.sorted_ids().into_iter().cloned().collect();
+        for id in &ids {
+            let co = cj.city_objects.get_mut(id).unwrap();
+            if let Some(gs) = co.geometry.as_mut() {
+                let mut gi = 0;
+                gs.retain(|g| {
+                    gi += 1;
+                    let ok = g.vertex_indices().iter().all(|i| *i < nv)
+                        && g.template.is_none_or(|t| t < nt);
+                    if !ok {
+                        actions.push(format!(
+                            "CityObject #{}: geometry #{} removed (index out of range)",
+                            id,
+                            gi - 1
+                        ));
+                    }
+                    ok
+                });
+            }
+        }
+        let existing: HashSet<String> = cj.city_objects.keys().cloned().collect();
+        for id in &ids {
+            let co = cj.city_objects.get_mut(id).unwrap();
+            for (what, l) in [("child", &mut co.children), ("parent", &mut co.parents)] {
+                if let Some(l) = l {
+                    for k in l.iter().filter(|k| !existing.contains(*k)) {
+                        actions.push(format!(
+                            "CityObject #{}: {} #{} removed (it doesn't exist)",
+                            id, what, k
+                        ));
+                    }
+                    l.retain(|k| existing.contains(k));
+                }
+            }
+        }
+        (cj, actions)
+    }
+}
+
+impl Geometry {
+    pub fn snap_shells(
+        &mut self,
+        vertices: &[Vec<i64>],
+        transform: &Transform,
+        tolerance: f64,
+    ) -> usize {
+        //-- in each shell of a Solid/MultiSolid/CompositeSolid, the vertices of the open
+        //-- edges (used by only 1 surface) closer than tolerance (in the units of the CRS) are
+        //-- welded to the one with the smallest index, and the repeated consecutive vertices
+        //-- of the rings are removed. the textured geometries are not modified (one uv per
+        //-- vertex of the rings); the vertices are not modified. returns the number of
+        //-- vertices replaced in the boundaries
+        if self.texture.is_some() {
+            return 0;
+        }
+        let mut solids: Vec<Vec<Vec<Vec<Vec<usize>>>>> = match self.thetype {
+            GeometryType::Solid => match self.boundaries_as() {
+                Ok(x) => vec![x],
+                Err(_) => return 0,
+            },
+            GeometryType::MultiSolid | GeometryType::CompositeSolid => match self.boundaries_as() {
+                Ok(x) => x,
+                Err(_) => return 0,
+            },
+            _ => return 0,
+        };
+        let t2 = tolerance * tolerance;
+        let p = |i: usize| vertices.get(i).map(|v| transform.real_coordinates(v));
+        let mut re = 0;
+        for shell in solids.iter_mut().flatten() {
+            let mut count: HashMap<(usize, usize), usize> = HashMap::new();
+            for ring in shell.iter().flatten() {
+                for (k, a) in ring.iter().enumerate() {
+                    let b = ring[(k + 1) % ring.len()];
+                    *count.entry(((*a).min(b), (*a).max(b))).or_default() += 1;
+                }
+            }
+            let mut open: Vec<usize> = count
+                .iter()
+                .filter(|(_, n)| **n == 1)
+                .flat_map(|((a, b), _)| [*a, *b])
+                .collect();
+            open.sort_unstable();
+            open.dedup();
+            //-- each open vertex to the 1st (smallest) one within tolerance, chains included
+            let mut snap: HashMap<usize, usize> = HashMap::new();
+            for (k, a) in open.iter().enumerate() {
+                let pa = match p(*a) {
+                    Some(x) => x,
+                    None => continue,
+                };
+                for b in &open[..k] {
+                    let close = p(*b).is_some_and(|pb| {
+                        (0..3).map(|i| (pa[i] - pb[i]).powi(2)).sum::<f64>() <= t2
+                    });
+                    if close {
+                        snap.insert(*a, *snap.get(b).unwrap_or(b));
+                        break;
+                    }
+                }
+            }
+            if snap.is_empty() {
+                continue;
+            }
+            for ring in shell.iter_mut().flatten() {
+                for i in ring.iter_mut() {
+                    if let Some(j) = snap.get(i) {
+                        *i = *j;
+                        re += 1;
+                    }
+                }
+                ring.dedup();
+                while ring.len() > 1 && ring.first() == ring.last() {
+                    ring.pop();
+                }
+            }
+        }
+        if re > 0 {
+            self.boundaries = match self.thetype {
+                GeometryType::Solid => json!(solids[0]),
+                _ => json!(solids),
+            };
+        }
+        re
+    }
+    pub fn check_manifold(&self) -> Result<(), Vec<String>> {
+        //-- each edge of the exterior shell of a Solid must be used by exactly 2 surfaces,
+        //-- once in each direction (thus a consistent orientation); the edges of the inner
+        //-- rings are counted as the others
+        if self.thetype != GeometryType::Solid {
+            return Err(vec![format!("a {:?} is not a Solid", self.thetype)]);
+        }
+        let shell: Vec<Vec<Vec<usize>>> = match self.boundaries_as::<Vec<Vec<Vec<Vec<usize>>>>>() {
+            Ok(mut x) if !x.is_empty() => x.swap_remove(0),
+            Ok(_) => return Err(vec!["the Solid has no shell".to_string()]),
+            Err(e) => return Err(vec![e]),
+        };
+        //-- for each edge (smallest vertex first): the surfaces with a->b, and with b->a
+        let mut edges: BTreeMap<(usize, usize), (Vec<usize>, Vec<usize>)> = BTreeMap::new();
+        for (si, surface) in shell.iter().enumerate() {
+            for ring in surface {
+                for (k, a) in ring.iter().enumerate() {
+                    let b = ring[(k + 1) % ring.len()];
+                    if *a == b {
+                        continue;
+                    }
+                    let e = edges.entry((b.min(*a), b.max(*a))).or_default();
+                    if *a < b {
+                        e.0.push(si);
+                    } else {
+                        e.1.push(si);
+                    }
+                }
+            }
+        }
+        let mut ls_errors: Vec<String> = Vec::new();
+        for ((a, b), (forward, backward)) in &edges {
+            let n = forward.len() + backward.len();
+            if n != 2 {
+                ls_errors.push(format!(
+                    "edge ({}, {}): used by {} surface(s), not 2",
+                    a, b, n
+                ));
+            } else if forward.len() != 1 {
+                let sfs = if forward.is_empty() {
+                    backward
+                } else {
+                    forward
+                };
+                ls_errors.push(format!(
+                    "edge ({}, {}): same orientation in surfaces #{} and #{}",
+                    a, b, sfs[0], sfs[1]
+                ));
+            }
+        }
+        if ls_errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ls_errors)
+        }
+    }
+}
+
+//-- the members of a material whose values are in [0, 1]
+const MATERIAL_UNIT_VALUES: [&str; 6] = [
+    "ambientIntensity",
+    "diffuseColor",
+    "emissiveColor",
+    "specularColor",
+    "shininess",
+    "transparency",
+];
+
+fn clamp(v: &mut Value) -> bool {
+    //-- true if the number was modified
+    match v.as_f64() {
+        Some(x) if !(0.0..=1.0).contains(&x) => {
+            *v = json!(x.clamp(0.0, 1.0));
+            true
+        }
+        _ => false,
+    }
+}
+
+fn canonical(cj: &CityJSON) -> BTreeMap<String, Value> {
+    //-- each CityObject with its indices replaced by what they reference
+    let mut re: BTreeMap<String, Value> = BTreeMap::new();
+    for (id, co) in &cj.city_objects {
+        let mut j = serde_json::to_value(co).unwrap();
+        if let Some(gs) = j.get_mut("geometry").and_then(|g| g.as_array_mut()) {
+            for g in gs {
+                g["boundaries"] = resolve_boundaries(cj, &g["boundaries"]);
+                if let Some(Value::Object(ts)) = g.get_mut("texture") {
+                    for t in ts.values_mut() {
+                        t["values"] = resolve_texture(cj, &t["values"]);
+                    }
+                }
+                if let Some(Value::Object(ms)) = g.get_mut("material") {
+                    for m in ms.values_mut() {
+                        for k in ["values", "value"] {
+                            if let Some(x) = m.get(k) {
+                                m[k] = resolve_material(cj, x);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        re.insert(id.clone(), j);
+    }
+    re
+}
+
+fn resolve_boundaries(cj: &CityJSON, b: &Value) -> Value {
+    //-- rounded to the precision of the file, to compare integers and not floats
+    let f = 10f64.powi(cj.transform.decimals() as i32);
+    match b {
+        Value::Array(a) => Value::Array(a.iter().map(|x| resolve_boundaries(cj, x)).collect()),
+        Value::Number(n) => match cj.vertices.get(n.as_u64().unwrap_or(u64::MAX) as usize) {
+            Some(v) => {
+                let p = cj.transform.real_coordinates(v);
+                json!(p
+                    .iter()
+                    .map(|c| (c * f).round() as i64)
+                    .collect::<Vec<i64>>())
+            }
+            None => Value::Null,
+        },
+        _ => b.clone(),
+    }
+}
+
+fn resolve_texture(cj: &CityJSON, v: &Value) -> Value {
+    //-- each ring [texture, uv, uv, ...] becomes [texture object, [u, v], [u, v], ...]
+    let a = cj.appearance.as_ref();
+    let ts = a.and_then(|a| a.textures.as_ref());
+    let uvs = a.and_then(|a| a.vertices_texture.as_ref());
+    match v {
+        Value::Array(x) if x.iter().all(|e| !e.is_array()) => {
+            let mut re: Vec<Value> = Vec::new();
+            for (i, e) in x.iter().enumerate() {
+                let j = e.as_u64().map(|j| j as usize);
+                re.push(match j {
+                    Some(j) if i == 0 => ts.and_then(|t| t.get(j)).cloned().unwrap_or_default(),
+                    Some(j) => json!(uvs.and_then(|t| t.get(j))),
+                    None => Value::Null,
+                });
+            }
+            Value::Array(re)
+        }
+        Value::Array(x) => Value::Array(x.iter().map(|e| resolve_texture(cj, e)).collect()),
+        _ => v.clone(),
+    }
+}
+
+fn resolve_material(cj: &CityJSON, v: &Value) -> Value {
+    let mats = cj.appearance.as_ref().and_then(|a| a.materials.as_ref());
+    match v {
+        Value::Array(x) => Value::Array(x.iter().map(|e| resolve_material(cj, e)).collect()),
+        Value::Number(n) => mats
+            .and_then(|m| m.get(n.as_u64().unwrap_or(u64::MAX) as usize))
+            .cloned()
+            .unwrap_or_default(),
+        _ => v.clone(),
+    }
+}
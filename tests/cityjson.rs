@@ -0,0 +1,2098 @@
+//-- tests of the operations of cjseq::cityjson on small documents
+
+use cjseq::cityjson::{
+    Address, Appearance, AttributeType, CityJSON, CityJSONFeature, CityObject, CjseqError, CrsKind,
+    DupStrategy, Geometry, GeometryType, PointOfContactBuilder, ReferenceSystem, SortingStrategy,
+    Transform, TypeLevel,
+};
+use cjseq::stream::CatStream;
+use serde_json::{json, Value};
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+use std::str::FromStr;
+
+fn cube_geometry(lod: &str) -> serde_json::Value {
+    json!({
+        "type": "MultiSurface",
+        "lod": lod,
+        "boundaries": [[[0, 1, 2, 3]], [[4, 5, 6, 7]]]
+    })
+}
+
+fn document(geometries: Vec<serde_json::Value>) -> CityJSON {
+    CityJSON::from_value(json!({
+        "type": "CityJSON",
+        "version": "2.0",
+        "transform": {"scale": [0.001, 0.001, 0.001], "translate": [0.0, 0.0, 0.0]},
+        "CityObjects": {
+            "b": {"type": "Building", "geometry": geometries}
+        },
+        "vertices": [
+            [0, 0, 0], [1000, 0, 0], [1000, 1000, 0], [0, 1000, 0],
+            [0, 0, 1000], [1000, 0, 1000], [1000, 1000, 1000], [0, 1000, 1000]
+        ]
+    }))
+    .unwrap()
+}
+
+#[test]
+fn dedup_geometries_removes_exact_copies() {
+    let mut cj = document(vec![
+        cube_geometry("2"),
+        cube_geometry("2"),
+        cube_geometry("1"),
+    ]);
+    cj.dedup_geometries();
+    let gs = cj.city_objects["b"].geometry.as_ref().unwrap();
+    assert_eq!(gs.len(), 2);
+    assert_eq!(gs[0].lod.as_deref(), Some("2"));
+    assert_eq!(gs[1].lod.as_deref(), Some("1"));
+}
+
+#[test]
+fn dedup_geometries_keeps_different_semantics() {
+    let mut g2 = cube_geometry("2");
+    g2["semantics"] = json!({"surfaces": [{"type": "RoofSurface"}], "values": [0, 0]});
+    let mut cj = document(vec![cube_geometry("2"), g2]);
+    cj.dedup_geometries();
+    assert_eq!(cj.city_objects["b"].geometry.as_ref().unwrap().len(), 2);
+}
+
+#[test]
+fn lenient_synthesizes_a_transform_for_float_vertices() {
+    let s = r#"{"type": "CityJSON", "version": "2.0", "CityObjects": {},
+        "vertices": [[1.5, 2.5, 0.0], [3.25, 2.5, 10.0]]}"#;
+    assert!(CityJSON::from_str(s).is_err());
+    let cj = CityJSON::from_str_lenient(s).unwrap();
+    assert_eq!(cj.transform.scale, vec![0.01, 0.01, 0.01]);
+    assert_eq!(cj.transform.translate, vec![1.5, 2.5, 0.0]);
+    assert_eq!(cj.vertices, vec![vec![0, 0, 0], vec![175, 0, 1000]]);
+    assert_eq!(
+        cj.transform.real_coordinates(&cj.vertices[1]),
+        [3.25, 2.5, 10.0]
+    );
+}
+
+#[test]
+fn simplify_footprints_removes_collinear_vertices() {
+    let footprint = json!({"type": "MultiSurface", "lod": "0", "boundaries": [[[0, 1, 8, 2, 3]]]});
+    let mut cj = document(vec![footprint, cube_geometry("2")]);
+    //-- 1mm away from the edge 1-2
+    cj.vertices.push(vec![1001, 500, 0]);
+    cj.simplify_footprints(0.01);
+    let gs = cj.city_objects["b"].geometry.as_ref().unwrap();
+    assert_eq!(gs[0].boundaries, json!([[[0, 1, 2, 3]]]));
+    assert_eq!(gs[1].boundaries, cube_geometry("2")["boundaries"]);
+    assert_eq!(cj.vertices.len(), 8);
+    //-- a ring is never collapsed below 3 vertices
+    cj.simplify_footprints(100.0);
+    let gs = cj.city_objects["b"].geometry.as_ref().unwrap();
+    assert_eq!(gs[0].boundaries[0][0].as_array().unwrap().len(), 3);
+}
+
+#[test]
+fn stats_per_type_of_a_mixed_document() {
+    let mut cj = document(vec![cube_geometry("1"), cube_geometry("2.2")]);
+    cj.add_co("b2".to_string(), CityObject::new("Building"));
+    let mut road = CityObject::new("Road");
+    road.add_geometry(serde_json::from_value(cube_geometry("1")).unwrap());
+    cj.add_co("r".to_string(), road);
+    let per_type = cj.stats().per_type;
+    assert_eq!(per_type.len(), 2);
+    let b = &per_type["Building"];
+    assert_eq!(b.count, 2);
+    assert_eq!(b.lods.iter().collect::<Vec<_>>(), ["1", "2.2"]);
+    assert_eq!(b.vertices, 16);
+    assert_eq!(b.average_geometries(), 1.0);
+    assert!(!b.textured);
+    assert_eq!(per_type["Road"].count, 1);
+}
+
+#[test]
+fn near_equal_transforms_are_compatible() {
+    let cj = document(vec![cube_geometry("2")]);
+    let mut cj2 = document(vec![cube_geometry("2")]);
+    cj2.transform.translate[0] = 1e-12;
+    assert_ne!(cj.transform, cj2.transform);
+    assert!(cj.transform.approx_eq(&cj2.transform, 1e-9));
+    assert!(cj.crs_compatible(&cj2));
+    cj2.transform.scale = vec![0.01, 0.01, 0.01];
+    assert!(!cj.transform.approx_eq(&cj2.transform, 1e-9));
+    assert!(!cj.crs_compatible(&cj2));
+    //-- the same CRS with 2 URLs
+    let mut cj2 = document(vec![cube_geometry("2")]);
+    let mut cj = cj2.clone();
+    cj.metadata = Some(json!({"referenceSystem": "https://www.opengis.net/def/crs/EPSG/0/7415"}));
+    cj2.metadata = Some(json!({"referenceSystem": "http://www.opengis.net/def/crs/EPSG/0/7415"}));
+    assert!(cj.crs_compatible(&cj2));
+    cj2.metadata = Some(json!({"referenceSystem": "https://www.opengis.net/def/crs/EPSG/0/28992"}));
+    assert!(!cj.crs_compatible(&cj2));
+    cj2.metadata = None;
+    assert!(!cj.crs_compatible(&cj2));
+}
+
+#[test]
+fn from_geojson_extrudes_a_footprint() {
+    //-- a clockwise exterior ring (RFC 7946 wants counter-clockwise), it is reoriented
+    let fc = json!({"type": "FeatureCollection", "features": [
+        {"type": "Feature", "id": "b1", "properties": {"h": 10, "name": "box"},
+         "geometry": {"type": "Polygon",
+                      "coordinates": [[[0, 0], [0, 5], [5, 5], [5, 0], [0, 0]]]}},
+        {"type": "Feature", "properties": {"name": "no height"},
+         "geometry": {"type": "Polygon",
+                      "coordinates": [[[0, 0], [1, 0], [1, 1], [0, 0]]]}}
+    ]});
+    let cj = cjseq::conv::geojson::from_geojson(&fc, "h").unwrap();
+    assert_eq!(cj.city_objects.len(), 1);
+    let co = &cj.city_objects["b1"];
+    assert_eq!(co.attributes, Some(json!({"h": 10, "name": "box"})));
+    assert_eq!(cj.vertices.len(), 8);
+    assert_eq!(cj.bbox(), Some([0.0, 0.0, 0.0, 5.0, 5.0, 10.0]));
+    assert!(cj.validate_solids(false).is_ok());
+    //-- all the normals point outwards
+    let g = &co.geometry.as_ref().unwrap()[0];
+    let surfaces = g.get_surfaces();
+    assert_eq!(surfaces.len(), 6);
+    for s in surfaces {
+        let ps: Vec<[f64; 3]> = s[0]
+            .iter()
+            .map(|i| cj.transform.real_coordinates(&cj.vertices[*i]))
+            .collect();
+        let mut n = [0.0; 3];
+        for (k, a) in ps.iter().enumerate() {
+            let b = ps[(k + 1) % ps.len()];
+            n[0] += (a[1] - b[1]) * (a[2] + b[2]);
+            n[1] += (a[2] - b[2]) * (a[0] + b[0]);
+            n[2] += (a[0] - b[0]) * (a[1] + b[1]);
+        }
+        let c: Vec<f64> = (0..3)
+            .map(|k| ps.iter().map(|p| p[k]).sum::<f64>() / ps.len() as f64)
+            .collect();
+        let d = (c[0] - 2.5) * n[0] + (c[1] - 2.5) * n[1] + (c[2] - 5.0) * n[2];
+        assert!(d > 0.0);
+    }
+}
+
+#[test]
+fn center_on_origin_moves_the_bbox() {
+    let mut cj = document(vec![cube_geometry("2")]);
+    cj.transform.translate = vec![85000.0, 446000.0, -2.0];
+    cj.update_geographical_extent();
+    let vertices = cj.vertices.clone();
+    let offset = cj.center_on_origin();
+    assert_eq!(offset, [85000.5, 446000.5, -1.5]);
+    assert_eq!(cj.vertices, vertices);
+    assert_eq!(cj.bbox(), Some([-0.5, -0.5, -0.5, 0.5, 0.5, 0.5]));
+    assert_eq!(
+        cj.metadata.unwrap()["geographicalExtent"],
+        json!([-0.5, -0.5, -0.5, 0.5, 0.5, 0.5])
+    );
+}
+
+#[test]
+fn highest_lod_with_missing_lods() {
+    let mut nolod = cube_geometry("1");
+    nolod.as_object_mut().unwrap().remove("lod");
+    let mut invalid = cube_geometry("1");
+    invalid["lod"] = json!("high");
+    let cj = document(vec![nolod.clone(), cube_geometry("2"), invalid.clone()]);
+    let gs = cjseq::conv::find_highest_lod_geometry(&cj.city_objects["b"]);
+    assert_eq!(gs.len(), 1);
+    assert_eq!(gs[0].lod.as_deref(), Some("2"));
+    //-- none is valid: only the 1st one
+    let cj = document(vec![invalid, nolod]);
+    let gs = cjseq::conv::find_highest_lod_geometry(&cj.city_objects["b"]);
+    assert_eq!(gs.len(), 1);
+    assert_eq!(gs[0].lod.as_deref(), Some("high"));
+    //-- which is a warning for the caller
+    let opts = cjseq::conv::Options::default();
+    assert_eq!(
+        opts.warnings(&cj),
+        ["Building #b has 2 geometries without a valid LoD, only the 1st one is used"]
+    );
+    let opts = cjseq::conv::Options {
+        lod: Some("high".to_string()),
+        ..Default::default()
+    };
+    assert!(opts.warnings(&cj).is_empty());
+}
+
+#[test]
+fn validate_and_repair_fixes_and_reports() {
+    let mut bad = cube_geometry("1");
+    bad["boundaries"] = json!([[[0, 1, 2, 99]]]);
+    let mut cj = document(vec![cube_geometry("2"), bad]);
+    cj.city_objects.get_mut("b").unwrap().children = Some(vec!["gone".to_string()]);
+    let mut a = Appearance::new();
+    a.add_material(json!({"name": "red", "diffuseColor": [1.5, 0.0, -0.2], "shininess": 0.5}));
+    cj.appearance = Some(a);
+    let (fixed, actions) = cj.validate_and_repair();
+    assert_eq!(
+        actions,
+        [
+            "material #0: \"diffuseColor\" clamped to [0, 1]",
+            "CityObject #b: geometry #1 removed (index out of range)",
+            "CityObject #b: child #gone removed (it doesn't exist)",
+        ]
+    );
+    let m = &fixed
+        .appearance
+        .as_ref()
+        .unwrap()
+        .materials
+        .as_ref()
+        .unwrap()[0];
+    assert_eq!(m["diffuseColor"], json!([1.0, 0.0, 0.0]));
+    assert_eq!(m["shininess"], json!(0.5));
+    let co = &fixed.city_objects["b"];
+    assert_eq!(co.geometry.as_ref().unwrap().len(), 1);
+    assert_eq!(co.children, Some(vec![]));
+    //-- nothing to repair the 2nd time
+    assert!(fixed.validate_and_repair().1.is_empty());
+}
+
+#[test]
+fn unreferenced_vertices_are_listed_and_removed() {
+    let mut cj = document(vec![json!({
+        "type": "MultiSurface", "lod": "1", "boundaries": [[[0, 1, 9, 3]]]
+    })]);
+    //-- 8 is an orphan, and 2 and 4-7 are not used by the geometry
+    cj.vertices.push(vec![5000, 5000, 5000]);
+    cj.vertices.push(vec![1000, 1000, 0]);
+    assert_eq!(cj.list_unreferenced_vertices(), [2, 4, 5, 6, 7, 8]);
+    cj.remove_unused_vertices();
+    assert!(cj.list_unreferenced_vertices().is_empty());
+    let g = &cj.city_objects["b"].geometry.as_ref().unwrap()[0];
+    assert_eq!(g.boundaries, json!([[[0, 1, 3, 2]]]));
+    assert_eq!(cj.vertices[3], vec![1000, 1000, 0]);
+}
+
+#[test]
+fn collect_report_of_the_duplicate_vertices() {
+    let mut cj = document(vec![json!({
+        "type": "MultiSurface", "lod": "1", "boundaries": [[[8, 1, 2, 9]]]
+    })]);
+    //-- copies of the vertices 0 and 3
+    cj.vertices.push(vec![0, 0, 0]);
+    cj.vertices.push(vec![0, 1000, 0]);
+    let r = cj.remove_duplicate_vertices();
+    assert_eq!((r.vertices_before, r.vertices_after), (10, 8));
+    assert_eq!(r.translate_before, r.translate_after);
+    assert_eq!(
+        cj.city_objects["b"].geometry.as_ref().unwrap()[0].boundaries,
+        json!([[[0, 1, 2, 3]]])
+    );
+    //-- the vertices are moved to the origin
+    for v in cj.vertices.iter_mut() {
+        v[0] += 2000;
+    }
+    let r = r.then(cj.retransform());
+    assert_eq!((r.vertices_before, r.vertices_after), (10, 8));
+    assert_eq!(r.translate_before, vec![0.0, 0.0, 0.0]);
+    assert_eq!(r.translate_after, vec![2.0, 0.0, 0.0]);
+}
+
+#[test]
+fn geometry_for_the_closest_lod() {
+    let cj = document(vec![cube_geometry("2.2"), cube_geometry("1.2")]);
+    let co = &cj.city_objects["b"];
+    let lod = |t: f64| co.geometry_for_lod(t).and_then(|g| g.lod.clone());
+    assert_eq!(lod(1.4).as_deref(), Some("1.2"));
+    assert_eq!(lod(2.0).as_deref(), Some("2.2"));
+    //-- a tie: the lowest
+    let cj = document(vec![cube_geometry("2"), cube_geometry("1")]);
+    let g = cj.city_objects["b"].geometry_for_lod(1.5).unwrap();
+    assert_eq!(g.lod.as_deref(), Some("1"));
+    assert!(CityObject::new("Building").geometry_for_lod(1.0).is_none());
+}
+
+#[test]
+fn seeded_random_order_is_reproducible() {
+    let mut cj = CityJSON::new();
+    for i in 0..20 {
+        cj.add_co(format!("b{:02}", i), CityObject::new("Building"));
+    }
+    let ids = |order: SortingStrategy| -> Vec<String> {
+        let mut buf: Vec<u8> = Vec::new();
+        cj.write_cityjsonseq(&mut buf, order).unwrap();
+        String::from_utf8(buf)
+            .unwrap()
+            .lines()
+            .skip(1)
+            .map(|l| CityJSONFeature::from_str(l).unwrap().id)
+            .collect()
+    };
+    let sorted = ids(SortingStrategy::Alphabetical);
+    assert!(sorted.windows(2).all(|w| w[0] < w[1]));
+    let shuffled = ids(SortingStrategy::Random(42));
+    assert_eq!(shuffled, ids(SortingStrategy::Random(42)));
+    assert_ne!(shuffled, sorted);
+    assert_ne!(shuffled, ids(SortingStrategy::Random(43)));
+    let mut s = shuffled.clone();
+    s.sort();
+    assert_eq!(s, sorted);
+}
+
+#[test]
+fn geometry_boundaries_from_and_to_nested_vecs() {
+    let b: Vec<Vec<Vec<u32>>> = vec![
+        vec![vec![0, 1, 2, 3]],
+        vec![vec![4, 5, 6, 7], vec![0, 1, 2]],
+    ];
+    let g = Geometry::new(GeometryType::MultiSurface, Some("2"), b.clone()).unwrap();
+    assert_eq!(
+        g.boundaries,
+        json!([[[0, 1, 2, 3]], [[4, 5, 6, 7], [0, 1, 2]]])
+    );
+    assert_eq!(g.boundaries_as::<Vec<Vec<Vec<u32>>>>().unwrap(), b);
+    //-- the depth is the one of the type, and uniform
+    assert!(g.boundaries_as::<Vec<Vec<Vec<Vec<u32>>>>>().is_err());
+    assert!(Geometry::new(GeometryType::Solid, None, b).is_err());
+    let mut g = g;
+    g.boundaries = json!([[[0, 1, 2]], [0, 1, 2]]);
+    assert!(g.boundaries_as::<Vec<Vec<Vec<u32>>>>().is_err());
+}
+
+#[test]
+fn validate_semantics_out_of_range_index_and_count_mismatch() {
+    let sem = |values: Value| {
+        json!({
+            "type": "MultiSurface",
+            "lod": "2",
+            "boundaries": [[[0, 1, 2, 3]], [[4, 5, 6, 7]]],
+            "semantics": {"surfaces": [{"type": "GroundSurface"}, {"type": "RoofSurface"}], "values": values}
+        })
+    };
+    let cj = document(vec![sem(json!([0, 1])), sem(json!([null, 1]))]);
+    assert_eq!(cj.validate_semantics_surface_count(), Ok(()));
+    let cj = document(vec![sem(json!([0, 2])), sem(json!([0]))]);
+    assert_eq!(
+        cj.validate_semantics_surface_count(),
+        Err(vec![
+            "CityObject #b, geometry #0, surface #1: semantic value 2 is not a surface of the semantics (2 surfaces)".to_string(),
+            "CityObject #b, geometry #1: 1 semantic value(s) for 2 surface(s)".to_string(),
+        ])
+    );
+}
+
+#[test]
+fn vertex_precision_report_spacing_and_recommendation() {
+    let s = fs::read_to_string(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("data/1b_w_texture.city.json"),
+    )
+    .unwrap();
+    let cj = CityJSON::from_str(&s).unwrap();
+    let r = cj.vertex_precision_report();
+    let spacing: Vec<f64> = r.min_spacing.iter().map(|d| d.unwrap()).collect();
+    for (d, expected) in spacing.iter().zip([0.144, 0.05, 0.11]) {
+        assert!((d - expected).abs() < 1e-9);
+    }
+    assert_eq!(r.lossless_scale, r.scale);
+    assert_eq!(
+        r.recommendation(),
+        "the scale [0.001, 0.001, 0.001] is adequate"
+    );
+    //-- the cube has vertices 1m apart, stored in mm
+    let r = document(vec![cube_geometry("2")]).vertex_precision_report();
+    assert_eq!(r.min_spacing, [Some(1.0), Some(1.0), Some(1.0)]);
+    assert_eq!(r.lossless_scale, [1.0, 1.0, 1.0]);
+    assert!(r.recommendation().contains("over-resolves"));
+    let mut cj = document(vec![cube_geometry("2")]);
+    cj.vertices[5] = vec![1, 1, 1];
+    assert!(cj
+        .vertex_precision_report()
+        .recommendation()
+        .contains("under-resolve"));
+}
+
+#[test]
+fn city_object_type_levels() {
+    let levels: Vec<TypeLevel> = ["Building", "BuildingPart", "+Noise", "Buildng"]
+        .iter()
+        .map(|t| CityObject::new(t).type_level())
+        .collect();
+    assert_eq!(
+        levels,
+        [
+            TypeLevel::FirstLevel,
+            TypeLevel::SecondLevel,
+            TypeLevel::Extension,
+            TypeLevel::Unknown
+        ]
+    );
+    assert!(CityObject::new("Building").is_first_level_type());
+    assert!(!CityObject::new("BuildingPart").is_first_level_type());
+    assert!(CityObject::new("BuildingPart").is_second_level_type());
+    assert!(!CityObject::new("+Noise").is_second_level_type());
+}
+
+#[test]
+fn check_manifold_closed_cube_and_open_box() {
+    let cube = |surfaces: Value| -> Geometry {
+        serde_json::from_value(json!({"type": "Solid", "lod": "2", "boundaries": [surfaces]}))
+            .unwrap()
+    };
+    let closed = json!([
+        [[0, 3, 2, 1]],
+        [[4, 5, 6, 7]],
+        [[0, 1, 5, 4]],
+        [[1, 2, 6, 5]],
+        [[2, 3, 7, 6]],
+        [[3, 0, 4, 7]]
+    ]);
+    assert_eq!(cube(closed.clone()).check_manifold(), Ok(()));
+    //-- without the roof, and with a wall flipped
+    let mut open = closed.clone();
+    open.as_array_mut().unwrap().remove(1);
+    assert_eq!(
+        cube(open).check_manifold(),
+        Err(vec![
+            "edge (4, 5): used by 1 surface(s), not 2".to_string(),
+            "edge (4, 7): used by 1 surface(s), not 2".to_string(),
+            "edge (5, 6): used by 1 surface(s), not 2".to_string(),
+            "edge (6, 7): used by 1 surface(s), not 2".to_string(),
+        ])
+    );
+    let mut flipped = closed;
+    flipped[2] = json!([[4, 5, 1, 0]]);
+    let errors = cube(flipped).check_manifold().unwrap_err();
+    assert_eq!(errors.len(), 4);
+    assert_eq!(
+        errors[0],
+        "edge (0, 1): same orientation in surfaces #0 and #2"
+    );
+    assert!(Geometry::new(GeometryType::MultiSurface, None, json!([]))
+        .unwrap()
+        .check_manifold()
+        .is_err());
+}
+
+#[test]
+fn validate_lod_presence_reports_the_missing_lod() {
+    let mut cj = document(vec![cube_geometry("1"), cube_geometry("2.2")]);
+    cj.city_objects
+        .insert("c".to_string(), CityObject::new("Building"));
+    cj.city_objects
+        .get_mut("c")
+        .unwrap()
+        .add_geometry(serde_json::from_value(cube_geometry("1.2")).unwrap());
+    assert_eq!(cj.validate_lod_presence(1.0), Ok(()));
+    assert_eq!(
+        cj.validate_lod_presence(2.0),
+        Err(vec![
+            "CityObject #c (Building): no geometry with a LoD >= 2".to_string()
+        ])
+    );
+    //-- the LoD of the BuildingParts count for their parent
+    let s =
+        fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("data/3dbag_b2.city.jsonl"))
+            .unwrap();
+    let mut lines = s.lines();
+    let mut cj = CityJSON::from_str(lines.next().unwrap()).unwrap();
+    for l in lines.filter(|l| !l.trim().is_empty()) {
+        cj.add_one_cjf(CityJSONFeature::from_str(l).unwrap())
+            .unwrap();
+    }
+    assert_eq!(cj.validate_lod_presence(2.2), Ok(()));
+    assert_eq!(
+        cj.validate_lod_presence(3.0).unwrap_err().len(),
+        cj.city_objects.len()
+    );
+}
+
+#[test]
+fn snap_shells_closes_a_cube_with_a_gap() {
+    //-- vertex 8 is vertex 5 moved by 1mm, used by the roof only
+    let mut cj = document(vec![json!({"type": "Solid", "lod": "2", "boundaries": [[
+        [[0, 3, 2, 1]],
+        [[4, 8, 6, 7]],
+        [[0, 1, 5, 4]],
+        [[1, 2, 6, 5]],
+        [[2, 3, 7, 6]],
+        [[3, 0, 4, 7]]
+    ]]})]);
+    cj.vertices.push(vec![1001, 0, 1000]);
+    let t = cj.transform.clone();
+    let vs = cj.vertices.clone();
+    let g = &mut cj
+        .city_objects
+        .get_mut("b")
+        .unwrap()
+        .geometry
+        .as_mut()
+        .unwrap()[0];
+    assert!(g.check_manifold().is_err());
+    assert_eq!(g.snap_shells(&vs, &t, 0.0005), 0);
+    assert_eq!(g.snap_shells(&vs, &t, 0.01), 1);
+    assert_eq!(g.check_manifold(), Ok(()));
+    assert_eq!(g.boundaries[0][1], json!([[4, 5, 6, 7]]));
+    assert_eq!(g.snap_shells(&vs, &t, 0.01), 0);
+}
+
+#[test]
+fn attribute_schema_of_a_fixture() {
+    let s =
+        fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("data/3dbag_b2.city.jsonl"))
+            .unwrap();
+    let mut lines = s.lines();
+    let mut cj = CityJSON::from_str(lines.next().unwrap()).unwrap();
+    for l in lines.filter(|l| !l.trim().is_empty()) {
+        cj.add_one_cjf(CityJSONFeature::from_str(l).unwrap())
+            .unwrap();
+    }
+    let types = cj.attribute_schema();
+    assert_eq!(types["status"].thetype, AttributeType::String);
+    assert_eq!(
+        types["oorspronkelijkbouwjaar"].thetype,
+        AttributeType::Number
+    );
+    assert_eq!(types["b3_kas_warenhuis"].thetype, AttributeType::Bool);
+    assert_eq!(types["eindgeldigheid"].thetype, AttributeType::Null);
+    //-- only the 2 Buildings have attributes, not their BuildingParts
+    assert_eq!(types["status"].count, 2);
+    assert_eq!(
+        types["status"].count_per_type.iter().collect::<Vec<_>>(),
+        [(&"Building".to_string(), &2)]
+    );
+    let b = cj
+        .city_objects
+        .values_mut()
+        .find(|co| co.thetype == "Building")
+        .unwrap();
+    b.attributes.as_mut().unwrap()["status"] = json!(3);
+    b.attributes.as_mut().unwrap()["eindgeldigheid"] = json!("2024");
+    let types = cj.attribute_schema();
+    assert_eq!(types["status"].thetype, AttributeType::Mixed);
+    assert_eq!(types["eindgeldigheid"].thetype, AttributeType::String);
+}
+
+#[test]
+fn strict_rejects_a_misspelled_member() {
+    let s = r#"{"type": "CityJSON", "version": "2.0",
+        "transform": {"scale": [0.01, 0.01, 0.01], "translate": [0.0, 0.0, 0.0]},
+        "CityObjects": {"b": {"type": "Building", "attribtes": {"height": 10}}},
+        "vertices": [], "+census": {}}"#;
+    let cj = CityJSON::from_str(s).unwrap();
+    assert!(cj.city_objects["b"].attributes.is_none());
+    let e = CityJSON::from_str_strict(s).unwrap_err();
+    assert_eq!(e, "unknown members: \"attribtes\" (CityObject #b)");
+    let s = s.replace("\"vertices\"", "\"vertixes\": [], \"vertices\"");
+    let e = CityJSON::from_str_strict(&s).unwrap_err();
+    assert!(e.starts_with("unknown members: \"vertixes\", "));
+}
+
+#[test]
+fn validate_transform_roundtrip_detects_collapsed_vertices() {
+    //-- 2 vertices 1mm apart
+    let mut cj = document(vec![cube_geometry("2")]);
+    cj.vertices[1] = vec![1, 0, 0];
+    let t = |s: f64| Transform {
+        scale: vec![s, s, s],
+        translate: vec![0.0, 0.0, 0.0],
+    };
+    assert_eq!(cj.validate_transform_roundtrip(&t(0.001)), Ok(()));
+    assert_eq!(
+        cj.validate_transform_roundtrip(&t(0.01)),
+        Err("1 vertices collapse with the scale [0.01, 0.01, 0.01]".to_string())
+    );
+}
+
+fn cat_in_memory(cj: &CityJSON) -> Vec<Value> {
+    let mut re = vec![cj.get_metadata().to_value()];
+    for (k, co) in &cj.city_objects {
+        if cj.is_feature_root(co) {
+            re.push(cj.get_cjfeature(k).unwrap().to_value());
+        }
+    }
+    re
+}
+
+fn cat_streamed(s: &str) -> Vec<Value> {
+    let st = CatStream::new(Cursor::new(s.as_bytes())).unwrap();
+    let mut re = vec![st.header().get_metadata().to_value()];
+    st.for_each_feature(|f| {
+        re.push(f.to_value());
+        Ok(())
+    })
+    .unwrap();
+    re
+}
+
+#[test]
+fn streamed_cat_equals_in_memory_cat() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("data");
+    for name in [
+        "1b_w_texture.city.json",
+        "solid_w_texture.city.json",
+        "3dbag_b2.city.jsonl",
+    ] {
+        let s = fs::read_to_string(dir.join(name)).unwrap();
+        let cj = if name.ends_with(".jsonl") {
+            //-- collected first, it has children
+            let mut lines = s.lines();
+            let mut cj = CityJSON::from_str(lines.next().unwrap()).unwrap();
+            for l in lines.filter(|l| !l.trim().is_empty()) {
+                cj.add_one_cjf(CityJSONFeature::from_str(l).unwrap())
+                    .unwrap();
+            }
+            cj
+        } else {
+            CityJSON::from_str(&s).unwrap()
+        };
+        let mut a = cat_in_memory(&cj);
+        let mut b = cat_streamed(&serde_json::to_string(&cj).unwrap());
+        assert_eq!(a.len(), b.len(), "{}", name);
+        assert_eq!(a[0], b[0], "{}", name);
+        let key = |v: &Value| v["id"].as_str().unwrap_or("").to_string();
+        a.sort_by_key(key);
+        b.sort_by_key(key);
+        assert_eq!(a, b, "{}", name);
+    }
+}
+
+#[test]
+fn point_of_contact_builder() {
+    let poc = PointOfContactBuilder::new("3D geoinformation", "info@example.org")
+        .contact_type("organization")
+        .address(Address {
+            locality: Some("Delft".to_string()),
+            country: Some("the Netherlands".to_string()),
+            ..Default::default()
+        })
+        .build()
+        .unwrap();
+    let mut cj = CityJSON::new();
+    cj.set_point_of_contact(&poc);
+    assert_eq!(
+        cj.metadata.as_ref().unwrap()["pointOfContact"],
+        json!({
+            "contactName": "3D geoinformation",
+            "emailAddress": "info@example.org",
+            "contactType": "organization",
+            "address": {"locality": "Delft", "country": "the Netherlands"}
+        })
+    );
+    assert_eq!(cj.point_of_contact(), Some(Ok(poc)));
+}
+
+#[test]
+fn point_of_contact_builder_rejects_bad_values() {
+    let re = PointOfContactBuilder::new("Jo", "info@example.org")
+        .contact_type("company")
+        .build();
+    assert_eq!(
+        re,
+        Err(vec![
+            "\"contactType\" \"company\" is not \"individual\" or \"organization\"".to_string()
+        ])
+    );
+    assert!(PointOfContactBuilder::new("Jo", "info.example.org")
+        .build()
+        .is_err());
+}
+
+#[test]
+fn iter_city_objects_is_deterministic() {
+    let p = Path::new(env!("CARGO_MANIFEST_DIR")).join("data/3dbag_b2.city.jsonl");
+    let s = fs::read_to_string(p).unwrap();
+    let mut lines = s.lines();
+    let mut cj = CityJSON::from_str(lines.next().unwrap()).unwrap();
+    for l in lines.filter(|l| !l.trim().is_empty()) {
+        cj.add_one_cjf(CityJSONFeature::from_str(l).unwrap())
+            .unwrap();
+    }
+    //-- parsed again, the HashMap has another order
+    let cj2 = CityJSON::from_str(&serde_json::to_string(&cj).unwrap()).unwrap();
+    let ids: Vec<&String> = cj.iter_city_objects().map(|(k, _)| k).collect();
+    assert_eq!(
+        ids,
+        cj.iter_city_objects().map(|(k, _)| k).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        ids,
+        cj2.iter_city_objects().map(|(k, _)| k).collect::<Vec<_>>()
+    );
+    assert_eq!(ids.len(), cj.city_objects.len());
+    let n = cj.num_features();
+    assert!(ids[..n]
+        .iter()
+        .all(|k| cj.is_feature_root(&cj.city_objects[*k])));
+    assert!(ids[n..]
+        .iter()
+        .all(|k| !cj.is_feature_root(&cj.city_objects[*k])));
+}
+
+#[test]
+fn check_vertex_indices_out_of_range() {
+    let j = json!({
+        "type": "CityJSONFeature",
+        "id": "f",
+        "CityObjects": {"b": {"type": "Building", "geometry": [cube_geometry("2")]}},
+        "vertices": [[0, 0, 0], [1, 0, 0], [1, 1, 0], [0, 1, 0]]
+    });
+    let mut cjf = CityJSONFeature::from_value(j).unwrap();
+    let e = CjseqError::InvalidValue {
+        feature: "f".to_string(),
+        reason: "CityObject #b uses the vertex #7 but the feature has only 4 vertices".to_string(),
+    };
+    assert_eq!(cjf.check_vertex_indices(), Err(e.clone()));
+    //-- and nothing is added
+    let mut cj = CityJSON::new();
+    assert_eq!(cj.add_one_cjf(cjf.clone()), Err(e));
+    assert!(cj.city_objects.is_empty() && cj.vertices.is_empty());
+    cjf.vertices.extend(vec![vec![0, 0, 1]; 4]);
+    assert_eq!(cjf.check_vertex_indices(), Ok(()));
+    assert!(cj.add_one_cjf(cjf).is_ok());
+    assert_eq!(cj.vertices.len(), 8);
+}
+
+#[test]
+fn collect_with_capacity_gives_the_same_result() {
+    let s =
+        fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("data/3dbag_b2.city.jsonl"))
+            .unwrap();
+    let lines: Vec<&str> = s.lines().filter(|l| !l.trim().is_empty()).collect();
+    let mut a = CityJSON::from_str(lines[0]).unwrap();
+    let mut b = CityJSON::from_str(lines[0]).unwrap();
+    b.reserve(100, 10_000);
+    for l in &lines[1..] {
+        a.add_one_cjf(CityJSONFeature::from_str(l).unwrap())
+            .unwrap();
+        b.add_one_cjf(CityJSONFeature::from_str(l).unwrap())
+            .unwrap();
+    }
+    assert!(b.vertices.capacity() >= 10_000);
+    assert_eq!(a.to_value(), b.to_value());
+    let c = CityJSON::with_capacity(10, 20);
+    assert!(c.vertices.capacity() >= 20);
+    assert_eq!(c.to_value(), CityJSON::new().to_value());
+}
+
+#[test]
+fn validate_solids_zero_shells_and_too_few_surfaces() {
+    let cube = json!([
+        [[0, 3, 2, 1]],
+        [[4, 5, 6, 7]],
+        [[0, 1, 5, 4]],
+        [[1, 2, 6, 5]],
+        [[2, 3, 7, 6]],
+        [[3, 0, 4, 7]]
+    ]);
+    let cj = document(vec![
+        json!({"type": "Solid", "lod": "2", "boundaries": [cube]}),
+        json!({"type": "Solid", "lod": "2", "boundaries": []}),
+        json!({"type": "Solid", "lod": "2", "boundaries": [[[[0, 1, 2]], [[0, 2, 3]], [[0, 3, 1]]]]}),
+    ]);
+    assert_eq!(
+        cj.validate_solids(false),
+        Err(vec![
+            "CityObject #b, geometry #1: the Solid has no shell".to_string(),
+            "CityObject #b, geometry #2, shell #0: 3 surface(s), at least 4 are needed".to_string(),
+        ])
+    );
+    //-- an interior shell (vertices 8-15) inside the exterior one, then partly outside
+    let inner: Vec<Vec<Vec<u64>>> = cube
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|sf| {
+            vec![sf[0]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|i| i.as_u64().unwrap() + 8)
+                .collect()]
+        })
+        .collect();
+    let mut cj = document(vec![
+        json!({"type": "Solid", "lod": "2", "boundaries": [cube, inner]}),
+    ]);
+    for i in 0..8 {
+        let v: Vec<i64> = cj.vertices[i].iter().map(|c| 250 + c / 2).collect();
+        cj.vertices.push(v);
+    }
+    assert_eq!(cj.validate_solids(true), Ok(()));
+    cj.vertices[15] = vec![250, 750, 2000];
+    assert_eq!(cj.validate_solids(false), Ok(()));
+    assert_eq!(
+        cj.validate_solids(true),
+        Err(vec!["CityObject #b, geometry #0, shell #1: the interior shell is not inside the exterior shell".to_string()])
+    );
+}
+
+#[test]
+fn city_object_bbox() {
+    let mut cj = document(vec![
+        json!({"type": "MultiSurface", "lod": "2", "boundaries": [[[0, 1, 2, 3]]]}),
+    ]);
+    let co = &cj.city_objects["b"];
+    assert_eq!(
+        co.bbox(&cj.vertices, &cj.transform),
+        Some([0.0, 0.0, 0.0, 1.0, 1.0, 0.0])
+    );
+    //-- the geographicalExtent is used when there is one, even if it's not the same
+    let co = cj.city_objects.get_mut("b").unwrap();
+    co.geographical_extent = Some(vec![10.0, 10.0, 0.0, 20.0, 20.0, 5.0]);
+    let co = &cj.city_objects["b"];
+    assert_eq!(
+        co.bbox(&cj.vertices, &cj.transform),
+        Some([10.0, 10.0, 0.0, 20.0, 20.0, 5.0])
+    );
+    let empty = document(vec![]);
+    assert_eq!(
+        empty.city_objects["b"].bbox(&empty.vertices, &empty.transform),
+        None
+    );
+}
+
+#[test]
+fn appearance_merge_reuses_the_materials() {
+    let red = json!({"name": "red", "diffuseColor": [1.0, 0.0, 0.0]});
+    let blue = json!({"name": "blue", "diffuseColor": [0.0, 0.0, 1.0]});
+    let green = json!({"name": "green", "diffuseColor": [0.0, 1.0, 0.0]});
+    let mut a = Appearance::new();
+    a.materials = Some(vec![red.clone(), blue.clone()]);
+    a.vertices_texture = Some(vec![vec![0.0, 0.0], vec![1.0, 0.0]]);
+    let mut b = Appearance::new();
+    b.materials = Some(vec![green.clone(), red.clone()]);
+    b.textures = Some(vec![json!({"type": "PNG", "image": "roof.png"})]);
+    b.vertices_texture = Some(vec![vec![0.5, 0.5]]);
+    let remap = a.merge(&b);
+    assert_eq!(a.materials, Some(vec![red, blue, green]));
+    assert_eq!(remap.materials[&0], 2);
+    assert_eq!(remap.materials[&1], 0);
+    assert_eq!(remap.textures[&0], 0);
+    assert_eq!(remap.vertices_texture[&0], 2);
+    assert_eq!(a.vertices_texture.as_ref().unwrap().len(), 3);
+    //-- merging the same appearance again adds only the texture vertices
+    let remap = a.merge(&b);
+    assert_eq!(a.materials.as_ref().unwrap().len(), 3);
+    assert_eq!(a.textures.as_ref().unwrap().len(), 1);
+    assert_eq!(remap.vertices_texture[&0], 3);
+}
+
+#[test]
+fn retain_city_objects_keeps_the_references_consistent() {
+    let mut cj = document(vec![]);
+    let parts = json!({
+        "b": {"type": "Building", "children": ["b-0", "b-1"]},
+        "b-0": {"type": "BuildingPart", "parents": ["b"], "geometry": [cube_geometry("2")]},
+        "b-1": {"type": "BuildingPart", "parents": ["b"]},
+        "r": {"type": "Road"}
+    });
+    cj.city_objects = serde_json::from_value(parts).unwrap();
+    let mut parts_only = cj.clone();
+
+    cj.retain_city_objects(|_, co| co.thetype != "BuildingPart");
+    assert_eq!(cj.sorted_ids(), vec!["b", "r"]);
+    assert!(cj.city_objects["b"].children.is_none());
+
+    parts_only.retain_city_objects(|id, co| co.thetype == "BuildingPart" && id != "b-1");
+    assert_eq!(parts_only.sorted_ids(), vec!["b-0"]);
+    assert!(parts_only.city_objects["b-0"].parents.is_none());
+    //-- the vertices are kept, until they are pruned
+    assert_eq!(parts_only.vertices.len(), 8);
+    parts_only.remove_unused_vertices();
+    assert_eq!(parts_only.vertices.len(), 8);
+    cj.remove_unused_vertices();
+    assert!(cj.vertices.is_empty());
+}
+
+#[test]
+fn rescale_feet_to_metres() {
+    let mut cj = document(vec![cube_geometry("2")]);
+    cj.transform.translate = vec![1000.0, 2000.0, 0.0];
+    cj.city_objects.get_mut("b").unwrap().geographical_extent =
+        Some(vec![1000.0, 2000.0, 0.0, 1001.0, 2001.0, 1.0]);
+    let vertices = cj.vertices.clone();
+    cj.rescale(0.3048);
+    assert_eq!(cj.vertices, vertices);
+    let b = cj.bbox().unwrap();
+    let expected = [304.8, 609.6, 0.0, 305.1048, 609.9048, 0.3048];
+    for (x, e) in b.iter().zip(expected) {
+        assert!((x - e).abs() < 1e-9, "{:?}", b);
+    }
+    let ge = cj.city_objects["b"].geographical_extent.as_ref().unwrap();
+    for (x, e) in ge.iter().zip(expected) {
+        assert!((x - e).abs() < 1e-9, "{:?}", ge);
+    }
+}
+
+#[test]
+fn select_theme_keeps_only_its_materials() {
+    let mut g = cube_geometry("2");
+    g["material"] = json!({"summer": {"value": 0}, "winter": {"value": 1}});
+    let mut cj = document(vec![g]);
+    let summer = json!({"name": "green", "diffuseColor": [0.0, 1.0, 0.0]});
+    let winter = json!({"name": "white", "diffuseColor": [1.0, 1.0, 1.0]});
+    let mut a = Appearance::new();
+    a.materials = Some(vec![summer.clone(), winter.clone()]);
+    a.default_theme_material = Some("summer".to_string());
+    cj.appearance = Some(a);
+    let mut cj_default = cj.clone();
+
+    cj.select_theme(Some("winter"));
+    let a = cj.appearance.as_ref().unwrap();
+    assert_eq!(a.materials, Some(vec![winter]));
+    assert_eq!(a.default_theme_material.as_deref(), Some("winter"));
+    let m = cj.city_objects["b"].geometry.as_ref().unwrap()[0]
+        .material
+        .as_ref()
+        .unwrap();
+    assert_eq!(m.keys().collect::<Vec<_>>(), ["winter"]);
+    assert_eq!(m["winter"].value, Some(0));
+
+    //-- without a name, the default theme is kept
+    cj_default.select_theme(None);
+    let a = cj_default.appearance.as_ref().unwrap();
+    assert_eq!(a.materials, Some(vec![summer]));
+    let m = cj_default.city_objects["b"].geometry.as_ref().unwrap()[0]
+        .material
+        .as_ref()
+        .unwrap();
+    assert_eq!(m.keys().collect::<Vec<_>>(), ["summer"]);
+}
+
+#[test]
+fn city_object_from_scratch() {
+    let mut co = CityObject::new("Building");
+    co.add_geometry(serde_json::from_value(cube_geometry("1")).unwrap());
+    co.add_geometry(serde_json::from_value(cube_geometry("2")).unwrap());
+    assert_eq!(
+        serde_json::to_value(&co).unwrap(),
+        json!({
+            "type": "Building",
+            "geometry": [cube_geometry("1"), cube_geometry("2")]
+        })
+    );
+    co.set_geometry(vec![]);
+    assert_eq!(
+        serde_json::to_value(&co).unwrap(),
+        json!({"type": "Building"})
+    );
+}
+
+#[test]
+fn merge_refuses_another_version() {
+    let mut cj = document(vec![cube_geometry("2")]);
+    let mut other = document(vec![cube_geometry("1")]);
+    other.version = "1.1".to_string();
+    let e = cj.merge(&other, DupStrategy::SuffixNumeric).unwrap_err();
+    assert_eq!(
+        e,
+        CjseqError::VersionMismatch {
+            expected: "2.0".to_string(),
+            found: "1.1".to_string()
+        }
+    );
+    assert_eq!(
+        e.to_string(),
+        "version mismatch: expected CityJSON v2.0, found v1.1"
+    );
+    assert_eq!(cj.city_objects.len(), 1);
+    other.version = "2.0".to_string();
+    cj.merge(&other, DupStrategy::SuffixNumeric).unwrap();
+    assert_eq!(cj.city_objects.len(), 2);
+}
+
+#[test]
+fn validate_attribute_schema_reports_the_drift() {
+    let mut cj = document(vec![]);
+    let cos = json!({
+        "a": {"type": "Building", "attributes": {"height": 10.0, "roofType": "flat"}},
+        "b": {"type": "Building", "attributes": {"height": 12.0, "roofType": "gabled"}},
+        "c": {"type": "Building", "attributes": {"height": 8.0, "roof_type": "flat"}},
+        "p": {"type": "BuildingPart"}
+    });
+    cj.city_objects = serde_json::from_value(cos).unwrap();
+    let schema = cj.attribute_schema();
+    assert_eq!(schema.len(), 3);
+    assert_eq!(schema["height"].count_per_type["Building"], 3);
+    assert_eq!(schema["roofType"].count_per_type["Building"], 2);
+    assert_eq!(schema["roof_type"].count_per_type["Building"], 1);
+    assert!(schema
+        .values()
+        .all(|a| !a.count_per_type.contains_key("BuildingPart")));
+    assert_eq!(
+        cj.validate_attribute_schema().unwrap_err(),
+        [
+            "CityObject #c (Building): unexpected attribute \"roof_type\"",
+            "CityObject #c (Building): missing attribute \"roofType\""
+        ]
+    );
+}
+
+#[test]
+fn cat_a_group_and_its_members() {
+    //-- the group is a feature without its members, each member is in its own feature
+    let mut cj = document(vec![]);
+    let cos = json!({
+        "g": {"type": "CityObjectGroup", "children": ["a", "b"]},
+        "a": {"type": "Building", "parents": ["g"], "children": ["a-0"]},
+        "a-0": {"type": "BuildingPart", "parents": ["a"], "geometry": [cube_geometry("2")]},
+        "b": {"type": "Building", "parents": ["g"], "geometry": [cube_geometry("1")]}
+    });
+    cj.city_objects = serde_json::from_value(cos).unwrap();
+    assert_eq!(cj.num_features(), 3);
+    let ids = |fs: &[Value]| -> Vec<Vec<String>> {
+        let mut re: Vec<Vec<String>> = fs[1..]
+            .iter()
+            .map(|f| {
+                let mut ks: Vec<String> = f["CityObjects"]
+                    .as_object()
+                    .unwrap()
+                    .keys()
+                    .cloned()
+                    .collect();
+                ks.sort();
+                ks
+            })
+            .collect();
+        re.sort();
+        re
+    };
+    let expected = [vec!["a", "a-0"], vec!["b"], vec!["g"]];
+    let features = cat_in_memory(&cj);
+    assert_eq!(ids(&features), expected);
+    assert_eq!(ids(&cat_streamed(&cj.to_value().to_string())), expected);
+    let g = features.iter().find(|f| f["id"] == "g").unwrap();
+    assert!(g["vertices"].as_array().unwrap().is_empty());
+    assert_eq!(g["CityObjects"]["g"]["children"], json!(["a", "b"]));
+
+    //-- and collected back
+    let mut cj2 = cj.get_metadata();
+    for f in &features[1..] {
+        cj2.add_one_cjf(CityJSONFeature::from_value(f.clone()).unwrap())
+            .unwrap();
+    }
+    assert_eq!(cj2.city_objects.len(), 4);
+    assert_eq!(cj2.city_objects["a"].parents, Some(vec!["g".to_string()]));
+}
+
+#[test]
+fn geometry_apply_matrix() {
+    //-- rotation of 90 degrees around z, then translation (10, 20, 30)
+    let mut g: Geometry = serde_json::from_value(json!({
+        "type": "GeometryInstance",
+        "template": 0,
+        "boundaries": [0],
+        "transformationMatrix": [
+            0.0, -1.0, 0.0, 10.0,
+            1.0, 0.0, 0.0, 20.0,
+            0.0, 0.0, 1.0, 30.0,
+            0.0, 0.0, 0.0, 1.0
+        ]
+    }))
+    .unwrap();
+    assert_eq!(g.apply_matrix([1.0, 0.0, 0.0]), [10.0, 21.0, 30.0]);
+    assert_eq!(g.apply_matrix([2.0, 3.0, 4.0]), [7.0, 22.0, 34.0]);
+    g.transformation_matrix = Some(json!([1.0, 0.0]));
+    assert!(g.matrix().is_err());
+    g.transformation_matrix = None;
+    assert!(g.matrix().is_err());
+    assert_eq!(g.apply_matrix([2.0, 3.0, 4.0]), [2.0, 3.0, 4.0]);
+}
+
+#[test]
+fn validate_version_specific_fields_numeric_lod() {
+    let mut j = document(vec![cube_geometry("2")]).to_value();
+    assert!(CityJSON::validate_version_specific_fields(&j).is_ok());
+    j["CityObjects"]["b"]["geometry"][0]["lod"] = json!(2);
+    assert_eq!(
+        CityJSON::validate_version_specific_fields(&j).unwrap_err(),
+        ["the LoD 2 is a number, it is a string since v1.1 (eg \"2\") [path:/CityObjects/b/geometry/0/lod]"]
+    );
+    //-- GenericCityObject is only in v2.0
+    j["CityObjects"]["b"]["geometry"][0]["lod"] = json!("2");
+    j["CityObjects"]["b"]["type"] = json!("GenericCityObject");
+    assert!(CityJSON::validate_version_specific_fields(&j).is_ok());
+    j["version"] = json!("1.1");
+    assert_eq!(
+        CityJSON::validate_version_specific_fields(&j)
+            .unwrap_err()
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn material_summary_lists_the_textures() {
+    let p = Path::new(env!("CARGO_MANIFEST_DIR")).join("data/1b_w_texture.city.json");
+    let cjj = CityJSON::from_str(&fs::read_to_string(p).unwrap()).unwrap();
+    let co = &cjj.city_objects["BID_dd628cf8-1e08-430d-9672-eff269b33d4c"];
+    let s = co.material_summary(cjj.appearance.as_ref());
+    assert!(s.materials.is_empty());
+    let textures: Vec<String> = (89..=96)
+        .map(|i| format!("appearance/tex_{}.jpg", i))
+        .collect();
+    assert_eq!(s.textures.into_iter().collect::<Vec<_>>(), textures);
+    assert!(s.missing_textures.is_empty());
+    //-- without the appearance, the indices are missing
+    let s = co.material_summary(None);
+    assert!(s.textures.is_empty());
+    assert_eq!(s.missing_textures.len(), 8);
+}
+
+#[test]
+fn write_cityjsonseq_to_a_buffer() {
+    let s =
+        fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("data/3dbag_b2.city.jsonl"))
+            .unwrap();
+    let mut lines = s.lines();
+    let mut cj = CityJSON::from_str(lines.next().unwrap()).unwrap();
+    for l in lines.filter(|l| !l.trim().is_empty()) {
+        cj.add_one_cjf(CityJSONFeature::from_str(l).unwrap())
+            .unwrap();
+    }
+    let mut buf: Vec<u8> = Vec::new();
+    cj.write_cityjsonseq(&mut buf, SortingStrategy::Alphabetical)
+        .unwrap();
+    let out = String::from_utf8(buf).unwrap();
+    let mut lines = out.lines();
+    let mut cj2 = CityJSON::from_str(lines.next().unwrap()).unwrap();
+    let mut ids: Vec<String> = Vec::new();
+    for l in lines {
+        let cjf = CityJSONFeature::from_str(l).unwrap();
+        ids.push(cjf.id.clone());
+        cj2.add_one_cjf(cjf).unwrap();
+    }
+    assert_eq!(ids.len(), cj.num_features());
+    assert!(ids.windows(2).all(|w| w[0] < w[1]));
+    assert_eq!(cj2.content_hash(), cj.content_hash());
+    //-- a missing child is an error
+    let id = ids[0].clone();
+    let child = cj.city_objects[&id].get_children_keys()[0].clone();
+    cj.city_objects.remove(&child);
+    assert!(cj
+        .write_cityjsonseq(&mut Vec::new(), SortingStrategy::Type)
+        .is_err());
+}
+
+fn textured_feature(id: &str, image: &str) -> CityJSONFeature {
+    CityJSONFeature::from_value(json!({
+        "type": "CityJSONFeature",
+        "id": id,
+        "CityObjects": {id: {"type": "Building", "geometry": [{
+            "type": "MultiSurface",
+            "lod": "1",
+            "boundaries": [[[0, 1, 2]]],
+            "texture": {"rgb": {"values": [[[0, 0, 1, 2]]]}}
+        }]}},
+        "vertices": [[0, 0, 0], [1, 0, 0], [0, 1, 0]],
+        "appearance": {
+            "textures": [{"type": "JPG", "image": image}],
+            "vertices-texture": [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]]
+        }
+    }))
+    .unwrap()
+}
+
+#[test]
+fn dedup_textures_by_content() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("dedup_textures");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("tex_a.jpg"), b"same bytes").unwrap();
+    fs::write(dir.join("tex_b.jpg"), b"same bytes").unwrap();
+    let collect = |a: &str, b: &str| {
+        let mut cj = document(vec![]);
+        cj.city_objects.clear();
+        cj.add_one_cjf(textured_feature("a", a)).unwrap();
+        cj.add_one_cjf(textured_feature("b", b)).unwrap();
+        cj
+    };
+    let num_textures = |cj: &CityJSON| {
+        cj.appearance
+            .as_ref()
+            .unwrap()
+            .textures
+            .as_ref()
+            .unwrap()
+            .len()
+    };
+    let mut cj = collect("tex_a.jpg", "./tex_b.jpg");
+    assert_eq!(num_textures(&cj), 2);
+    assert_eq!(cj.dedup_textures(&dir), 1);
+    assert_eq!(num_textures(&cj), 1);
+    for id in ["a", "b"] {
+        let t = cj.city_objects[id].geometry.as_ref().unwrap()[0]
+            .texture
+            .as_ref()
+            .unwrap();
+        assert_eq!(t["rgb"].values.as_ref().unwrap()[0][0][0], 0);
+    }
+    //-- the texture vertices are not modified
+    assert_eq!(
+        cj.appearance
+            .as_ref()
+            .unwrap()
+            .vertices_texture
+            .as_ref()
+            .unwrap()
+            .len(),
+        6
+    );
+    //-- different contents, or files that cannot be read and different paths
+    let mut cj = collect("tex_a.jpg", "tex_b.jpg");
+    assert_eq!(cj.dedup_textures(&dir.join("missing")), 0);
+    let mut cj2 = collect("x/tex_a.jpg", "x/./y/../tex_a.jpg");
+    assert_eq!(cj2.dedup_textures(&dir.join("missing")), 1);
+    fs::write(dir.join("tex_b.jpg"), b"other bytes").unwrap();
+    assert_eq!(cj.dedup_textures(&dir), 0);
+}
+
+#[test]
+fn vertices_and_boundaries_flat() {
+    let cj = document(vec![cube_geometry("2")]);
+    let vs = cj.vertices_flat();
+    assert_eq!(vs.len(), 3 * cj.vertices.len());
+    for (i, v) in vs.chunks(3).enumerate() {
+        assert_eq!(v, cj.transform.real_coordinates(&cj.vertices[i]));
+    }
+    //-- 2 triangles per square, with the orientation of the ring
+    let ts = cj.boundaries_flat();
+    assert_eq!(ts, [3, 0, 1, 1, 2, 3, 7, 4, 5, 5, 6, 7]);
+    //-- a concave ring (L-shape), its area is 3
+    let mut cj = document(vec![json!({
+        "type": "MultiSurface",
+        "lod": "1",
+        "boundaries": [[[0, 1, 2, 3, 4, 5]]]
+    })]);
+    cj.vertices = vec![
+        vec![0, 0, 0],
+        vec![2000, 0, 0],
+        vec![2000, 1000, 0],
+        vec![1000, 1000, 0],
+        vec![1000, 2000, 0],
+        vec![0, 2000, 0],
+    ];
+    let vs = cj.vertices_flat();
+    let ts = cj.boundaries_flat();
+    assert_eq!(ts.len(), 4 * 3);
+    let p = |i: usize| (vs[3 * i], vs[3 * i + 1]);
+    let areas: Vec<f64> = ts
+        .chunks(3)
+        .map(|t| {
+            let (a, b, c) = (p(t[0]), p(t[1]), p(t[2]));
+            ((b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)) / 2.0
+        })
+        .collect();
+    //-- no triangle is flipped (ie outside the ring)
+    assert!(areas.iter().all(|a| *a > 0.0));
+    assert!((areas.iter().sum::<f64>() - 3.0).abs() < 1e-9);
+}
+
+fn instanced_document() -> CityJSON {
+    CityJSON::from_value(json!({
+        "type": "CityJSON",
+        "version": "2.0",
+        "transform": {"scale": [0.01, 0.01, 0.01], "translate": [100.0, 200.0, 0.0]},
+        "CityObjects": {
+            "t1": {"type": "SolitaryVegetationObject", "geometry": [{
+                "type": "GeometryInstance",
+                "template": 0,
+                "boundaries": [1],
+                "transformationMatrix": [2.0, 0.0, 0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0, 0.0, 1.0]
+            }]},
+            "t2": {"type": "SolitaryVegetationObject", "geometry": [{
+                "type": "GeometryInstance",
+                "template": 0,
+                "boundaries": [2],
+                "transformationMatrix": [1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0]
+            }]},
+            "b": {"type": "Building", "geometry": [cube_geometry("1")]}
+        },
+        "vertices": [
+            [0, 0, 0], [100, 0, 0], [100, 100, 0], [0, 100, 0],
+            [0, 0, 100], [100, 0, 100], [100, 100, 100], [0, 100, 100]
+        ],
+        "geometry-templates": {
+            "templates": [
+                {"type": "MultiSurface", "lod": "1", "boundaries": [[[0, 2, 1]]]},
+                {"type": "MultiSurface", "lod": "2", "boundaries": [[[0, 1, 2]]]}
+            ],
+            "vertices-templates": [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 1.0]]
+        }
+    }))
+    .unwrap()
+}
+
+#[test]
+fn geometry_instance_anchor_round_trip() {
+    let mut cj = instanced_document();
+    //-- a "values" is not valid for a GeometryInstance, but must not be a problem
+    let t1 = cj.city_objects.get_mut("t1").unwrap();
+    t1.geometry.as_mut().unwrap()[0].material =
+        Some(serde_json::from_value(json!({"colour": {"values": [0]}})).unwrap());
+    let anchor = |cj: &CityJSON, id: &str| -> Vec<i64> {
+        let g = &cj.city_objects[id].geometry.as_ref().unwrap()[0];
+        assert_eq!(g.vertex_indices().len(), 1);
+        cj.vertices[g.vertex_indices()[0]].clone()
+    };
+    //-- cat: the anchor is the only vertex of the feature
+    let f = cj.get_cjfeature("t2").unwrap();
+    assert_eq!(
+        f.city_objects["t2"].geometry.as_ref().unwrap()[0].boundaries,
+        json!([0])
+    );
+    assert_eq!(f.vertices, [cj.vertices[2].clone()]);
+    //-- collect: the anchors are offset after the vertices of the other features
+    let mut cj2 = CityJSON::from_str(&serde_json::to_string(&cj.get_metadata()).unwrap()).unwrap();
+    for id in ["b", "t1", "t2"] {
+        let cjf = serde_json::to_string(&cj.get_cjfeature(id).unwrap()).unwrap();
+        cj2.add_one_cjf(CityJSONFeature::from_str(&cjf).unwrap())
+            .unwrap();
+    }
+    assert_eq!(
+        cj2.city_objects["t2"].geometry.as_ref().unwrap()[0].boundaries,
+        json!([9])
+    );
+    for id in ["t1", "t2"] {
+        assert_eq!(anchor(&cj2, id), anchor(&cj, id));
+    }
+}
+
+#[test]
+fn template_and_feature_materials_round_trip() {
+    let mut cj = instanced_document();
+    let mut a = Appearance::new();
+    for name in ["red", "green", "blue"] {
+        a.add_material(json!({"name": name}));
+    }
+    cj.appearance = Some(a);
+    let gts = cj.geometry_templates.as_mut().unwrap();
+    gts.templates[0].material =
+        Some(serde_json::from_value(json!({"colour": {"values": [2]}})).unwrap());
+    let b = cj.city_objects.get_mut("b").unwrap();
+    b.geometry.as_mut().unwrap()[0].material =
+        Some(serde_json::from_value(json!({"colour": {"values": [0, 0]}})).unwrap());
+    //-- cat then collect
+    let header = cj.get_metadata();
+    assert_eq!(
+        header.appearance.as_ref().unwrap().material_names(),
+        ["blue"]
+    );
+    let mut cj2 = CityJSON::from_str(&serde_json::to_string(&header).unwrap()).unwrap();
+    for id in ["b", "t1", "t2"] {
+        let cjf = serde_json::to_string(&cj.get_cjfeature(id).unwrap()).unwrap();
+        cj2.add_one_cjf(CityJSONFeature::from_str(&cjf).unwrap())
+            .unwrap();
+    }
+    let names = cj2.appearance.as_ref().unwrap().material_names();
+    let name = |g: &Geometry| -> Vec<String> {
+        g.material_indices()
+            .iter()
+            .map(|i| names[*i].clone())
+            .collect()
+    };
+    let gts = cj2.geometry_templates.as_ref().unwrap();
+    assert_eq!(name(&gts.templates[0]), ["blue"]);
+    assert_eq!(
+        name(&cj2.city_objects["b"].geometry.as_ref().unwrap()[0]),
+        ["red"]
+    );
+}
+
+#[test]
+fn instanced_features_round_trip() {
+    let mut cj = instanced_document();
+    //-- the tree is template #1
+    for id in ["t1", "t2"] {
+        cj.city_objects
+            .get_mut(id)
+            .unwrap()
+            .geometry
+            .as_mut()
+            .unwrap()[0]
+            .template = Some(1);
+    }
+    let expanded = |cj: &CityJSON| {
+        let mut cj = cj.clone();
+        cj.expand_templates().unwrap();
+        cj.content_hash()
+    };
+    let header = cj.get_metadata();
+    let gts = header.geometry_templates.clone().unwrap();
+    //-- the features refer to the templates of the 1st line
+    let mut cj2 = header.clone();
+    for id in ["b", "t1", "t2"] {
+        let cjf = cj.get_cjfeature(id).unwrap();
+        assert!(cjf.check_template_indices(Some(&gts)).is_ok());
+        cj2.add_one_cjf(cjf).unwrap();
+    }
+    assert_eq!(expanded(&cj2), expanded(&cj));
+    //-- self-contained features, the templates are restored by collect (only once)
+    let mut cj3 = header.clone();
+    cj3.geometry_templates = None;
+    for id in ["b", "t1", "t2"] {
+        let mut cjf = cj.get_cjfeature(id).unwrap();
+        cjf.localize_templates(&gts);
+        if id != "b" {
+            let local = cjf.geometry_templates.as_ref().unwrap();
+            assert_eq!(local.templates.len(), 1);
+            assert_eq!(local.vertices().len(), 3);
+        }
+        let cjf = CityJSONFeature::from_str(&serde_json::to_string(&cjf).unwrap()).unwrap();
+        assert!(cjf.check_template_indices(None).is_ok());
+        cj3.add_one_cjf(cjf).unwrap();
+    }
+    assert_eq!(cj3.geometry_templates.as_ref().unwrap().templates.len(), 1);
+    assert_eq!(expanded(&cj3), expanded(&cj));
+    //-- a template that does not exist
+    let mut cjf = cj.get_cjfeature("t1").unwrap();
+    cjf.city_objects
+        .get_mut("t1")
+        .unwrap()
+        .geometry
+        .as_mut()
+        .unwrap()[0]
+        .template = Some(5);
+    assert!(cjf.check_template_indices(Some(&gts)).is_err());
+}
+
+#[test]
+fn collect_keeps_the_default_themes_of_the_features() {
+    let cjf = CityJSONFeature::from_value(json!({
+        "type": "CityJSONFeature",
+        "id": "b",
+        "CityObjects": {"b": {"type": "Building", "geometry": [{
+            "type": "MultiSurface",
+            "lod": "2",
+            "boundaries": [[[0, 1, 2]]],
+            "material": {"summer": {"value": 0}, "winter": {"value": 1}}
+        }]}},
+        "vertices": [[0, 0, 0], [1000, 0, 0], [0, 1000, 0]],
+        "appearance": {
+            "materials": [{"name": "green"}, {"name": "white"}],
+            "default-theme-material": "summer"
+        }
+    }))
+    .unwrap();
+    let mut cj = CityJSON::new();
+    assert!(cj.add_one_cjf(cjf).unwrap().is_empty());
+    let a = cj.appearance.as_ref().unwrap();
+    assert_eq!(a.default_theme_material.as_deref(), Some("summer"));
+    //-- and after cat then collect
+    let mut seq: Vec<u8> = Vec::new();
+    cj.write_cityjsonseq(&mut seq, SortingStrategy::Alphabetical)
+        .unwrap();
+    let s = String::from_utf8(seq).unwrap();
+    let mut lines = s.lines();
+    let mut cj2 = CityJSON::from_str(lines.next().unwrap()).unwrap();
+    for l in lines {
+        cj2.add_one_cjf(CityJSONFeature::from_str(l).unwrap())
+            .unwrap();
+    }
+    assert_eq!(
+        cj2.appearance.unwrap().default_theme_material.as_deref(),
+        Some("summer")
+    );
+    //-- the 1st one is kept
+    let mut other = cj.get_cjfeature("b").unwrap();
+    other.id = "c".to_string();
+    let co = other.city_objects.remove("b").unwrap();
+    other.city_objects.insert("c".to_string(), co);
+    other.appearance.as_mut().unwrap().default_theme_material = Some("winter".to_string());
+    let conflicts = cj.add_one_cjf(other).unwrap();
+    assert_eq!(conflicts.len(), 1);
+    assert!(conflicts[0].contains("default-theme-material"));
+    let a = cj.appearance.as_ref().unwrap();
+    assert_eq!(a.default_theme_material.as_deref(), Some("summer"));
+}
+
+#[test]
+fn num_vertices_and_num_features_of_the_fixtures() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("data");
+    let cj = CityJSON::from_str(&fs::read_to_string(dir.join("1b_w_texture.city.json")).unwrap())
+        .unwrap();
+    assert_eq!(cj.num_vertices(), 21);
+    assert_eq!(cj.num_features(), 1);
+    //-- 2 Buildings, each with a BuildingPart
+    let s = fs::read_to_string(dir.join("3dbag_b2.city.jsonl")).unwrap();
+    let mut lines = s.lines();
+    let mut cj = CityJSON::from_str(lines.next().unwrap()).unwrap();
+    for l in lines {
+        cj.add_one_cjf(CityJSONFeature::from_str(l).unwrap())
+            .unwrap();
+    }
+    assert_eq!(cj.city_objects.len(), 4);
+    assert_eq!(cj.num_features(), 2);
+    assert_eq!(cj.num_vertices(), 127);
+}
+
+#[test]
+fn lenient_reads_a_bom_and_trailing_commas() {
+    let s = fs::read_to_string(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("data/1b_w_texture.city.json"),
+    )
+    .unwrap();
+    let bom = format!("\u{feff}{s}");
+    let cj = CityJSON::from_str_lenient(&bom).unwrap();
+    assert_eq!(cj.num_vertices(), 21);
+    //-- a comma before the last "}" and in an array
+    let t = s.trim_end();
+    let commas = format!("{},}}", &t[..t.len() - 1]).replacen("]]", "],]", 1);
+    assert!(CityJSON::from_str(&commas).is_err());
+    let cj = CityJSON::from_str_lenient(&commas).unwrap();
+    assert_eq!(cj.num_vertices(), 21);
+    assert_eq!(cj.city_objects.len(), 1);
+}
+
+#[test]
+fn geometries_iter_mut_visits_every_geometry() {
+    let s =
+        fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("data/3dbag_b2.city.jsonl"))
+            .unwrap();
+    let mut lines = s.lines();
+    let mut cj = CityJSON::from_str(lines.next().unwrap()).unwrap();
+    for l in lines {
+        cj.add_one_cjf(CityJSONFeature::from_str(l).unwrap())
+            .unwrap();
+    }
+    let stats = cj.stats();
+    let per_type: usize = stats.per_type.values().map(|t| t.geometries).sum();
+    assert!(stats.geometries > 0);
+    assert_eq!(per_type, stats.geometries);
+    assert_eq!(cj.geometries_iter_mut().count(), stats.geometries);
+}
+
+#[test]
+fn wkt_of_the_cube_is_a_multipolygon_of_6_faces() {
+    let cj = document(vec![json!({
+        "type": "Solid",
+        "lod": "2",
+        "boundaries": [[
+            [[0, 3, 2, 1]], [[4, 5, 6, 7]], [[0, 1, 5, 4]],
+            [[1, 2, 6, 5]], [[2, 3, 7, 6]], [[3, 0, 4, 7]]
+        ]]
+    })]);
+    let rows = cjseq::conv::wkt::to_wkt_rows(&cj, false, &cjseq::conv::Options::default());
+    assert_eq!(rows.len(), 1);
+    let (id, wkt) = &rows[0];
+    assert_eq!(id, "b");
+    assert!(wkt.starts_with("MULTIPOLYGON Z ((("), "{}", wkt);
+    assert!(wkt.ends_with(")))"));
+    //-- 6 polygons, each a closed ring of 5 points
+    let faces: Vec<&str> = wkt["MULTIPOLYGON Z (".len()..wkt.len() - 1]
+        .split("), (")
+        .collect();
+    assert_eq!(faces.len(), 6);
+    for f in faces {
+        let pts: Vec<&str> = f
+            .trim_matches(|c| c == '(' || c == ')')
+            .split(", ")
+            .collect();
+        assert_eq!(pts.len(), 5);
+        assert_eq!(pts[0], pts[4]);
+        assert_eq!(pts[0].split(' ').count(), 3);
+    }
+    assert!(wkt.contains("1.000 1.000 1.000"));
+    //-- and in 2D
+    let rows = cjseq::conv::wkt::to_wkt_rows(&cj, true, &cjseq::conv::Options::default());
+    assert!(rows[0].1.starts_with("MULTIPOLYGON ((("));
+}
+
+#[cfg(feature = "schema")]
+#[test]
+fn validate_schema_of_a_good_and_a_tampered_fixture() {
+    let s = fs::read_to_string(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("data/1b_w_texture.city.json"),
+    )
+    .unwrap();
+    let cj = CityJSON::from_str(&s).unwrap();
+    assert_eq!(cj.validate_schema(), Ok(()));
+    //-- a vertex with only 2 coordinates
+    let mut v = cj.to_value();
+    v["vertices"][3] = json!([1, 2]);
+    let cj = CityJSON::from_value(v).unwrap();
+    let errors = cj.validate_schema().unwrap_err();
+    assert!(
+        errors.iter().any(|e| e.ends_with("[path:/vertices/3]")),
+        "{:?}",
+        errors
+    );
+}
+
+#[test]
+fn split_by_type_of_a_mixed_fixture() {
+    let s =
+        fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("data/3dbag_b2.city.jsonl"))
+            .unwrap();
+    let mut lines = s.lines();
+    let mut cj = CityJSON::from_str(lines.next().unwrap()).unwrap();
+    for l in lines {
+        cj.add_one_cjf(CityJSONFeature::from_str(l).unwrap())
+            .unwrap();
+    }
+    //-- the 2nd building becomes a bridge, its BuildingPart stays with it
+    let bridge = "NL.IMBAG.Pand.0503100000031927";
+    cj.city_objects.get_mut(bridge).unwrap().thetype = "Bridge".to_string();
+    let docs = cj.split_by_type().unwrap();
+    let mut types: Vec<&String> = docs.keys().collect();
+    types.sort();
+    assert_eq!(types, ["Bridge", "Building"]);
+    assert!(docs["Bridge"].city_objects.contains_key(bridge));
+    assert!(docs["Bridge"]
+        .city_objects
+        .contains_key(&format!("{bridge}-0")));
+    assert_eq!(docs["Building"].city_objects.len(), 2);
+    for d in docs.values() {
+        assert!(d.list_unreferenced_vertices().is_empty());
+        assert!(d.validate_and_repair().1.is_empty());
+        assert_eq!(d.validate_semantics_surface_count(), Ok(()));
+        assert_eq!(d.validate_transform_roundtrip(&cj.transform), Ok(()));
+        assert_eq!(d.validate_reference_system(), Ok(()));
+        #[cfg(feature = "schema")]
+        assert_eq!(d.validate_schema(), Ok(()));
+    }
+    let nv: usize = docs.values().map(|d| d.vertices.len()).sum();
+    assert_eq!(nv, cj.vertices.len());
+    //-- the extent is the one of each file, not that of the whole dataset
+    let extent = |d: &CityJSON| d.metadata.as_ref().unwrap()["geographicalExtent"].clone();
+    for d in docs.values() {
+        let mut expected = d.clone();
+        expected.update_geographical_extent();
+        assert_eq!(extent(d), extent(&expected));
+    }
+    assert_ne!(extent(&docs["Bridge"]), extent(&cj));
+}
+
+#[test]
+fn expand_a_template_used_twice() {
+    let cj = instanced_document();
+    let mut cj2 = cj.clone();
+    cj2.expand_templates().unwrap();
+    assert!(cj2.geometry_templates.is_none());
+    //-- the 3 vertices of the template for each of the 2 instances
+    assert_eq!(cj2.vertices.len(), 8 + 3 + 3);
+    let mut used: Vec<usize> = Vec::new();
+    for id in ["t1", "t2"] {
+        let g = &cj2.city_objects[id].geometry.as_ref().unwrap()[0];
+        assert_eq!(g.thetype, GeometryType::MultiSurface);
+        assert_eq!(g.lod.as_deref(), Some("1"));
+        assert!(g.template.is_none());
+        let vi = g.vertex_indices();
+        assert_eq!(vi.len(), 3);
+        assert!(vi.iter().all(|i| *i >= 8));
+        used.extend(vi);
+    }
+    used.sort();
+    assert_eq!(used, (8..14).collect::<Vec<usize>>());
+    //-- t1 is scaled by 2 at [101, 200, 0]
+    let g = &cj2.city_objects["t1"].geometry.as_ref().unwrap()[0];
+    let last = g.boundaries[0][0][1].as_u64().unwrap() as usize;
+    assert_eq!(
+        cj2.transform.real_coordinates(&cj2.vertices[last]),
+        [101.0, 202.0, 2.0]
+    );
+    //-- an instance of a template that does not exist, nothing is modified
+    let mut bad = cj.clone();
+    bad.city_objects
+        .get_mut("t2")
+        .unwrap()
+        .geometry
+        .as_mut()
+        .unwrap()[0]
+        .template = Some(7);
+    let e = bad.expand_templates().unwrap_err();
+    assert!(e.contains("#t2") && e.contains("template"), "{}", e);
+    assert_eq!(bad.vertices.len(), 8);
+    assert!(bad.geometry_templates.is_some());
+    //-- and a reference point that does not exist
+    let mut bad = cj.clone();
+    bad.city_objects
+        .get_mut("t1")
+        .unwrap()
+        .geometry
+        .as_mut()
+        .unwrap()[0]
+        .boundaries = json!([42]);
+    assert!(bad.expand_templates().unwrap_err().contains("#t1"));
+}
+
+#[test]
+fn content_hash_ignores_the_order() {
+    let a = CityJSON::from_value(json!({
+        "type": "CityJSON",
+        "version": "2.0",
+        "transform": {"scale": [0.001, 0.001, 0.001], "translate": [0.0, 0.0, 0.0]},
+        "CityObjects": {
+            "b1": {"type": "Building", "attributes": {"h": 3, "name": "x"},
+                   "geometry": [{"type": "MultiSurface", "lod": "1", "boundaries": [[[0, 1, 2]]]}]},
+            "b2": {"type": "Building",
+                   "geometry": [{"type": "MultiSurface", "lod": "1", "boundaries": [[[2, 1, 3]]]}]}
+        },
+        "vertices": [[0, 0, 0], [1000, 0, 0], [0, 1000, 0], [1000, 1000, 0]]
+    }))
+    .unwrap();
+    //-- the same, with the CityObjects, attributes and vertices in another order
+    let b = CityJSON::from_value(json!({
+        "vertices": [[1000, 1000, 0], [0, 1000, 0], [1000, 0, 0], [0, 0, 0]],
+        "CityObjects": {
+            "b2": {"geometry": [{"boundaries": [[[1, 2, 0]]], "lod": "1", "type": "MultiSurface"}],
+                   "type": "Building"},
+            "b1": {"attributes": {"name": "x", "h": 3}, "type": "Building",
+                   "geometry": [{"type": "MultiSurface", "lod": "1", "boundaries": [[[3, 2, 1]]]}]}
+        },
+        "transform": {"translate": [0.0, 0.0, 0.0], "scale": [0.001, 0.001, 0.001]},
+        "version": "2.0",
+        "type": "CityJSON"
+    }))
+    .unwrap();
+    assert_eq!(a.content_hash(), b.content_hash());
+    assert_eq!(a.content_hash().len(), 64);
+    let mut c = b.clone();
+    c.city_objects.get_mut("b1").unwrap().thetype = "BuildingPart".to_string();
+    assert_ne!(a.content_hash(), c.content_hash());
+}
+
+#[test]
+fn centroid_of_a_geometry_and_of_a_cityobject() {
+    let cj = document(vec![
+        cube_geometry("1"),
+        //-- vertex 0 is used twice, it counts once
+        json!({"type": "MultiSurface", "lod": "0", "boundaries": [[[0, 1, 3]], [[0, 3, 1]]]}),
+    ]);
+    let gs = cj.city_objects["b"].geometry.as_ref().unwrap();
+    assert_eq!(
+        gs[0].centroid(&cj.vertices, &cj.transform),
+        Some([0.5, 0.5, 0.5])
+    );
+    let c = gs[1].centroid(&cj.vertices, &cj.transform).unwrap();
+    assert!((c[0] - 1.0 / 3.0).abs() < 1e-9 && (c[1] - 1.0 / 3.0).abs() < 1e-9);
+    assert_eq!(c[2], 0.0);
+    //-- the average of those of its geometries
+    let c = cj.city_objects["b"]
+        .centroid(&cj.vertices, &cj.transform)
+        .unwrap();
+    assert!((c[0] - (0.5 + 1.0 / 3.0) / 2.0).abs() < 1e-9);
+    assert!((c[2] - 0.25).abs() < 1e-9);
+    assert_eq!(
+        CityObject::new("Building").centroid(&cj.vertices, &cj.transform),
+        None
+    );
+}
+
+#[test]
+fn validate_extensions_declared_with_an_undeclared_noise() {
+    let mut cj = document(vec![cube_geometry("1")]);
+    let mut n = CityObject::new("+Noise");
+    n.add_geometry(serde_json::from_value(cube_geometry("1")).unwrap());
+    cj.add_co("n".to_string(), n);
+    let errors = cj.validate_extensions_declared().unwrap_err();
+    assert_eq!(
+        errors,
+        ["\"+Noise\" is not a declared Extension [CityObject #n]"]
+    );
+    //-- declared
+    cj.extensions = Some(json!({
+        "Noise": {"url": "https://example.org/noise.ext.json", "version": "2.0"}
+    }));
+    assert_eq!(cj.validate_extensions_declared(), Ok(()));
+}
+
+#[test]
+fn inline_textures_of_a_fixture() {
+    use base64::Engine;
+    //-- a 1x1 PNG
+    let png = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNkYPhfDwAChwGA60e6kgAAAABJRU5ErkJggg==";
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("inline_textures_of_a_fixture");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("appearance")).unwrap();
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(png)
+        .unwrap();
+    fs::write(dir.join("appearance/roof.png"), &bytes).unwrap();
+    fs::write(dir.join("appearance/wall.png"), &bytes).unwrap();
+    let s = fs::read_to_string(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("data/solid_w_texture.city.json"),
+    )
+    .unwrap();
+    let cj = CityJSON::from_str(&s).unwrap();
+    //-- appearance/ground.jpg is missing
+    let mut cj2 = cj.clone();
+    let e = cj2.inline_textures(&dir).unwrap_err();
+    assert_eq!(e.kind(), std::io::ErrorKind::NotFound);
+    assert!(e.to_string().contains("ground.jpg"), "{}", e);
+    fs::write(dir.join("appearance/ground.jpg"), b"jpg").unwrap();
+    let mut cj2 = cj.clone();
+    cj2.inline_textures(&dir).unwrap();
+    let ts = cj2.appearance.unwrap().textures.unwrap();
+    assert_eq!(ts[0]["image"], format!("data:image/png;base64,{png}"));
+    assert_eq!(ts[1]["image"], ts[0]["image"]);
+    assert_eq!(ts[2]["image"], "data:image/jpeg;base64,anBn");
+}
+
+#[test]
+fn feature_from_a_json_value() {
+    let j = json!({
+        "type": "CityJSONFeature",
+        "id": "b",
+        "CityObjects": {
+            "b": {"type": "Building", "attributes": {"h": 12.5},
+                  "geometry": [{"type": "MultiSurface", "lod": "1", "boundaries": [[[0, 1, 2]]]}]}
+        },
+        "vertices": [[0, 0, 0], [1000, 0, 0], [0, 1000, 0]]
+    });
+    let cjf = CityJSONFeature::from_value(j.clone()).unwrap();
+    assert_eq!(cjf.id, "b");
+    assert_eq!(cjf.vertices.len(), 3);
+    assert_eq!(cjf.city_objects["b"].thetype, "Building");
+    assert_eq!(
+        cjf.to_value(),
+        CityJSONFeature::from_str(&j.to_string())
+            .unwrap()
+            .to_value()
+    );
+    assert!(CityJSONFeature::from_value(json!({"type": "CityJSONFeature", "id": 1})).is_err());
+}
+
+#[test]
+fn to_value_round_trips_through_from_value() {
+    let s = fs::read_to_string(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("data/1b_w_texture.city.json"),
+    )
+    .unwrap();
+    let cj = CityJSON::from_str(&s).unwrap();
+    let v = cj.to_value();
+    assert_eq!(v, serde_json::to_value(&cj).unwrap());
+    let cj2 = CityJSON::from_value(v.clone()).unwrap();
+    assert_eq!(cj2.to_value(), v);
+    assert_eq!(cj2.num_vertices(), cj.num_vertices());
+    assert_eq!(cj2.stats().textures, cj.stats().textures);
+    //-- and a feature
+    let cjf = cj.get_cjfeature(cj.sorted_ids()[0]).unwrap();
+    let v = cjf.to_value();
+    assert_eq!(
+        CityJSONFeature::from_value(v.clone()).unwrap().to_value(),
+        v
+    );
+}
+
+#[test]
+fn semantic_surface_types_of_a_building() {
+    let s = fs::read_to_string(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("data/solid_w_texture.city.json"),
+    )
+    .unwrap();
+    let mut cj = CityJSON::from_str(&s).unwrap();
+    let b0 = cj.city_objects.get_mut("b0").unwrap();
+    assert_eq!(
+        b0.semantic_surface_types(),
+        ["GroundSurface", "RoofSurface", "WallSurface"]
+    );
+    //-- the attributes of the surfaces, of all the geometries
+    let g = &mut b0.geometry.as_mut().unwrap()[0];
+    g.semantics.as_mut().unwrap()["surfaces"][1]["slope"] = json!(30);
+    let mut g2 = g.clone();
+    g2.semantics.as_mut().unwrap()["surfaces"][1]["slope"] = json!(45);
+    b0.add_geometry(g2);
+    assert_eq!(
+        b0.aggregate_semantic_attribute("slope"),
+        [json!(30), json!(45)]
+    );
+    assert_eq!(b0.semantic_surface_types().len(), 3);
+    assert!(CityObject::new("Building")
+        .semantic_surface_types()
+        .is_empty());
+}
+
+#[test]
+fn merge_with_a_colliding_id_under_each_strategy() {
+    let cj = document(vec![cube_geometry("2")]);
+    let other = document(vec![cube_geometry("1")]);
+    let lods = |cj: &CityJSON, id: &str| -> Vec<String> {
+        cj.city_objects[id]
+            .geometry
+            .iter()
+            .flatten()
+            .filter_map(|g| g.lod.clone())
+            .collect()
+    };
+    let mut a = cj.clone();
+    let e = a.merge(&other, DupStrategy::Error).unwrap_err();
+    assert_eq!(
+        e,
+        CjseqError::CityJson("duplicate CityObject id \"b\"".to_string())
+    );
+    assert_eq!(a.city_objects.len(), 1);
+    let mut a = cj.clone();
+    a.merge(&other, DupStrategy::KeepFirst).unwrap();
+    assert_eq!(a.city_objects.len(), 1);
+    assert_eq!(lods(&a, "b"), ["2"]);
+    let mut a = cj.clone();
+    a.merge(&other, DupStrategy::SuffixNumeric).unwrap();
+    let mut ids: Vec<&String> = a.city_objects.keys().collect();
+    ids.sort();
+    assert_eq!(ids, ["b", "b_1"]);
+    assert_eq!(lods(&a, "b"), ["2"]);
+    assert_eq!(lods(&a, "b_1"), ["1"]);
+    //-- "b_1" is taken too
+    a.merge(&other, DupStrategy::SuffixNumeric).unwrap();
+    assert!(a.city_objects.contains_key("b_2"));
+    assert_eq!(a.num_features(), 3);
+}
+
+fn resolved_textures(cj: &Value, id: &str) -> Vec<Value> {
+    //-- per ring, the image and the uv-coordinates (null for a ring without texture)
+    fn rings<'a>(v: &'a Value, re: &mut Vec<&'a Vec<Value>>) {
+        if let Value::Array(a) = v {
+            if a.iter().all(|x| !x.is_array()) {
+                re.push(a);
+            } else {
+                a.iter().for_each(|x| rings(x, re));
+            }
+        }
+    }
+    let a = &cj["appearance"];
+    let mut ls: Vec<&Vec<Value>> = Vec::new();
+    for g in cj["CityObjects"][id]["geometry"].as_array().unwrap() {
+        rings(&g["texture"]["summer"]["values"], &mut ls);
+    }
+    ls.iter()
+        .map(|r| match r[0].as_u64() {
+            Some(t) => json!({
+                "image": a["textures"][t as usize]["image"],
+                "uv": r[1..]
+                    .iter()
+                    .map(|i| a["vertices-texture"][i.as_u64().unwrap() as usize].clone())
+                    .collect::<Vec<Value>>()
+            }),
+            None => Value::Null,
+        })
+        .collect()
+}
+
+#[test]
+fn cat_then_collect_of_textured_solids() {
+    let s = fs::read_to_string(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("data/solid_w_texture.city.json"),
+    )
+    .unwrap();
+    let solid = CityJSON::from_str(&s).unwrap();
+    //-- the same, with b1 as a MultiSolid
+    let mut multisolid = solid.clone();
+    let g = &mut multisolid
+        .city_objects
+        .get_mut("b1")
+        .unwrap()
+        .geometry
+        .as_mut()
+        .unwrap()[0];
+    g.thetype = GeometryType::MultiSolid;
+    g.boundaries = json!([g.boundaries]);
+    g.semantics.as_mut().unwrap()["values"] = json!([g.semantics.as_ref().unwrap()["values"]]);
+    let t = g.texture.as_mut().unwrap().get_mut("summer").unwrap();
+    t.values = Some(json!([t.values]));
+    for cj in [solid, multisolid] {
+        let features = cat_in_memory(&cj);
+        for f in &features[1..] {
+            //-- only the textures and the texture vertices used by the feature
+            let id = f["id"].as_str().unwrap();
+            let (nt, nvt) = if id == "b0" { (3, 4) } else { (2, 2) };
+            assert_eq!(f["appearance"]["textures"].as_array().unwrap().len(), nt);
+            assert_eq!(
+                f["appearance"]["vertices-texture"]
+                    .as_array()
+                    .unwrap()
+                    .len(),
+                nvt
+            );
+            assert_eq!(
+                resolved_textures(f, id),
+                resolved_textures(&cj.to_value(), id)
+            );
+        }
+        let mut cj2 = CityJSON::from_value(features[0].clone()).unwrap();
+        for f in &features[1..] {
+            cj2.add_one_cjf(CityJSONFeature::from_value(f.clone()).unwrap())
+                .unwrap();
+        }
+        let (a, b) = (cj.to_value(), cj2.to_value());
+        for id in ["b0", "b1"] {
+            assert_eq!(resolved_textures(&b, id), resolved_textures(&a, id));
+        }
+        let app = cj2.appearance.as_ref().unwrap();
+        assert_eq!(app.textures.as_ref().unwrap().len(), 3);
+        assert_eq!(
+            cj2.city_objects["b1"].geometry.as_ref().unwrap()[0].thetype,
+            cj.city_objects["b1"].geometry.as_ref().unwrap()[0].thetype
+        );
+    }
+}
+
+#[test]
+fn empty_geometries_are_skipped_in_obj() {
+    let empty = json!({"type": "MultiSurface", "lod": "2", "boundaries": []});
+    let mut cj = document(vec![cube_geometry("1"), empty.clone()]);
+    let mut e = CityObject::new("Building");
+    e.add_geometry(serde_json::from_value(empty).unwrap());
+    cj.add_co("e".to_string(), e);
+    let gs = cj.city_objects["b"].geometry.as_ref().unwrap();
+    assert!(!gs[0].is_empty());
+    assert!(gs[1].is_empty());
+    //-- the LoD2 is empty, thus the LoD1 is the highest one
+    let obj = cjseq::conv::obj::to_obj(&cj, None, &cjseq::conv::Options::default());
+    let objects: Vec<&str> = obj.lines().filter(|l| l.starts_with("o ")).collect();
+    assert_eq!(objects, ["o b"]);
+    assert_eq!(obj.lines().filter(|l| l.starts_with("f ")).count(), 2);
+    assert!(!obj.lines().any(|l| l.trim() == "f"));
+    cj.drop_empty_geometries();
+    assert_eq!(cj.city_objects["b"].geometry.as_ref().unwrap().len(), 1);
+    assert!(cj.city_objects["e"].geometry.as_ref().unwrap().is_empty());
+}
+
+#[test]
+fn reference_system_recognition_and_validation() {
+    let rs = ReferenceSystem::from_url("https://www.opengis.net/def/crs/EPSG/0/7415").unwrap();
+    assert_eq!(rs.authority, "EPSG");
+    assert_eq!(rs.code, "7415");
+    assert_eq!(rs.kind(), Some(CrsKind::Compound));
+    assert!(rs.is_projected());
+    assert!(!rs.is_geographic());
+    let rs = ReferenceSystem::from_url("https://www.opengis.net/def/crs/EPSG/0/4979").unwrap();
+    assert!(rs.is_geographic());
+    let rs = ReferenceSystem::from_url("https://www.opengis.net/def/crs/EPSG/0/31370").unwrap();
+    assert_eq!(rs.kind(), None);
+    //-- a malformed authority, a code that is not a number, a missing part
+    assert_eq!(
+        ReferenceSystem::from_url("https://www.opengis.net/def/crs/EPGS/0/7415"),
+        Err("unknown CRS authority \"EPGS\"".to_string())
+    );
+    assert_eq!(
+        ReferenceSystem::from_url("https://www.opengis.net/def/crs/EPSG/0/74a5"),
+        Err("EPSG code \"74a5\" is not a number".to_string())
+    );
+    assert!(ReferenceSystem::from_url("https://www.opengis.net/def/crs/EPSG/7415").is_err());
+    assert!(ReferenceSystem::from_url("EPSG:7415").is_err());
+}
+
+#[test]
+fn sequence_extent_equals_the_bbox_of_the_collected_document() {
+    let s =
+        fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("data/3dbag_b2.city.jsonl"))
+            .unwrap();
+    let e = cjseq::cityjson::sequence_extent(Cursor::new(s.as_bytes()))
+        .unwrap()
+        .unwrap();
+    let mut lines = s.lines();
+    let mut cj = CityJSON::from_str(lines.next().unwrap()).unwrap();
+    for l in lines.filter(|l| !l.trim().is_empty()) {
+        cj.add_one_cjf(CityJSONFeature::from_str(l).unwrap())
+            .unwrap();
+    }
+    cj.remove_duplicate_vertices();
+    cj.retransform();
+    //-- the extent is rounded to the precision of the transform (0.001)
+    let b = cj.bbox().unwrap();
+    for i in 0..6 {
+        assert!((e[i] - b[i]).abs() <= 0.0005, "{:?} {:?}", e, b);
+    }
+    assert!(e[0] < e[3] && e[1] < e[4] && e[2] < e[5]);
+    //-- only a header
+    let header = s.lines().next().unwrap();
+    assert_eq!(
+        cjseq::cityjson::sequence_extent(Cursor::new(header.as_bytes())).unwrap(),
+        None
+    );
+}
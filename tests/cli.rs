@@ -0,0 +1,1355 @@
+//-- the cjseq binary on small inputs
+
+use serde_json::json;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+fn cjseq(args: &[&str], stdin: &[u8]) -> Output {
+    cjseq_in(Path::new("."), args, stdin)
+}
+
+fn cjseq_in(dir: &Path, args: &[&str], stdin: &[u8]) -> Output {
+    let mut c = Command::new(env!("CARGO_BIN_EXE_cjseq"))
+        .current_dir(dir)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    c.stdin.take().unwrap().write_all(stdin).unwrap();
+    c.wait_with_output().unwrap()
+}
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("data")
+        .join(name)
+}
+
+fn seq_with_bad_lines() -> Vec<u8> {
+    //-- lines 3 and 5 are invalid
+    let s = fs::read_to_string(fixture("3dbag_b2.city.jsonl")).unwrap();
+    let lines: Vec<&str> = s.lines().filter(|l| !l.trim().is_empty()).collect();
+    let re = [
+        lines[0],
+        lines[1],
+        "{bad",
+        lines[2],
+        "{\"type\":\"CityJSONFeature\"}",
+    ];
+    format!("{}\n", re.join("\n")).into_bytes()
+}
+
+#[test]
+fn on_error_abort() {
+    for args in [vec!["collect"], vec!["filter", "--cotype", "Building"]] {
+        let mut a = args.clone();
+        a.extend(["--on-error", "abort"]);
+        let o = cjseq(&a, &seq_with_bad_lines());
+        assert!(!o.status.success(), "{:?}", args);
+        assert!(String::from_utf8_lossy(&o.stderr).contains("Error (JSON)"));
+    }
+}
+
+#[test]
+fn on_error_skip() {
+    let o = cjseq(&["collect", "--on-error", "skip"], &seq_with_bad_lines());
+    assert!(o.status.success());
+    let stderr = String::from_utf8_lossy(&o.stderr);
+    assert!(stderr.contains("line 3 skipped"));
+    assert!(stderr.contains("line 5 skipped"));
+    let j: serde_json::Value = serde_json::from_slice(&o.stdout).unwrap();
+    assert_eq!(j["CityObjects"].as_object().unwrap().len(), 4);
+
+    let o = cjseq(
+        &["filter", "--cotype", "Building", "--on-error", "skip"],
+        &seq_with_bad_lines(),
+    );
+    assert!(o.status.success());
+    assert_eq!(String::from_utf8_lossy(&o.stdout).lines().count(), 3);
+}
+
+#[test]
+fn on_error_collect() {
+    let o = cjseq(&["collect", "--on-error", "collect"], &seq_with_bad_lines());
+    assert!(!o.status.success());
+    //-- the output is written, and the report is the 1st line of stderr
+    assert!(serde_json::from_slice::<serde_json::Value>(&o.stdout).is_ok());
+    let stderr = String::from_utf8_lossy(&o.stderr);
+    let report: serde_json::Value = serde_json::from_str(stderr.lines().next().unwrap()).unwrap();
+    let lines: Vec<u64> = report["errors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["line"].as_u64().unwrap())
+        .collect();
+    assert_eq!(lines, vec![3, 5]);
+}
+
+#[test]
+fn on_error_collect_with_update_extent() {
+    //-- the buffered output is written before the report
+    let mut counts: Vec<usize> = Vec::new();
+    for extra in [vec![], vec!["--update-extent"]] {
+        let args = [
+            vec!["filter", "--cotype", "Building", "--on-error", "collect"],
+            extra,
+        ]
+        .concat();
+        let o = cjseq(&args, &seq_with_bad_lines());
+        assert!(!o.status.success());
+        let stderr = String::from_utf8_lossy(&o.stderr);
+        let report: serde_json::Value =
+            serde_json::from_str(stderr.lines().next().unwrap()).unwrap();
+        assert_eq!(report["errors"].as_array().unwrap().len(), 2);
+        counts.push(String::from_utf8_lossy(&o.stdout).lines().count());
+    }
+    assert_eq!(counts, [3, 3]);
+}
+
+#[test]
+fn invalid_utf8_is_an_error_not_a_panic() {
+    let mut input = fs::read(fixture("3dbag_b2.city.jsonl")).unwrap();
+    input.extend(b"{\"type\":\"CityJSONFeature\",\"id\":\"\xff\xfe\"}\n");
+    for args in [
+        vec!["filter", "--random", "1"],
+        vec!["filter", "--cotype", "Building"],
+        vec!["filter", "--bbox", "0", "0", "1", "1"],
+        vec!["filter", "--radius", "0", "0", "1"],
+        vec!["filter", "--expr", "id != \"\""],
+        vec!["collect"],
+    ] {
+        let o = cjseq(&args, &input);
+        let stderr = String::from_utf8_lossy(&o.stderr);
+        assert_eq!(o.status.code(), Some(1), "{:?}", args);
+        assert!(!stderr.contains("panicked"), "{:?}", args);
+        assert!(
+            stderr.contains("line 4: stream did not contain valid UTF-8"),
+            "{:?}: {}",
+            args,
+            stderr
+        );
+    }
+}
+
+#[test]
+fn a_vertex_index_out_of_range_is_an_error() {
+    let seq = [
+        json!({"type": "CityJSON", "version": "2.0",
+               "transform": {"scale": [0.001, 0.001, 0.001], "translate": [0.0, 0.0, 0.0]},
+               "CityObjects": {}, "vertices": []}),
+        json!({"type": "CityJSONFeature", "id": "a",
+               "CityObjects": {"a": {"type": "Building", "geometry": [
+                   {"type": "MultiSurface", "lod": "1", "boundaries": [[[0, 1, 9]]]}]}},
+               "vertices": [[0, 0, 0], [1000, 0, 0], [0, 1000, 0]]}),
+    ]
+    .map(|j| j.to_string())
+    .join("\n");
+    for args in [
+        vec!["collect"],
+        vec!["convert", "--format", "wkt"],
+        vec!["convert", "--format", "obj"],
+        vec!["normalize"],
+        vec!["info", "--per-type"],
+        vec!["hash"],
+        vec!["validate", "--solids"],
+    ] {
+        let o = cjseq(&args, seq.as_bytes());
+        let stderr = String::from_utf8_lossy(&o.stderr);
+        assert_eq!(o.status.code(), Some(1), "{:?}", args);
+        assert!(o.stdout.is_empty(), "{:?}", args);
+        assert!(
+            stderr.contains("feature #a: CityObject #a uses the vertex #9"),
+            "{:?}: {}",
+            args,
+            stderr
+        );
+    }
+}
+
+#[test]
+fn invalid_utf8_in_a_file_and_for_cat() {
+    let mut input = fs::read(fixture("3dbag_b2.city.jsonl")).unwrap();
+    input.extend(b"{\"type\":\"CityJSONFeature\",\"id\":\"\xff\xfe\"}\n");
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("invalid_utf8_in_a_file");
+    fs::create_dir_all(&dir).unwrap();
+    let f = dir.join("bad.city.jsonl");
+    fs::write(&f, &input).unwrap();
+    let o = cjseq(&["collect", "-f", f.to_str().unwrap()], b"");
+    assert_eq!(o.status.code(), Some(1));
+    assert!(
+        String::from_utf8_lossy(&o.stderr).contains("line 4: stream did not contain valid UTF-8")
+    );
+    //-- a line like the others for --on-error
+    let o = cjseq(
+        &["collect", "-f", f.to_str().unwrap(), "--on-error", "skip"],
+        b"",
+    );
+    assert!(o.status.success());
+    assert!(String::from_utf8_lossy(&o.stderr).contains("line 4 skipped"));
+    let o = cjseq(&["collect", "--on-error", "skip"], &input);
+    assert!(o.status.success());
+    let o = cjseq(&["cat"], &input);
+    assert_eq!(o.status.code(), Some(1));
+    assert!(o.stdout.is_empty());
+}
+
+#[test]
+fn collect_faithful_keeps_all_the_vertices() {
+    let seq = fs::read(fixture("3dbag_b2.city.jsonl")).unwrap();
+    let s = String::from_utf8(seq.clone()).unwrap();
+    let mut lines = s.lines().filter(|l| !l.trim().is_empty());
+    let header: serde_json::Value = serde_json::from_str(lines.next().unwrap()).unwrap();
+    let mut vertices: Vec<serde_json::Value> = Vec::new();
+    for l in lines {
+        let f: serde_json::Value = serde_json::from_str(l).unwrap();
+        vertices.extend(f["vertices"].as_array().unwrap().iter().cloned());
+    }
+    let o = cjseq(&["collect", "--faithful"], &seq);
+    assert!(o.status.success());
+    let j: serde_json::Value = serde_json::from_slice(&o.stdout).unwrap();
+    assert_eq!(j["vertices"].as_array().unwrap(), &vertices);
+    assert_eq!(j["transform"], header["transform"]);
+
+    //-- cat then collect --faithful of a CityJSON gives back its vertices
+    let o = cjseq(
+        &[
+            "cat",
+            "-f",
+            fixture("1b_w_texture.city.json").to_str().unwrap(),
+        ],
+        b"",
+    );
+    let o2 = cjseq(&["collect", "--faithful"], &o.stdout);
+    let original: serde_json::Value =
+        serde_json::from_slice(&fs::read(fixture("1b_w_texture.city.json")).unwrap()).unwrap();
+    let j: serde_json::Value = serde_json::from_slice(&o2.stdout).unwrap();
+    assert_eq!(
+        j["vertices"].as_array().unwrap().len(),
+        original["vertices"].as_array().unwrap().len()
+    );
+}
+
+#[test]
+fn reorder_two_features() {
+    let s = fs::read_to_string(fixture("3dbag_b2.city.jsonl")).unwrap();
+    let lines: Vec<&str> = s.lines().filter(|l| !l.trim().is_empty()).collect();
+    let reversed = format!("{}\n{}\n{}\n", lines[0], lines[2], lines[1]);
+    let o = cjseq(&["reorder", "--order", "alphabetical"], reversed.as_bytes());
+    assert!(o.status.success());
+    let out = String::from_utf8(o.stdout).unwrap();
+    let out: Vec<&str> = out.lines().collect();
+    assert_eq!(out.len(), 3);
+    assert_eq!(out[0], lines[0]);
+    let ids: Vec<String> = out[1..]
+        .iter()
+        .map(|l| {
+            serde_json::from_str::<serde_json::Value>(l).unwrap()["id"]
+                .as_str()
+                .unwrap()
+                .to_string()
+        })
+        .collect();
+    let mut sorted = ids.clone();
+    sorted.sort();
+    assert_eq!(ids, sorted);
+    //-- the lines are not modified
+    let mut a = out[1..].to_vec();
+    let mut b = lines[1..].to_vec();
+    a.sort();
+    b.sort();
+    assert_eq!(a, b);
+}
+
+#[test]
+fn collect_a_stream_without_header() {
+    let s = fs::read_to_string(fixture("3dbag_b2.city.jsonl")).unwrap();
+    let features: Vec<&str> = s.lines().filter(|l| !l.trim().is_empty()).skip(1).collect();
+    let input = format!("{}\n", features.join("\n"));
+    let o = cjseq(&["collect"], input.as_bytes());
+    assert!(o.status.success());
+    assert!(String::from_utf8_lossy(&o.stderr).contains("no metadata/CRS/transform found"));
+    let j: serde_json::Value = serde_json::from_slice(&o.stdout).unwrap();
+    assert_eq!(j["version"], "2.0");
+    assert!(j.get("metadata").is_none());
+    let ids: Vec<String> = features
+        .iter()
+        .map(|l| {
+            serde_json::from_str::<serde_json::Value>(l).unwrap()["id"]
+                .as_str()
+                .unwrap()
+                .to_string()
+        })
+        .collect();
+    for id in ids {
+        assert!(j["CityObjects"].get(&id).is_some(), "{}", id);
+    }
+}
+
+#[test]
+fn filter_buildings_without_their_parts() {
+    let seq = fs::read(fixture("3dbag_b2.city.jsonl")).unwrap();
+    let o = cjseq(&["filter", "--cotype", "Building", "--no-children"], &seq);
+    assert!(o.status.success());
+    let out = String::from_utf8(o.stdout).unwrap();
+    let features: Vec<serde_json::Value> = out
+        .lines()
+        .skip(1)
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(features.len(), 2);
+    for f in &features {
+        let cos = f["CityObjects"].as_object().unwrap();
+        assert_eq!(cos.len(), 1);
+        let co = &cos[f["id"].as_str().unwrap()];
+        assert_eq!(co["type"], "Building");
+        assert!(co.get("children").is_none());
+        //-- the vertices were only used by the BuildingParts
+        assert!(f["vertices"].as_array().unwrap().is_empty());
+    }
+}
+
+#[test]
+fn cat_grid_2x2() {
+    //-- a triangle in 3 of the 4 cells of the extent [0, 10], 2 of them in the cell (0, 0)
+    let triangle = |i: usize| json!([{"type": "MultiSurface", "lod": "1", "boundaries": [[[i, i + 1, i + 2]]]}]);
+    let cj = json!({
+        "type": "CityJSON",
+        "version": "2.0",
+        "transform": {"scale": [1.0, 1.0, 1.0], "translate": [0.0, 0.0, 0.0]},
+        "CityObjects": {
+            "a": {"type": "Building", "geometry": triangle(0)},
+            "b": {"type": "Building", "geometry": triangle(3)},
+            "c": {"type": "Building", "geometry": triangle(6)},
+            "d": {"type": "Building", "geometry": triangle(9)}
+        },
+        "vertices": [
+            [0, 0, 0], [1, 0, 0], [0, 1, 0],
+            [9, 0, 0], [10, 0, 0], [10, 1, 0],
+            [9, 10, 0], [10, 9, 0], [10, 10, 0],
+            [4, 0, 0], [5, 0, 0], [4, 1, 0]
+        ]
+    });
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("cat_grid_2x2");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let o = cjseq_in(&dir, &["cat", "--grid", "2x2"], cj.to_string().as_bytes());
+    assert!(o.status.success(), "{}", String::from_utf8_lossy(&o.stderr));
+    let mut names: Vec<String> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().into_string().unwrap())
+        .collect();
+    names.sort();
+    assert_eq!(
+        names,
+        [
+            "out_0_0.city.jsonl",
+            "out_1_0.city.jsonl",
+            "out_1_1.city.jsonl"
+        ]
+    );
+    let ids = |name: &str| -> Vec<String> {
+        let s = fs::read_to_string(dir.join(name)).unwrap();
+        let mut lines = s.lines();
+        let header: serde_json::Value = serde_json::from_str(lines.next().unwrap()).unwrap();
+        assert_eq!(header["type"], "CityJSON");
+        lines
+            .map(|l| {
+                serde_json::from_str::<serde_json::Value>(l).unwrap()["id"]
+                    .as_str()
+                    .unwrap()
+                    .to_string()
+            })
+            .collect()
+    };
+    assert_eq!(ids("out_0_0.city.jsonl"), ["a", "d"]);
+    assert_eq!(ids("out_1_0.city.jsonl"), ["b"]);
+    assert_eq!(ids("out_1_1.city.jsonl"), ["c"]);
+
+    let o = cjseq(&["cat", "--grid", "2by2"], b"");
+    assert!(!o.status.success());
+}
+
+#[test]
+fn output_dir_and_name_template() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("output_dir_and_name_template");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let names = |d: &Path| -> Vec<String> {
+        let mut re: Vec<String> = fs::read_dir(d)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        re.sort();
+        re
+    };
+    let input = fs::read(fixture("1b_w_texture.city.json")).unwrap();
+    let o = cjseq_in(
+        &dir,
+        &[
+            "cat",
+            "--grid",
+            "1x1",
+            "--output-dir",
+            "tiles/a",
+            "--name-template",
+            "tile_{index}.city.jsonl",
+        ],
+        &input,
+    );
+    assert!(o.status.success(), "{}", String::from_utf8_lossy(&o.stderr));
+    assert_eq!(names(&dir.join("tiles/a")), ["tile_0.city.jsonl"]);
+    let o = cjseq_in(
+        &dir,
+        &[
+            "split",
+            "--output-dir",
+            "types",
+            "--name-template",
+            "{type}.city.json",
+        ],
+        &input,
+    );
+    assert!(o.status.success(), "{}", String::from_utf8_lossy(&o.stderr));
+    assert_eq!(names(&dir.join("types")), ["Building.city.json"]);
+    //-- an unknown variable, and no --grid
+    for args in [
+        vec!["split", "--name-template", "{index}.city.json"],
+        vec!["cat", "--output-dir", "x"],
+    ] {
+        let o = cjseq_in(&dir, &args, &input);
+        assert!(!o.status.success());
+    }
+    assert!(!dir.join("x").exists());
+}
+
+#[test]
+fn collect_with_an_external_header() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("collect_with_an_external_header");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let json_of = |o: &Output| -> serde_json::Value { serde_json::from_slice(&o.stdout).unwrap() };
+    let seq = fs::read_to_string(fixture("3dbag_b2.city.jsonl")).unwrap();
+    let (header, features) = seq.split_once('\n').unwrap();
+    let h: serde_json::Value = serde_json::from_str(header).unwrap();
+    fs::write(
+        dir.join("header.json"),
+        serde_json::to_string_pretty(&h).unwrap(),
+    )
+    .unwrap();
+    fs::write(dir.join("features.jsonl"), features).unwrap();
+    //-- as with the CityJSONSeq (the vertices are retransformed only from stdin)
+    let expected = cjseq(
+        &[
+            "collect",
+            "-f",
+            fixture("3dbag_b2.city.jsonl").to_str().unwrap(),
+        ],
+        b"",
+    );
+    assert!(expected.status.success());
+    let o = cjseq_in(
+        &dir,
+        &["collect", "--header", "header.json", "-f", "features.jsonl"],
+        b"",
+    );
+    assert!(o.status.success(), "{}", String::from_utf8_lossy(&o.stderr));
+    assert_eq!(json_of(&o), json_of(&expected));
+    let expected = cjseq(&["collect"], seq.as_bytes());
+    let o = cjseq_in(
+        &dir,
+        &["collect", "--header", "header.json"],
+        features.as_bytes(),
+    );
+    assert!(o.status.success(), "{}", String::from_utf8_lossy(&o.stderr));
+    assert_eq!(json_of(&o), json_of(&expected));
+    //-- a header with CityObjects
+    let o = cjseq(
+        &[
+            "collect",
+            "--header",
+            fixture("1b_w_texture.city.json").to_str().unwrap(),
+        ],
+        features.as_bytes(),
+    );
+    assert!(!o.status.success());
+    assert!(String::from_utf8_lossy(&o.stderr).contains("no CityObjects"));
+}
+
+#[test]
+fn collect_a_json_array_of_features() {
+    let seq = fs::read_to_string(fixture("3dbag_b2.city.jsonl")).unwrap();
+    let lines: Vec<serde_json::Value> = seq
+        .lines()
+        .take(3)
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    let json_of = |o: &Output| -> serde_json::Value {
+        assert!(o.status.success(), "{}", String::from_utf8_lossy(&o.stderr));
+        serde_json::from_slice(&o.stdout).unwrap()
+    };
+    let expected = json_of(&cjseq(
+        &["collect"],
+        lines
+            .iter()
+            .map(|l| l.to_string() + "\n")
+            .collect::<String>()
+            .as_bytes(),
+    ));
+    let array = serde_json::to_string_pretty(&lines).unwrap();
+    let cj = json_of(&cjseq(&["collect", "--array"], array.as_bytes()));
+    assert_eq!(cj, expected);
+    assert_eq!(cj["CityObjects"].as_object().unwrap().len(), 4);
+    //-- only the 2 features, a default header is used
+    let array = serde_json::to_string(&lines[1..]).unwrap();
+    let cj = json_of(&cjseq(&["collect", "--array"], array.as_bytes()));
+    assert_eq!(cj["CityObjects"], expected["CityObjects"]);
+    assert!(!cjseq(&["collect", "--array"], seq.as_bytes())
+        .status
+        .success());
+}
+
+#[test]
+fn header_of_a_cityjsonseq_and_of_a_cityjson() {
+    let seq = fs::read(fixture("3dbag_b2.city.jsonl")).unwrap();
+    let cj = cjseq(&["collect"], &seq);
+    assert!(cj.status.success());
+    for (args, input) in [
+        (
+            vec![
+                "header",
+                "-f",
+                fixture("3dbag_b2.city.jsonl").to_str().unwrap(),
+            ],
+            vec![],
+        ),
+        (vec!["header"], cj.stdout),
+    ] {
+        let o = cjseq(&args, &input);
+        assert!(o.status.success(), "{}", String::from_utf8_lossy(&o.stderr));
+        let s = String::from_utf8(o.stdout).unwrap();
+        assert_eq!(s.lines().count(), 1);
+        let h: serde_json::Value = serde_json::from_str(&s).unwrap();
+        assert!(h["CityObjects"].as_object().unwrap().is_empty());
+        assert_eq!(
+            h["metadata"]["referenceSystem"],
+            "https://www.opengis.net/def/crs/EPSG/0/7415"
+        );
+    }
+}
+
+#[test]
+fn cat_drop_appearance() {
+    for name in ["1b_w_texture.city.json", "solid_w_texture.city.json"] {
+        let f = fixture(name);
+        let o = cjseq(
+            &["cat", "--drop-appearance", "-f", f.to_str().unwrap()],
+            b"",
+        );
+        assert!(o.status.success());
+        let out = String::from_utf8(o.stdout).unwrap();
+        assert!(out.lines().count() > 1);
+        for l in out.lines() {
+            let j: serde_json::Value = serde_json::from_str(l).unwrap();
+            assert!(j.get("appearance").is_none(), "{}", name);
+            for co in j["CityObjects"].as_object().unwrap().values() {
+                for g in co["geometry"].as_array().into_iter().flatten() {
+                    assert!(g.get("material").is_none() && g.get("texture").is_none());
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn collect_two_shards() {
+    let s = fs::read_to_string(fixture("3dbag_b2.city.jsonl")).unwrap();
+    let lines: Vec<&str> = s.lines().filter(|l| !l.trim().is_empty()).collect();
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("collect_two_shards");
+    fs::create_dir_all(&dir).unwrap();
+    let (a, b) = (dir.join("a.city.jsonl"), dir.join("b.city.jsonl"));
+    fs::write(&a, format!("{}\n{}\n", lines[0], lines[1])).unwrap();
+    fs::write(&b, format!("{}\n{}\n", lines[0], lines[2])).unwrap();
+    let o = cjseq(
+        &[
+            "collect",
+            "-f",
+            a.to_str().unwrap(),
+            "-f",
+            b.to_str().unwrap(),
+        ],
+        b"",
+    );
+    assert!(o.status.success(), "{}", String::from_utf8_lossy(&o.stderr));
+    let j: serde_json::Value = serde_json::from_slice(&o.stdout).unwrap();
+    let whole = cjseq(&["collect"], s.as_bytes());
+    let jw: serde_json::Value = serde_json::from_slice(&whole.stdout).unwrap();
+    assert_eq!(j["CityObjects"].as_object().unwrap().len(), 4);
+    assert_eq!(j["CityObjects"], jw["CityObjects"]);
+
+    //-- another transform is refused
+    let mut h: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+    h["transform"]["scale"] = json!([0.01, 0.01, 0.01]);
+    fs::write(&b, format!("{}\n{}\n", h, lines[2])).unwrap();
+    let o = cjseq(
+        &[
+            "collect",
+            "-f",
+            a.to_str().unwrap(),
+            "-f",
+            b.to_str().unwrap(),
+        ],
+        b"",
+    );
+    assert!(!o.status.success());
+    assert!(String::from_utf8_lossy(&o.stderr).contains("the transform differs"));
+}
+
+#[test]
+fn filter_update_extent() {
+    let header = json!({
+        "type": "CityJSON",
+        "version": "2.0",
+        "transform": {"scale": [1.0, 1.0, 1.0], "translate": [0.0, 0.0, 0.0]},
+        "metadata": {"geographicalExtent": [0.0, 0.0, 0.0, 100.0, 100.0, 100.0]},
+        "CityObjects": {},
+        "vertices": []
+    });
+    let feature = |id: &str, vs: serde_json::Value| {
+        let mut cos = serde_json::Map::new();
+        cos.insert(
+            id.to_string(),
+            json!({"type": "Building", "geometry": [{"type": "MultiSurface", "lod": "1", "boundaries": [[[0, 1, 2]]]}]}),
+        );
+        json!({"type": "CityJSONFeature", "id": id, "CityObjects": cos, "vertices": vs})
+    };
+    let seq = [
+        header,
+        feature("a", json!([[0, 0, 0], [1, 0, 0], [0, 1, 0]])),
+        feature("b", json!([[50, 50, 5], [51, 50, 5], [50, 51, 6]])),
+    ]
+    .iter()
+    .map(|j| format!("{}\n", j))
+    .collect::<String>();
+    let args = ["filter", "--bbox", "40", "40", "60", "60"];
+    let o = cjseq(&[&args[..], &["--update-extent"]].concat(), seq.as_bytes());
+    assert!(o.status.success());
+    let out = String::from_utf8(o.stdout).unwrap();
+    let lines: Vec<serde_json::Value> = out
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[1]["id"], "b");
+    assert_eq!(
+        lines[0]["metadata"]["geographicalExtent"],
+        json!([50.0, 50.0, 5.0, 51.0, 51.0, 6.0])
+    );
+    //-- without the option the header is not modified
+    let o = cjseq(&args, seq.as_bytes());
+    let out = String::from_utf8(o.stdout).unwrap();
+    let j: serde_json::Value = serde_json::from_str(out.lines().next().unwrap()).unwrap();
+    assert_eq!(j["metadata"]["geographicalExtent"][3], 100.0);
+}
+
+#[test]
+fn convert_to_obj_triangulated() {
+    let f = fixture("1b_w_texture.city.json");
+    let o = cjseq(
+        &["convert", "-i", f.to_str().unwrap(), "--format", "obj"],
+        b"",
+    );
+    assert!(o.status.success());
+    let out = String::from_utf8(o.stdout).unwrap();
+    let faces = |s: &str| s.lines().filter(|l| l.starts_with("f ")).count();
+    let n = faces(&out);
+    let o = cjseq(
+        &[
+            "convert",
+            "-i",
+            f.to_str().unwrap(),
+            "--format",
+            "obj",
+            "--triangulate",
+        ],
+        b"",
+    );
+    assert!(o.status.success());
+    let out = String::from_utf8(o.stdout).unwrap();
+    assert!(faces(&out) > n);
+    assert!(out
+        .lines()
+        .filter(|l| l.starts_with("f "))
+        .all(|l| l.split_whitespace().count() == 4));
+    //-- the former name of the subcommand
+    let o = cjseq(
+        &["export", "-f", f.to_str().unwrap(), "--format", "obj"],
+        b"",
+    );
+    assert!(o.status.success());
+}
+
+#[test]
+fn convert_refused_keeps_the_output_file() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("convert_refused");
+    fs::create_dir_all(&dir).unwrap();
+    let out = dir.join("out.parquet");
+    let seq = fixture("3dbag_b2.city.jsonl");
+    let bad = dir.join("bad.city.json");
+    fs::write(&bad, "{bad").unwrap();
+    for (args, input) in [
+        (vec!["--format", "parquet", "--lod", "2"], &seq),
+        (vec!["--format", "wkt"], &bad),
+        (vec!["--format", "obj", "--2d"], &seq),
+        (vec!["--format", "wkt", "--local-origin"], &seq),
+    ] {
+        fs::write(&out, "previous").unwrap();
+        let args = [
+            &[
+                "convert",
+                "-i",
+                input.to_str().unwrap(),
+                "-o",
+                out.to_str().unwrap(),
+            ],
+            &args[..],
+        ]
+        .concat();
+        let o = cjseq(&args, b"");
+        assert!(!o.status.success(), "{:?}", args);
+        assert_eq!(fs::read_to_string(&out).unwrap(), "previous", "{:?}", args);
+    }
+}
+
+#[test]
+fn convert_a_sequence_to_geojson() {
+    let seq = fs::read(fixture("3dbag_b2.city.jsonl")).unwrap();
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let o = cjseq_in(
+        dir,
+        &[
+            "convert",
+            "--format",
+            "geojson",
+            "--2d",
+            "--precision",
+            "1",
+            "-o",
+            "out.geojson",
+        ],
+        &seq,
+    );
+    assert!(o.status.success());
+    let j: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(dir.join("out.geojson")).unwrap()).unwrap();
+    assert_eq!(j["type"], "FeatureCollection");
+    let features = j["features"].as_array().unwrap();
+    //-- only the BuildingParts have geometries
+    assert_eq!(features.len(), 2);
+    for f in features {
+        assert_eq!(f["properties"]["type"], "BuildingPart");
+        assert_eq!(f["geometry"]["type"], "MultiPolygon");
+        let p = &f["geometry"]["coordinates"][0][0][0];
+        assert_eq!(p.as_array().unwrap().len(), 2);
+        let x = p[0].as_f64().unwrap();
+        assert_eq!((x * 10.0).round() / 10.0, x);
+    }
+}
+
+#[test]
+fn dry_run_reports_without_writing() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("dry_run");
+    fs::create_dir_all(&dir).unwrap();
+    let _ = fs::remove_file(dir.join("ids.json"));
+    let f = fixture("1b_w_texture.city.json");
+    let args = [
+        "cat",
+        "-f",
+        f.to_str().unwrap(),
+        "--drop-appearance",
+        "--compact-indices",
+        "--id-map",
+        "ids.json",
+        "--dry-run",
+    ];
+    let o = cjseq_in(&dir, &args, b"");
+    assert!(o.status.success());
+    assert_eq!(
+        String::from_utf8(o.stdout).unwrap(),
+        "textures: 8 -> 0 (-8)\nvertices-texture: 35 -> 0 (-35)\n"
+    );
+    assert!(!dir.join("ids.json").exists());
+    let o = cjseq(&["normalize", "-f", f.to_str().unwrap(), "--dry-run"], b"");
+    assert_eq!(
+        String::from_utf8(o.stdout).unwrap(),
+        "nothing would change\n"
+    );
+}
+
+#[test]
+fn convert_to_obj_with_vertex_colors() {
+    //-- 2 surfaces with different materials sharing an edge
+    let cj = json!({
+        "type": "CityJSON",
+        "version": "2.0",
+        "transform": {"scale": [1.0, 1.0, 1.0], "translate": [0.0, 0.0, 0.0]},
+        "CityObjects": {"b": {"type": "Building", "geometry": [{
+            "type": "MultiSurface",
+            "lod": "2",
+            "boundaries": [[[0, 1, 2, 3]], [[1, 4, 5, 2]]],
+            "material": {"colours": {"values": [0, 1]}}
+        }]}},
+        "vertices": [[0, 0, 0], [1, 0, 0], [1, 1, 0], [0, 1, 0], [2, 0, 0], [2, 1, 0]],
+        "appearance": {"materials": [
+            {"name": "red", "diffuseColor": [1.0, 0.0, 0.0]},
+            {"name": "blue", "diffuseColor": [0.0, 0.0, 1.0]}
+        ]}
+    });
+    let o = cjseq(
+        &["convert", "--format", "obj", "--vertex-colors"],
+        cj.to_string().as_bytes(),
+    );
+    assert!(o.status.success());
+    let out = String::from_utf8(o.stdout).unwrap();
+    let vs: Vec<&str> = out.lines().filter(|l| l.starts_with("v ")).collect();
+    //-- the 2 vertices of the shared edge are output once per colour
+    assert_eq!(vs.len(), 8);
+    assert_eq!(vs.iter().filter(|l| l.ends_with(" 1 0 0")).count(), 4);
+    assert_eq!(vs.iter().filter(|l| l.ends_with(" 0 0 1")).count(), 4);
+    assert!(out.contains("f 1 2 3 4\nf 5 6 7 8\n"));
+}
+
+#[test]
+fn check_reports_the_broken_line() {
+    let s = fs::read_to_string(fixture("3dbag_b2.city.jsonl")).unwrap();
+    let lines: Vec<&str> = s.lines().collect();
+    let broken = format!("{}\n{}\n{{bad\n{}\n", lines[0], lines[1], lines[2]);
+    let o = cjseq(&["check"], broken.as_bytes());
+    assert!(!o.status.success());
+    assert!(String::from_utf8_lossy(&o.stderr).contains("line 3 is not valid JSON"));
+    let o = cjseq(&["check"], s.as_bytes());
+    assert!(o.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&o.stdout),
+        "3 lines, all valid JSON\n"
+    );
+}
+
+#[test]
+fn cat_compact_indices_with_an_id_map() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("cat_compact_indices_with_an_id_map");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let cj = cjseq(
+        &["collect"],
+        &fs::read(fixture("3dbag_b2.city.jsonl")).unwrap(),
+    );
+    assert!(cj.status.success());
+    let original: serde_json::Value = serde_json::from_slice(&cj.stdout).unwrap();
+    let o = cjseq_in(
+        &dir,
+        &["cat", "--compact-indices", "--id-map", "map.json"],
+        &cj.stdout,
+    );
+    assert!(o.status.success(), "{}", String::from_utf8_lossy(&o.stderr));
+    let map: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_str(&fs::read_to_string(dir.join("map.json")).unwrap()).unwrap();
+    let back = |id: &str| map[id].as_str().unwrap().to_string();
+    let out = String::from_utf8(o.stdout).unwrap();
+    let mut ids: Vec<String> = Vec::new();
+    for l in out.lines().skip(1) {
+        let f: serde_json::Value = serde_json::from_str(l).unwrap();
+        for (id, co) in f["CityObjects"].as_object().unwrap() {
+            assert!(id.parse::<usize>().is_ok(), "{}", id);
+            //-- the links are renamed too
+            let links = co["children"]
+                .as_array()
+                .into_iter()
+                .chain(co["parents"].as_array());
+            for (new, old) in links.flatten().zip(
+                original["CityObjects"][back(id)]["children"]
+                    .as_array()
+                    .into_iter()
+                    .chain(original["CityObjects"][back(id)]["parents"].as_array())
+                    .flatten(),
+            ) {
+                assert_eq!(&back(new.as_str().unwrap()), old);
+            }
+            ids.push(back(id));
+        }
+    }
+    ids.sort();
+    let mut expected: Vec<String> = original["CityObjects"]
+        .as_object()
+        .unwrap()
+        .keys()
+        .cloned()
+        .collect();
+    expected.sort();
+    assert_eq!(ids, expected);
+}
+
+#[test]
+fn append_then_collect() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("append_then_collect");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let s = fs::read_to_string(fixture("3dbag_b2.city.jsonl")).unwrap();
+    let lines: Vec<&str> = s.lines().filter(|l| !l.trim().is_empty()).collect();
+    let file = dir.join("seq.city.jsonl");
+    fs::write(&file, format!("{}\n{}\n", lines[0], lines[1])).unwrap();
+    let o = cjseq(
+        &["append", file.to_str().unwrap()],
+        format!("{}\n{}\n", lines[0], lines[2]).as_bytes(),
+    );
+    assert!(o.status.success(), "{}", String::from_utf8_lossy(&o.stderr));
+    let o = cjseq(&["collect", "-f", file.to_str().unwrap()], b"");
+    assert!(o.status.success());
+    let j: serde_json::Value = serde_json::from_slice(&o.stdout).unwrap();
+    assert_eq!(j["CityObjects"].as_object().unwrap().len(), 4);
+    assert_eq!(j["vertices"].as_array().unwrap().len(), 127);
+    //-- another CRS is refused and the file is not modified
+    let before = fs::read(&file).unwrap();
+    let mut header: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+    header["metadata"]["referenceSystem"] = json!("https://www.opengis.net/def/crs/EPSG/0/28992");
+    let o = cjseq(
+        &["append", file.to_str().unwrap()],
+        format!("{}\n{}\n", header, lines[2]).as_bytes(),
+    );
+    assert!(!o.status.success());
+    assert!(String::from_utf8_lossy(&o.stderr).contains("referenceSystem"));
+    assert_eq!(fs::read(&file).unwrap(), before);
+    //-- a file whose last line has no newline
+    fs::write(&file, format!("{}\n{}", lines[0], lines[1])).unwrap();
+    let o = cjseq(
+        &["append", file.to_str().unwrap()],
+        format!("{}\n{}\n", lines[0], lines[2]).as_bytes(),
+    );
+    assert!(o.status.success(), "{}", String::from_utf8_lossy(&o.stderr));
+    let o = cjseq(&["collect", "-f", file.to_str().unwrap()], b"");
+    assert!(o.status.success(), "{}", String::from_utf8_lossy(&o.stderr));
+    let j: serde_json::Value = serde_json::from_slice(&o.stdout).unwrap();
+    assert_eq!(j["CityObjects"].as_object().unwrap().len(), 4);
+}
+
+#[test]
+fn collect_canonical_vertices_in_two_orders() {
+    let s = fs::read_to_string(fixture("3dbag_b2.city.jsonl")).unwrap();
+    let lines: Vec<&str> = s.lines().filter(|l| !l.trim().is_empty()).collect();
+    let vertices = |seq: String| {
+        let o = cjseq(&["collect", "--canonical-vertices"], seq.as_bytes());
+        assert!(o.status.success());
+        let j: serde_json::Value = serde_json::from_slice(&o.stdout).unwrap();
+        (
+            serde_json::to_string(&j["vertices"]).unwrap(),
+            j["transform"].clone(),
+        )
+    };
+    let a = vertices(format!("{}\n{}\n{}\n", lines[0], lines[1], lines[2]));
+    let b = vertices(format!("{}\n{}\n{}\n", lines[0], lines[2], lines[1]));
+    assert_eq!(a, b);
+    //-- while the default depends on the order
+    let o = cjseq(
+        &["collect"],
+        format!("{}\n{}\n{}\n", lines[0], lines[2], lines[1]).as_bytes(),
+    );
+    let j: serde_json::Value = serde_json::from_slice(&o.stdout).unwrap();
+    assert_ne!(serde_json::to_string(&j["vertices"]).unwrap(), a.0);
+}
+
+#[test]
+fn obj_with_a_local_origin() {
+    let seq = fs::read(fixture("3dbag_b2.city.jsonl")).unwrap();
+    let s = String::from_utf8(seq.clone()).unwrap();
+    let header: serde_json::Value = serde_json::from_str(s.lines().next().unwrap()).unwrap();
+    let t = &header["transform"]["translate"];
+    let coords = |obj: &str| -> Vec<f64> {
+        obj.lines()
+            .filter_map(|l| l.strip_prefix("v "))
+            .flat_map(|l| l.split(' ').map(|c| c.parse::<f64>().unwrap()))
+            .collect()
+    };
+    let o = cjseq(&["convert", "--format", "obj"], &seq);
+    assert!(o.status.success());
+    let global = coords(&String::from_utf8(o.stdout).unwrap());
+    assert!(global.iter().any(|c| c.abs() > 10000.0));
+    let o = cjseq(&["convert", "--format", "obj", "--local-origin"], &seq);
+    assert!(o.status.success());
+    let obj = String::from_utf8(o.stdout).unwrap();
+    let origin: Vec<f64> = obj
+        .lines()
+        .find_map(|l| l.strip_prefix("# origin: "))
+        .unwrap()
+        .split(' ')
+        .map(|c| c.parse().unwrap())
+        .collect();
+    for i in 0..3 {
+        assert_eq!(origin[i], t[i].as_f64().unwrap());
+    }
+    let local = coords(&obj);
+    assert_eq!(local.len(), global.len());
+    assert!(local.iter().all(|c| c.abs() < 1000.0));
+    //-- the offset gives back the coordinates
+    for (i, (l, g)) in local.iter().zip(&global).enumerate() {
+        assert!((l + origin[i % 3] - g).abs() < 1e-3);
+    }
+}
+
+#[test]
+fn empty_stdin_is_an_error() {
+    for cmd in ["cat", "collect"] {
+        let o = cjseq(&[cmd], b"");
+        assert!(!o.status.success(), "{}", cmd);
+        assert!(o.stdout.is_empty(), "{}", cmd);
+        assert!(
+            String::from_utf8_lossy(&o.stderr).contains("no input received"),
+            "{}",
+            cmd
+        );
+    }
+    //-- and an empty file
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("empty_stdin_is_an_error");
+    fs::create_dir_all(&dir).unwrap();
+    let f = dir.join("empty.city.json");
+    fs::write(&f, "").unwrap();
+    let o = cjseq(&["cat", "-f", f.to_str().unwrap()], b"");
+    assert!(!o.status.success());
+    assert!(String::from_utf8_lossy(&o.stderr).contains("no input received"));
+    //-- and nothing to append
+    let s = fs::read_to_string(fixture("3dbag_b2.city.jsonl")).unwrap();
+    let seq = dir.join("seq.city.jsonl");
+    fs::write(&seq, &s).unwrap();
+    let o = cjseq(&["append", seq.to_str().unwrap()], b"");
+    assert!(!o.status.success());
+    assert!(String::from_utf8_lossy(&o.stderr).contains("no input received"));
+    assert_eq!(fs::read_to_string(&seq).unwrap(), s);
+}
+
+#[test]
+fn cat_strip_extensions() {
+    let cj = json!({
+        "type": "CityJSON",
+        "version": "2.0",
+        "transform": {"scale": [0.001, 0.001, 0.001], "translate": [0.0, 0.0, 0.0]},
+        "extensions": {"Noise": {"url": "https://example.org/noise.ext.json", "version": "2.0"}},
+        "CityObjects": {
+            "b": {"type": "Building", "attributes": {"h": 3.0, "+noise-level": 54},
+                  "geometry": [{"type": "MultiSurface", "lod": "1", "boundaries": [[[0, 1, 2]]]}]},
+            "n": {"type": "+Noise",
+                  "geometry": [{"type": "MultiSurface", "lod": "1", "boundaries": [[[1, 2, 3]]]}]}
+        },
+        "vertices": [[0, 0, 0], [1000, 0, 0], [0, 1000, 0], [0, 0, 5000]]
+    });
+    let input = serde_json::to_vec(&cj).unwrap();
+    let o = cjseq(&["cat"], &input);
+    assert_eq!(String::from_utf8(o.stdout).unwrap().lines().count(), 3);
+    let o = cjseq(&["cat", "--strip-extensions"], &input);
+    assert!(o.status.success(), "{}", String::from_utf8_lossy(&o.stderr));
+    let out = String::from_utf8(o.stdout).unwrap();
+    let lines: Vec<serde_json::Value> = out
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].get("extensions").is_none());
+    assert_eq!(lines[1]["id"], "b");
+    assert_eq!(
+        lines[1]["CityObjects"]["b"]["attributes"],
+        json!({"h": 3.0})
+    );
+    //-- the vertex only used by "n" is gone
+    assert_eq!(lines[1]["vertices"].as_array().unwrap().len(), 3);
+}
+
+#[test]
+fn collect_drop_attr_and_keep_attr() {
+    let seq = fs::read(fixture("3dbag_b2.city.jsonl")).unwrap();
+    //-- the attribute keys of each CityObject that has attributes
+    let attributes = |args: &[&str]| -> Vec<(String, Vec<String>)> {
+        let o = cjseq(args, &seq);
+        assert!(o.status.success(), "{}", String::from_utf8_lossy(&o.stderr));
+        let j: serde_json::Value = serde_json::from_slice(&o.stdout).unwrap();
+        let mut re: Vec<(String, Vec<String>)> = j["CityObjects"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .filter_map(|(id, co)| co["attributes"].as_object().map(|m| (id, m)))
+            .filter(|(_, m)| !m.is_empty())
+            .map(|(id, m)| (id.clone(), m.keys().cloned().collect()))
+            .collect();
+        re.sort();
+        re
+    };
+    let all = attributes(&["collect"]);
+    assert_eq!(all.len(), 2);
+    assert!(all
+        .iter()
+        .all(|(_, ks)| ks.contains(&"b3_pw_bron".to_string())));
+    let dropped = attributes(&["collect", "--drop-attr", "b3_pw_bron"]);
+    assert_eq!(dropped.len(), 2);
+    for ((id, ks), (id2, before)) in dropped.iter().zip(&all) {
+        assert_eq!(id, id2);
+        assert!(!ks.contains(&"b3_pw_bron".to_string()));
+        assert_eq!(ks.len(), before.len() - 1);
+    }
+    let kept = attributes(&[
+        "collect",
+        "--keep-attr",
+        "status",
+        "--keep-attr",
+        "identificatie",
+    ]);
+    assert_eq!(kept.len(), 2);
+    for (_, ks) in kept {
+        assert_eq!(ks, ["identificatie", "status"]);
+    }
+}
+
+#[test]
+fn collect_dedup_scope_object_keeps_touching_buildings_apart() {
+    let cube = |x: i64| {
+        json!({
+            "type": "CityJSONFeature",
+            "id": format!("b{x}"),
+            "CityObjects": {format!("b{x}"): {"type": "Building", "geometry": [{
+                "type": "MultiSurface", "lod": "1",
+                "boundaries": [[[0, 3, 2, 1]], [[4, 5, 6, 7]], [[1, 2, 6, 5]], [[0, 4, 7, 3]]]
+            }]}},
+            "vertices": [
+                [x, 0, 0], [x + 1000, 0, 0], [x + 1000, 1000, 0], [x, 1000, 0],
+                [x, 0, 1000], [x + 1000, 0, 1000], [x + 1000, 1000, 1000], [x, 1000, 1000]
+            ]
+        })
+    };
+    let header = json!({
+        "type": "CityJSON", "version": "2.0", "CityObjects": {}, "vertices": [],
+        "transform": {"scale": [0.001, 0.001, 0.001], "translate": [0.0, 0.0, 0.0]}
+    });
+    //-- b1000 touches b0, they share 4 vertices
+    let seq = format!("{}\n{}\n{}\n", header, cube(0), cube(1000));
+    let collect = |args: &[&str]| -> serde_json::Value {
+        let o = cjseq(args, seq.as_bytes());
+        assert!(o.status.success(), "{}", String::from_utf8_lossy(&o.stderr));
+        serde_json::from_slice(&o.stdout).unwrap()
+    };
+    let indices = |j: &serde_json::Value, id: &str| -> Vec<u64> {
+        let mut re: Vec<u64> = j["CityObjects"][id]["geometry"][0]["boundaries"]
+            .to_string()
+            .split(|c: char| !c.is_ascii_digit())
+            .filter_map(|x| x.parse().ok())
+            .collect();
+        re.sort();
+        re.dedup();
+        re
+    };
+    let j = collect(&["collect"]);
+    assert_eq!(j["vertices"].as_array().unwrap().len(), 12);
+    let j = collect(&["collect", "--dedup-scope", "object"]);
+    assert_eq!(j["vertices"].as_array().unwrap().len(), 16);
+    let (a, b) = (indices(&j, "b0"), indices(&j, "b1000"));
+    assert_eq!(a.len(), 8);
+    assert_eq!(b.len(), 8);
+    assert!(a.iter().all(|i| !b.contains(i)));
+}
+
+#[test]
+fn cat_pretty_features_parse_back() {
+    let f = fixture("solid_w_texture.city.json");
+    let values = |args: &[&str]| -> Vec<serde_json::Value> {
+        let o = cjseq(args, b"");
+        assert!(o.status.success());
+        let mut re: Vec<serde_json::Value> = serde_json::Deserializer::from_slice(&o.stdout)
+            .into_iter::<serde_json::Value>()
+            .map(|v| v.unwrap())
+            .collect();
+        re[1..].sort_by_key(|v| v["id"].as_str().unwrap().to_string());
+        re
+    };
+    let o = cjseq(
+        &["cat", "--pretty-features", "-f", f.to_str().unwrap()],
+        b"",
+    );
+    assert!(String::from_utf8_lossy(&o.stderr).contains("not a valid CityJSONSeq"));
+    assert!(String::from_utf8(o.stdout).unwrap().lines().count() > 3);
+    let pretty = values(&["cat", "--pretty-features", "-f", f.to_str().unwrap()]);
+    let lines = values(&["cat", "-f", f.to_str().unwrap()]);
+    assert_eq!(pretty.len(), 3);
+    assert_eq!(pretty, lines);
+}
+
+#[test]
+fn normalize_is_idempotent() {
+    for name in [
+        "3dbag_b2.city.jsonl",
+        "1b_w_texture.city.json",
+        "solid_w_texture.city.json",
+    ] {
+        let once = cjseq(&["normalize", "-f", fixture(name).to_str().unwrap()], b"");
+        assert!(once.status.success(), "{}", name);
+        let twice = cjseq(&["normalize"], &once.stdout);
+        assert!(twice.status.success(), "{}", name);
+        assert_eq!(
+            String::from_utf8(twice.stdout).unwrap(),
+            String::from_utf8(once.stdout).unwrap(),
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn filter_expr() {
+    let seq = fs::read(fixture("3dbag_b2.city.jsonl")).unwrap();
+    let ids = |expr: &str| -> Vec<String> {
+        let o = cjseq(&["filter", "--expr", expr], &seq);
+        assert!(o.status.success(), "{}", String::from_utf8_lossy(&o.stderr));
+        String::from_utf8(o.stdout)
+            .unwrap()
+            .lines()
+            .skip(1)
+            .map(|l| {
+                let f: serde_json::Value = serde_json::from_str(l).unwrap();
+                f["id"].as_str().unwrap().to_string()
+            })
+            .collect()
+    };
+    //-- the heights are 13.44 and 9.83
+    assert_eq!(
+        ids("attr.b3_h_dak_max > 10"),
+        ["NL.IMBAG.Pand.0503100000031927"]
+    );
+    assert_eq!(ids("attr.b3_h_dak_max <= 10").len(), 1);
+    assert_eq!(
+        ids(r#"type == "Building" && attr.oorspronkelijkbouwjaar < 1940"#),
+        ["NL.IMBAG.Pand.0503100000031927"]
+    );
+    assert_eq!(
+        ids(r#"type == "Bridge" || (attr.b3_dak_type == "slanted" && attr.b3_h_dak_max < 10)"#),
+        ["NL.IMBAG.Pand.0503100000028341"]
+    );
+    assert!(ids(r#"type == "Bridge" && attr.b3_h_dak_max > 0"#).is_empty());
+    //-- the parse errors
+    for (expr, e) in [
+        ("attr.b3_h_dak_max > ", "unexpected end of the expression"),
+        (r#"(type == "Building""#, "missing ')' in the expression"),
+    ] {
+        let o = cjseq(&["filter", "--expr", expr], &seq);
+        assert!(!o.status.success());
+        assert!(o.stdout.is_empty());
+        assert!(String::from_utf8_lossy(&o.stderr).contains(e), "{}", expr);
+    }
+}
+
+#[test]
+fn collect_files_of_different_versions() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("collect_files_of_different_versions");
+    fs::create_dir_all(&dir).unwrap();
+    let s = fs::read_to_string(fixture("3dbag_b2.city.jsonl")).unwrap();
+    let lines: Vec<&str> = s.lines().filter(|l| !l.trim().is_empty()).collect();
+    let mut header: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+    header["version"] = json!("1.1");
+    let f = dir.join("v11.city.jsonl");
+    fs::write(&f, format!("{}\n{}\n", header, lines[2])).unwrap();
+    let o = cjseq(
+        &[
+            "collect",
+            "-f",
+            fixture("3dbag_b2.city.jsonl").to_str().unwrap(),
+            "-f",
+            f.to_str().unwrap(),
+        ],
+        b"",
+    );
+    assert!(!o.status.success());
+    assert!(String::from_utf8_lossy(&o.stderr)
+        .contains("version mismatch: expected CityJSON v2.0, found v1.1"));
+}
+
+#[test]
+fn cat_local_templates() {
+    let instance = |anchor: u64| {
+        json!({"type": "SolitaryVegetationObject", "geometry": [{
+            "type": "GeometryInstance",
+            "template": 1,
+            "boundaries": [anchor],
+            "transformationMatrix": [2.0, 0.0, 0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0, 0.0, 1.0]
+        }]})
+    };
+    let cj = json!({
+        "type": "CityJSON",
+        "version": "2.0",
+        "transform": {"scale": [0.01, 0.01, 0.01], "translate": [100.0, 200.0, 0.0]},
+        "CityObjects": {
+            "b": {"type": "Building", "geometry": [
+                {"type": "MultiSurface", "lod": "1", "boundaries": [[[0, 1, 2]]]}
+            ]},
+            "t1": instance(1),
+            "t2": instance(2)
+        },
+        "vertices": [[0, 0, 0], [100, 0, 0], [100, 100, 0]],
+        "geometry-templates": {
+            "templates": [
+                {"type": "MultiSurface", "lod": "1", "boundaries": [[[0, 2, 1]]]},
+                {"type": "MultiSurface", "lod": "2", "boundaries": [[[1, 2, 3]]]}
+            ],
+            "vertices-templates": [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 1.0], [1.0, 1.0, 1.0]]
+        }
+    });
+    let input = serde_json::to_vec(&cj).unwrap();
+    let lines = |o: &Output| -> Vec<serde_json::Value> {
+        assert!(o.status.success(), "{}", String::from_utf8_lossy(&o.stderr));
+        String::from_utf8_lossy(&o.stdout)
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect()
+    };
+    //-- by default, only the 1st line has templates (as in the specifications)
+    let default = cjseq(&["cat"], &input);
+    let ls = lines(&default);
+    assert_eq!(
+        ls[0]["geometry-templates"]["templates"]
+            .as_array()
+            .unwrap()
+            .len(),
+        2
+    );
+    assert!(ls[1..]
+        .iter()
+        .all(|f| f.get("geometry-templates").is_none()));
+    let local = cjseq(&["cat", "--local-templates"], &input);
+    let ls = lines(&local);
+    assert!(ls[0].get("geometry-templates").is_none());
+    for f in &ls[1..] {
+        let id = f["id"].as_str().unwrap();
+        if id == "b" {
+            assert!(f.get("geometry-templates").is_none());
+            continue;
+        }
+        //-- only the template used, with its 3 vertices
+        let gts = &f["geometry-templates"];
+        assert_eq!(gts["templates"].as_array().unwrap().len(), 1);
+        assert_eq!(gts["vertices-templates"].as_array().unwrap().len(), 3);
+        assert_eq!(f["CityObjects"][id]["geometry"][0]["template"], 0);
+    }
+    //-- collect puts them back in the CityJSON, the result is the same
+    let hash = |seq: &[u8]| {
+        let o = cjseq(&["collect", "--expand-templates"], seq);
+        assert!(o.status.success(), "{}", String::from_utf8_lossy(&o.stderr));
+        let h = cjseq(&["hash"], &o.stdout);
+        assert!(h.status.success() && !h.stdout.is_empty());
+        h.stdout
+    };
+    assert_eq!(hash(&local.stdout), hash(&default.stdout));
+    let o = cjseq(&["collect"], &local.stdout);
+    let j: serde_json::Value = serde_json::from_slice(&o.stdout).unwrap();
+    assert_eq!(
+        j["geometry-templates"]["templates"]
+            .as_array()
+            .unwrap()
+            .len(),
+        1
+    );
+}
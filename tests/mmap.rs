@@ -0,0 +1,27 @@
+//-- the features of a CityJSONSeq read by offset are those read sequentially
+#![cfg(feature = "mmap")]
+
+use cjseq::cityjson::{CityJSON, CityJSONFeature};
+use cjseq::mmap::MappedSeq;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+#[test]
+fn read_the_2nd_feature_by_offset() {
+    let p = Path::new(env!("CARGO_MANIFEST_DIR")).join("data/3dbag_b2.city.jsonl");
+    let s = fs::read_to_string(&p).unwrap();
+    let lines: Vec<&str> = s.lines().filter(|l| !l.trim().is_empty()).collect();
+    let m = MappedSeq::open(&p).unwrap();
+    assert_eq!(m.num_features(), lines.len() - 1);
+    assert_eq!(m.header_line().unwrap(), Some(lines[0]));
+    assert_eq!(m.feature_line(1).unwrap(), Some(lines[2]));
+    let cjf = m.feature(1).unwrap().unwrap();
+    let cjf2 = CityJSONFeature::from_str(lines[2]).unwrap();
+    assert_eq!(cjf.to_value(), cjf2.to_value());
+    assert_eq!(
+        CityJSON::feature_line(&p, 1).unwrap().as_deref(),
+        Some(lines[2])
+    );
+    assert!(m.feature(m.num_features()).unwrap().is_none());
+}
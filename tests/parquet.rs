@@ -0,0 +1,68 @@
+//-- export of the attributes of a CityJSONSeq to Parquet, read back
+#![cfg(feature = "arrow")]
+
+use cjseq::cityjson::CjseqError;
+use cjseq::conv::parquet::to_parquet;
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::Path;
+
+#[test]
+fn parquet_has_one_row_per_feature() {
+    let p = Path::new(env!("CARGO_MANIFEST_DIR")).join("data/3dbag_b2.city.jsonl");
+    let out = std::env::temp_dir().join("cjseq_test_3dbag_b2.parquet");
+    let n = to_parquet(
+        BufReader::new(File::open(&p).unwrap()),
+        File::create(&out).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(n, 2);
+    let r = SerializedFileReader::new(File::open(&out).unwrap()).unwrap();
+    let m = r.metadata().file_metadata();
+    assert_eq!(m.num_rows(), 2);
+    let cols: Vec<&str> = m
+        .schema_descr()
+        .columns()
+        .iter()
+        .map(|c| c.name())
+        .collect();
+    assert_eq!(
+        &cols[..5],
+        &["id", "type", "centroid_x", "centroid_y", "centroid_z"]
+    );
+    assert!(cols.contains(&"b3_h_dak_max"));
+    fs::remove_file(out).unwrap();
+}
+
+#[test]
+fn parquet_of_an_empty_input() {
+    let e = to_parquet(&b""[..], Vec::new()).unwrap_err();
+    assert!(matches!(e, CjseqError::EmptyInput));
+}
+
+#[test]
+fn convert_a_cityjson_to_parquet() {
+    //-- solid_w_texture is a one-line CityJSON, it is not the header of a CityJSONSeq
+    for (name, rows) in [
+        ("solid_w_texture.city.json", 2),
+        ("1b_w_texture.city.json", 1),
+        ("3dbag_b2.city.jsonl", 2),
+    ] {
+        let p = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("data")
+            .join(name);
+        let out = std::env::temp_dir().join(format!("cjseq_test_{}.parquet", name));
+        let o = std::process::Command::new(env!("CARGO_BIN_EXE_cjseq"))
+            .args(["convert", "--format", "parquet", "-i"])
+            .arg(&p)
+            .arg("-o")
+            .arg(&out)
+            .output()
+            .unwrap();
+        assert!(o.status.success(), "{}", String::from_utf8_lossy(&o.stderr));
+        let r = SerializedFileReader::new(File::open(&out).unwrap()).unwrap();
+        assert_eq!(r.metadata().file_metadata().num_rows(), rows, "{}", name);
+        fs::remove_file(out).unwrap();
+    }
+}
@@ -0,0 +1,191 @@
+//-- cat => collect round-trip of all the files in data/ (*.city.json and *.city.jsonl)
+//-- the collected CityJSON must be semantically equal to the original: same CityObjects
+//-- with the same geometries (real coordinates), textures/materials, and attributes
+
+use cjseq::cityjson::{CityJSON, CityJSONFeature};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+fn fixtures() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("data");
+    let mut re: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| {
+            let n = p.file_name().unwrap().to_string_lossy();
+            n.ends_with(".city.json") || n.ends_with(".city.jsonl")
+        })
+        .collect();
+    re.sort();
+    re
+}
+
+fn read_fixture(p: &Path) -> CityJSON {
+    let s = fs::read_to_string(p).unwrap();
+    if p.to_string_lossy().ends_with(".jsonl") {
+        let mut lines = s.lines();
+        let (first, features) = (lines.next().unwrap(), lines);
+        let features: Vec<CityJSONFeature> = features
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| CityJSONFeature::from_str(l).unwrap())
+            .collect();
+        collect(CityJSON::from_str(first).unwrap(), features)
+    } else {
+        CityJSON::from_str(&s).unwrap()
+    }
+}
+
+fn cat(cj: &CityJSON) -> (CityJSON, Vec<CityJSONFeature>) {
+    let mut keys: Vec<&String> = cj
+        .city_objects
+        .iter()
+        .filter(|(_, co)| co.is_toplevel())
+        .map(|(k, _)| k)
+        .collect();
+    keys.sort();
+    let features = keys
+        .into_iter()
+        .map(|k| cj.get_cjfeature(k).unwrap())
+        .collect();
+    (cj.get_metadata(), features)
+}
+
+fn collect(first: CityJSON, features: Vec<CityJSONFeature>) -> CityJSON {
+    let mut cj = first;
+    for f in features {
+        cj.add_one_cjf(f).unwrap();
+    }
+    cj.remove_duplicate_vertices();
+    cj
+}
+
+fn real(cj: &CityJSON, i: usize) -> Value {
+    //-- rounded to the precision of the file, to compare integers and not floats
+    let f = 10f64.powi(cj.transform.decimals() as i32);
+    let p = cj.transform.real_coordinates(&cj.vertices[i]);
+    json!(p
+        .iter()
+        .map(|c| (c * f).round() as i64)
+        .collect::<Vec<i64>>())
+}
+
+fn resolve_boundaries(cj: &CityJSON, b: &Value) -> Value {
+    match b {
+        Value::Array(a) => Value::Array(a.iter().map(|x| resolve_boundaries(cj, x)).collect()),
+        Value::Number(n) => real(cj, n.as_u64().unwrap() as usize),
+        _ => b.clone(),
+    }
+}
+
+fn resolve_texture(cj: &CityJSON, v: &Value) -> Value {
+    //-- each ring [texture, uv, uv, ...] becomes [texture object, [u, v], [u, v], ...]
+    let a = cj.appearance.as_ref().unwrap();
+    match v {
+        Value::Array(x) if x.iter().all(|e| !e.is_array()) => {
+            let mut re: Vec<Value> = Vec::new();
+            for (i, e) in x.iter().enumerate() {
+                match e.as_u64() {
+                    Some(j) if i == 0 => re.push(a.textures.as_ref().unwrap()[j as usize].clone()),
+                    Some(j) => re.push(json!(a.vertices_texture.as_ref().unwrap()[j as usize])),
+                    None => re.push(Value::Null),
+                }
+            }
+            Value::Array(re)
+        }
+        Value::Array(x) => Value::Array(x.iter().map(|e| resolve_texture(cj, e)).collect()),
+        _ => v.clone(),
+    }
+}
+
+fn resolve_material(cj: &CityJSON, v: &Value) -> Value {
+    let mats = cj.appearance.as_ref().unwrap().materials.as_ref().unwrap();
+    match v {
+        Value::Array(x) => Value::Array(x.iter().map(|e| resolve_material(cj, e)).collect()),
+        Value::Number(n) => mats[n.as_u64().unwrap() as usize].clone(),
+        _ => v.clone(),
+    }
+}
+
+fn canonical(cj: &CityJSON) -> BTreeMap<String, Value> {
+    let mut re: BTreeMap<String, Value> = BTreeMap::new();
+    for (id, co) in &cj.city_objects {
+        let mut j = serde_json::to_value(co).unwrap();
+        if let Some(gs) = j.get_mut("geometry").and_then(|g| g.as_array_mut()) {
+            for g in gs {
+                g["boundaries"] = resolve_boundaries(cj, &g["boundaries"]);
+                if let Some(Value::Object(ts)) = g.get_mut("texture") {
+                    for t in ts.values_mut() {
+                        t["values"] = resolve_texture(cj, &t["values"]);
+                    }
+                }
+                if let Some(Value::Object(ms)) = g.get_mut("material") {
+                    for m in ms.values_mut() {
+                        for k in ["values", "value"] {
+                            if let Some(x) = m.get(k) {
+                                m[k] = resolve_material(cj, x);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        re.insert(id.clone(), j);
+    }
+    re
+}
+
+fn count(cj: &CityJSON) -> (usize, usize, usize) {
+    match &cj.appearance {
+        Some(a) => (
+            a.materials.as_ref().map_or(0, |x| x.len()),
+            a.textures.as_ref().map_or(0, |x| x.len()),
+            a.vertices_texture.as_ref().map_or(0, |x| x.len()),
+        ),
+        None => (0, 0, 0),
+    }
+}
+
+#[test]
+fn roundtrip_all_fixtures() {
+    let fs = fixtures();
+    assert!(!fs.is_empty());
+    for p in fs {
+        let name = p.display().to_string();
+        let original = read_fixture(&p);
+        let (first, features) = cat(&original);
+        assert_eq!(features.len(), original.num_features(), "{}", name);
+        let back = collect(first, features);
+
+        let mut ids: Vec<&String> = original.city_objects.keys().collect();
+        let mut ids2: Vec<&String> = back.city_objects.keys().collect();
+        ids.sort();
+        ids2.sort();
+        assert_eq!(ids, ids2, "{}", name);
+        assert_eq!(canonical(&original), canonical(&back), "{}", name);
+
+        let mut dedup = original.clone();
+        dedup.remove_duplicate_vertices();
+        assert_eq!(dedup.vertices.len(), back.vertices.len(), "{}", name);
+        assert_eq!(count(&original).0, count(&back).0, "materials of {}", name);
+        assert_eq!(count(&original).1, count(&back).1, "textures of {}", name);
+        assert_eq!(original.metadata, back.metadata, "{}", name);
+        assert_eq!(original.transform, back.transform, "{}", name);
+        assert_eq!(original.verify_round_trippable(), Ok(()), "{}", name);
+    }
+}
+
+#[test]
+fn verify_round_trippable_reports_mismatch() {
+    //-- a CityObject whose parent doesn't exist is not output by cat
+    let p = Path::new(env!("CARGO_MANIFEST_DIR")).join("data/1b_w_texture.city.json");
+    let mut cj = read_fixture(&p);
+    let k = cj.city_objects.keys().next().unwrap().clone();
+    cj.city_objects.get_mut(&k).unwrap().parents = Some(vec!["nope".to_string()]);
+    assert_eq!(
+        cj.verify_round_trippable(),
+        Err(format!("CityObject #{} is missing after the round-trip", k))
+    );
+}